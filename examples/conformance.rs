@@ -0,0 +1,20 @@
+//! Checks every built-in header frames messages correctly end to end, printing a report.
+//!
+//! `cargo run --example conformance --features conformance`
+
+#[tokio::main]
+async fn main() {
+    let reports = match smalltalk::conformance::run_builtin().await {
+        Ok(reports) => reports,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+    };
+    for report in &reports {
+        println!("{report}\n");
+    }
+    if !reports.iter().all(smalltalk::conformance::Report::passed) {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,352 @@
+//! Derive macro for smalltalk's `IsHeader` trait.
+//!
+//! this is re-exported by smalltalk with the `derive` feature, use it from there
+//! (the generated code refers to `::smalltalk`)
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{spanned::Spanned, Data, DeriveInput, Fields, Ident, LitStr, Type};
+
+/// Derives `IsHeader` for a struct of fixed width fields.
+///
+/// one field must be marked `#[length]`, and holds the length of the message body.
+/// it must be a `u8`, `u16`, `u32` or `u64`. the other fields can be integers (`u8`..`u128`, `i8`..`i128`)
+/// or byte arrays (`[u8; N]`), and are set to zero by `IsHeader::new`.
+///
+/// a `u16` field can be marked `#[kind]`, to hold the kind of the message (see `IsHeader::kind`),
+/// and a `u64` field can be marked `#[sequence]`, to hold its sequence number (see `IsHeader::sequence`).
+///
+/// fields are laid out in the order they are declared, with no padding, so `header_size` is the sum of their sizes.
+/// integers are big-endian by default, put `#[header(endian = "little")]` on the struct
+/// or on a single field to change that.
+///
+/// ```ignore
+/// #[derive(smalltalk::IsHeader)]
+/// #[header(endian = "little")]
+/// struct MyHeader {
+///     version: u8,
+///     #[length]
+///     len: u32,
+///     #[kind]
+///     kind: u16,
+///     #[header(endian = "big")]
+///     flags: u16,
+/// }
+/// ```
+///
+/// # Panics
+/// the generated `new` panics if the message length does not fit in the length field, like `U32Header`.
+/// `try_new` (which is what is used when sending messages) returns `DerivedHeaderError::LengthOverflow` instead
+#[proc_macro_derive(IsHeader, attributes(length, kind, sequence, header))]
+pub fn derive_is_header(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Endian {
+    Big,
+    Little,
+}
+
+/// what a field is, which decides how it is converted to and from bytes
+enum Layout {
+    Int,
+    /// `[u8; len]`
+    Bytes(syn::Expr),
+}
+
+struct Field {
+    ident: Ident,
+    ty: Type,
+    layout: Layout,
+    endian: Endian,
+}
+
+const INTS: &[&str] = &[
+    "u8", "u16", "u32", "u64", "u128", "i8", "i16", "i32", "i64", "i128",
+];
+
+/// types that can be used for the length, they all convert to a `u64` without loss
+const LENGTHS: &[&str] = &["u8", "u16", "u32", "u64"];
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            input.generics.span(),
+            "generic headers can not derive `IsHeader`",
+        ));
+    }
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(
+            input.span(),
+            "`IsHeader` can only be derived for structs",
+        ));
+    };
+    let Fields::Named(named) = &data.fields else {
+        return Err(syn::Error::new(
+            data.fields.span(),
+            "`IsHeader` can only be derived for structs with named fields",
+        ));
+    };
+    let default_endian = endian_attr(&input.attrs)?.unwrap_or(Endian::Big);
+
+    let mut fields = Vec::new();
+    let mut length = None;
+    let mut kind = None;
+    let mut sequence = None;
+    for field in &named.named {
+        // named fields always have a ident
+        let ident = field.ident.clone().unwrap();
+        let layout = layout_of(&field.ty)?;
+        if field.attrs.iter().any(|a| a.path().is_ident("length")) {
+            if length.is_some() {
+                return Err(syn::Error::new(
+                    field.span(),
+                    "only one field can be marked `#[length]`",
+                ));
+            }
+            if !is_one_of(&field.ty, LENGTHS) {
+                return Err(syn::Error::new(
+                    field.ty.span(),
+                    "the `#[length]` field must be a `u8`, `u16`, `u32` or `u64`",
+                ));
+            }
+            length = Some((ident.clone(), field.ty.clone()));
+        }
+        if field.attrs.iter().any(|a| a.path().is_ident("kind")) {
+            if kind.is_some() {
+                return Err(syn::Error::new(
+                    field.span(),
+                    "only one field can be marked `#[kind]`",
+                ));
+            }
+            if !is_one_of(&field.ty, &["u16"]) {
+                return Err(syn::Error::new(
+                    field.ty.span(),
+                    "the `#[kind]` field must be a `u16`",
+                ));
+            }
+            kind = Some(ident.clone());
+        }
+        if field.attrs.iter().any(|a| a.path().is_ident("sequence")) {
+            if sequence.is_some() {
+                return Err(syn::Error::new(
+                    field.span(),
+                    "only one field can be marked `#[sequence]`",
+                ));
+            }
+            if !is_one_of(&field.ty, &["u64"]) {
+                return Err(syn::Error::new(
+                    field.ty.span(),
+                    "the `#[sequence]` field must be a `u64`",
+                ));
+            }
+            sequence = Some(ident.clone());
+        }
+        fields.push(Field {
+            ident,
+            ty: field.ty.clone(),
+            layout,
+            endian: endian_attr(&field.attrs)?.unwrap_or(default_endian),
+        });
+    }
+    let Some((len_ident, len_ty)) = length else {
+        return Err(syn::Error::new(
+            named.span(),
+            "one field must be marked `#[length]`, to hold the length of the message",
+        ));
+    };
+
+    let sizes = fields.iter().map(|f| {
+        let ty = &f.ty;
+        quote! { ::core::mem::size_of::<#ty>() }
+    });
+    let inits = fields.iter().map(|f| {
+        let ident = &f.ident;
+        if *ident == len_ident {
+            quote! {
+                #ident: <#len_ty as ::core::convert::TryFrom<u64>>::try_from(msg_len).map_err(|_| {
+                    ::smalltalk::header::error::DerivedHeaderError::LengthOverflow {
+                        len: msg_len,
+                        field: stringify!(#len_ty),
+                    }
+                })?
+            }
+        } else {
+            match &f.layout {
+                Layout::Int => quote! { #ident: 0 },
+                Layout::Bytes(len) => quote! { #ident: [0u8; #len] },
+            }
+        }
+    });
+    let writes = fields.iter().map(|f| {
+        let ident = &f.ident;
+        match (&f.layout, f.endian) {
+            (Layout::Int, Endian::Big) => {
+                quote! { bytes.extend_from_slice(&self.#ident.to_be_bytes()); }
+            }
+            (Layout::Int, Endian::Little) => {
+                quote! { bytes.extend_from_slice(&self.#ident.to_le_bytes()); }
+            }
+            (Layout::Bytes(_), _) => quote! { bytes.extend_from_slice(&self.#ident); },
+        }
+    });
+    let reads = fields.iter().map(|f| {
+        let ident = &f.ident;
+        let ty = &f.ty;
+        // the total length was checked, so all of these conversions succeed
+        let convert = match (&f.layout, f.endian) {
+            (Layout::Int, Endian::Big) => {
+                quote! { <#ty>::from_be_bytes(field.try_into().unwrap()) }
+            }
+            (Layout::Int, Endian::Little) => {
+                quote! { <#ty>::from_le_bytes(field.try_into().unwrap()) }
+            }
+            (Layout::Bytes(_), _) => {
+                quote! { <#ty as ::core::convert::TryFrom<&[u8]>>::try_from(field).unwrap() }
+            }
+        };
+        quote! {
+            let #ident = {
+                let size = ::core::mem::size_of::<#ty>();
+                let field = &bytes[offset..offset + size];
+                offset += size;
+                #convert
+            };
+        }
+    });
+    let idents = fields.iter().map(|f| &f.ident);
+    let kind_methods = kind.map(|kind| {
+        quote! {
+            fn kind(&self) -> u16 {
+                self.#kind
+            }
+
+            fn set_kind(&mut self, kind: u16) -> Result<(), ::smalltalk::header::error::KindError> {
+                self.#kind = kind;
+                Ok(())
+            }
+        }
+    });
+    let sequence_methods = sequence.map(|sequence| {
+        quote! {
+            fn sequence(&self) -> Option<u64> {
+                Some(self.#sequence)
+            }
+
+            fn set_sequence(&mut self, seq: u64) {
+                self.#sequence = seq;
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl ::smalltalk::header::IsHeader for #name {
+            type Error = ::smalltalk::header::error::DerivedHeaderError;
+
+            fn new(msg_len: u64) -> Self {
+                match <Self as ::smalltalk::header::IsHeader>::try_new(msg_len) {
+                    Ok(header) => header,
+                    Err(e) => panic!("{} (in `{}`)", e, stringify!(#name)),
+                }
+            }
+
+            fn try_new(msg_len: u64) -> Result<Self, Self::Error> {
+                Ok(Self { #(#inits),* })
+            }
+
+            fn size(&self) -> u64 {
+                u64::from(self.#len_ident)
+            }
+
+            #kind_methods
+
+            #sequence_methods
+
+            fn as_bytes(&self) -> ::smalltalk::__private::bytes::Bytes {
+                self.as_bytes_mut().freeze()
+            }
+
+            fn as_bytes_mut(&self) -> ::smalltalk::__private::bytes::BytesMut {
+                let mut bytes = ::smalltalk::__private::bytes::BytesMut::with_capacity(
+                    <Self as ::smalltalk::header::IsHeader>::header_size(),
+                );
+                #(#writes)*
+                bytes
+            }
+
+            #[allow(unused_assignments)]
+            fn from_bytes(bytes: ::smalltalk::__private::bytes::Bytes) -> Result<Self, Self::Error> {
+                let expected = <Self as ::smalltalk::header::IsHeader>::header_size();
+                if bytes.len() != expected {
+                    return Err(::smalltalk::header::error::DerivedHeaderError::WrongByteCount {
+                        expected,
+                        actual: bytes.len(),
+                    });
+                }
+                let mut offset = 0;
+                #(#reads)*
+                Ok(Self { #(#idents),* })
+            }
+
+            fn header_size() -> usize {
+                0 #(+ #sizes)*
+            }
+        }
+    })
+}
+
+/// Gets the endianness set with `#[header(endian = "...")]`, if any
+fn endian_attr(attrs: &[syn::Attribute]) -> syn::Result<Option<Endian>> {
+    let mut endian = None;
+    for attr in attrs.iter().filter(|a| a.path().is_ident("header")) {
+        attr.parse_nested_meta(|meta| {
+            if !meta.path.is_ident("endian") {
+                return Err(meta.error("unknown header attribute, expected `endian`"));
+            }
+            let value: LitStr = meta.value()?.parse()?;
+            endian = Some(match value.value().as_str() {
+                "big" => Endian::Big,
+                "little" => Endian::Little,
+                _ => {
+                    return Err(syn::Error::new(
+                        value.span(),
+                        "expected `\"big\"` or `\"little\"`",
+                    ))
+                }
+            });
+            Ok(())
+        })?;
+    }
+    Ok(endian)
+}
+
+fn layout_of(ty: &Type) -> syn::Result<Layout> {
+    if is_one_of(ty, INTS) {
+        return Ok(Layout::Int);
+    }
+    if let Type::Array(array) = ty {
+        if is_one_of(&array.elem, &["u8"]) {
+            return Ok(Layout::Bytes(array.len.clone()));
+        }
+    }
+    Err(syn::Error::new(
+        ty.span(),
+        "header fields must be integers (`u8`..`u128`, `i8`..`i128`) or byte arrays (`[u8; N]`)",
+    ))
+}
+
+/// Returns if `ty` is a plain path to one of `names`
+fn is_one_of(ty: &Type, names: &[&str]) -> bool {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => path
+            .path
+            .get_ident()
+            .is_some_and(|ident| names.iter().any(|name| ident == name)),
+        _ => false,
+    }
+}
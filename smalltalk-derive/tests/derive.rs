@@ -0,0 +1,102 @@
+//! headers made with `#[derive(IsHeader)]` round trip, in both byte orders
+
+use smalltalk::{header::error::DerivedHeaderError, IsHeader};
+
+#[derive(IsHeader, Debug, Clone, Copy, PartialEq, Eq)]
+struct BigHeader {
+    version: u8,
+    #[length]
+    len: u32,
+    #[kind]
+    kind: u16,
+    #[sequence]
+    seq: u64,
+    tag: [u8; 3],
+}
+
+#[derive(IsHeader, Debug, Clone, Copy, PartialEq, Eq)]
+#[header(endian = "little")]
+struct LittleHeader {
+    #[length]
+    len: u16,
+    #[header(endian = "big")]
+    flags: u16,
+    offset: i32,
+}
+
+#[test]
+fn big_endian_round_trip() {
+    let mut header = BigHeader::new(0x0102_0304);
+    header.version = 9;
+    header.set_kind(0x0506).unwrap();
+    header.set_sequence(7);
+    header.tag = *b"abc";
+    assert_eq!(BigHeader::header_size(), 1 + 4 + 2 + 8 + 3);
+
+    let bytes = header.as_bytes();
+    assert_eq!(
+        &bytes[..],
+        &[9, 1, 2, 3, 4, 5, 6, 0, 0, 0, 0, 0, 0, 0, 7, b'a', b'b', b'c']
+    );
+    let parsed = BigHeader::from_bytes(bytes).unwrap();
+    assert_eq!(parsed, header);
+    assert_eq!(parsed.size(), 0x0102_0304);
+    assert_eq!(parsed.kind(), 0x0506);
+    assert_eq!(parsed.sequence(), Some(7));
+}
+
+#[test]
+fn little_endian_round_trip() {
+    let mut header = LittleHeader::new(0x0102);
+    header.flags = 0x0304;
+    header.offset = -2;
+    assert_eq!(LittleHeader::header_size(), 2 + 2 + 4);
+
+    let bytes = header.as_bytes();
+    // the field marked big-endian overrides the struct
+    assert_eq!(&bytes[..], &[2, 1, 3, 4, 0xFE, 0xFF, 0xFF, 0xFF]);
+    assert_eq!(LittleHeader::from_bytes(bytes).unwrap(), header);
+    // no `#[kind]` or `#[sequence]`, so the defaults are used
+    assert_eq!(header.kind(), 0);
+    assert!(header.sequence().is_none());
+    assert!(LittleHeader::new(0).set_kind(1).is_err());
+}
+
+#[test]
+fn wrong_byte_count() {
+    let bytes = LittleHeader::new(1).as_bytes();
+    assert_eq!(
+        LittleHeader::from_bytes(bytes.slice(1..)).unwrap_err(),
+        DerivedHeaderError::WrongByteCount {
+            expected: 8,
+            actual: 7
+        }
+    );
+}
+
+#[test]
+fn lengths_that_do_not_fit_are_an_error() {
+    assert_eq!(
+        LittleHeader::try_new(u64::from(u16::MAX) + 1).unwrap_err(),
+        DerivedHeaderError::LengthOverflow {
+            len: u64::from(u16::MAX) + 1,
+            field: "u16"
+        }
+    );
+    assert_eq!(
+        LittleHeader::try_new(u64::from(u16::MAX)).unwrap().size(),
+        u64::from(u16::MAX)
+    );
+}
+
+#[test]
+#[should_panic(expected = "in `LittleHeader`")]
+fn new_panics_on_overflow() {
+    let _ = LittleHeader::new(u64::MAX);
+}
+
+#[test]
+fn compile_errors() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,8 @@
+#[derive(smalltalk::IsHeader)]
+#[header(endian = "middle")]
+struct Header {
+    #[length]
+    len: u32,
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+#[derive(smalltalk::IsHeader)]
+struct Header {
+    #[length]
+    len: i32,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+#[derive(smalltalk::IsHeader)]
+struct Header {
+    #[length]
+    len: u32,
+    #[kind]
+    kind: u16,
+    #[kind]
+    other_kind: u16,
+}
+
+fn main() {}
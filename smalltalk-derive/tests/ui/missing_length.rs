@@ -0,0 +1,6 @@
+#[derive(smalltalk::IsHeader)]
+struct Header {
+    len: u32,
+}
+
+fn main() {}
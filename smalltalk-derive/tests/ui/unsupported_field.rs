@@ -0,0 +1,8 @@
+#[derive(smalltalk::IsHeader)]
+struct Header {
+    #[length]
+    len: u32,
+    name: String,
+}
+
+fn main() {}
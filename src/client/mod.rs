@@ -1,73 +1,361 @@
-use std::{fmt::Debug, net::SocketAddr, ops::{Deref, DerefMut}};
+use std::{collections::VecDeque, fmt::Debug, net::SocketAddr, ops::{Deref, DerefMut}};
 
 use serde::{de::DeserializeOwned, Serialize};
 use tokio::net::TcpStream;
 
-use crate::socket::interface::SocketUtils;
+use crate::socket::{interface::SocketUtils, ReadOnlyConnection};
+
+mod reconnect;
+pub use reconnect::{EventHook, ReconnectConfig, ReconnectEvent, ReconnectingClient};
 
 pub mod error {
     use std::fmt::Debug;
 
     #[derive(Debug, thiserror::Error)]
-    #[error("Failed to connect!\n{err}")]
-    pub struct ConnectError {
-        #[from]
-        #[source]
-        err: std::io::Error,
+    pub enum ConnectError {
+        #[error("Failed to connect!\n{0}")]
+        Io(#[from] std::io::Error),
+        #[error("Failed to connect within {0:?}!")]
+        Timeout(std::time::Duration),
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    pub enum SessionConnectError<H: crate::header::IsHeader + Debug> {
+        #[error("Failed to connect!\n{0}")]
+        Connect(#[from] ConnectError),
+        #[error("Handshake failed!\n{0}")]
+        Handshake(#[from] crate::session::error::HandshakeError<H>),
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    pub enum SendAndCloseError {
+        #[error("Failed to connect!\n{0}")]
+        Connect(#[from] ConnectError),
+        #[error("Failed to queue message!\n{0}")]
+        Queue(#[from] crate::socket::write::error::QueueError),
+        #[error("Failed to send message!\n{0}")]
+        Write(#[from] crate::socket::write::error::WriteError),
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    pub enum ReconnectError<H: crate::header::IsHeader + Debug> {
+        #[error("Gave up reconnecting after {attempts} attempts!\n{source}")]
+        GaveUp {
+            attempts: u32,
+            #[source]
+            source: SessionConnectError<H>,
+        },
+        #[error("Connection failed!\n{0}")]
+        Connection(crate::socket::interface::error::WaitMessageError<H>),
+        #[error("Failed to send data!\n{0}")]
+        Write(crate::socket::write::error::WriteError),
+        #[error("Failed to take unsent messages from the old connection!\n{0}")]
+        TakeUnsent(#[from] std::io::Error),
+        #[error("Failed to replay unsent messages on the new connection!\n{0}")]
+        Replay(crate::socket::write::error::QueueError),
+        #[error("Failed to carry settings over to the new connection!\n{0}")]
+        Settings(std::io::Error),
     }
 }
 
 
+/// A queue of messages to send, that is independent of any [`Client`].
+///
+/// this lets messages outlive the client they were meant to be sent with,
+/// for example when a client has to be fully recreated after it fails.
+/// a new client sends the messages with [`Client::attach_queue`]
+pub struct PersistentSendQueue<M, H>
+where
+    M: Serialize,
+{
+    messages: VecDeque<crate::msg::MessageWrapper<M, H>>,
+}
+
+impl<M, H> PersistentSendQueue<M, H>
+where
+    M: Serialize,
+    H: crate::header::IsHeader,
+{
+    /// Creates a new, empty, queue
+    pub fn new() -> Self {
+        Self {
+            messages: VecDeque::new(),
+        }
+    }
+
+    /// Adds a message to the back of the queue
+    pub fn push(&mut self, message: crate::msg::MessageWrapper<M, H>) {
+        self.messages.push_back(message);
+    }
+
+    /// Gets the number of messages in the queue
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Returns if there are no messages in the queue
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+impl<M, H> Default for PersistentSendQueue<M, H>
+where
+    M: Serialize,
+    H: crate::header::IsHeader,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// A basic interface to represent a client,
 /// with methods for sending and reiving normal rust types
-pub struct Client<H, M, O>
+pub struct Client<H, M, C>
 where
     H: crate::header::IsHeader,
     M: Serialize + DeserializeOwned,
-    O: bincode::Options + Clone,
+    C: crate::codec::Codec + Clone,
 {
-    sock_interface: SocketUtils<H, M, O>
+    sock_interface: SocketUtils<H, M, C>
 }
 
-impl<H, M, O> Client<H, M, O>
+impl<H, M, C> Client<H, M, C>
 where
     H: crate::header::IsHeader + Clone + Debug,
     M: Serialize + DeserializeOwned,
-    O: bincode::Options + Clone,
+    C: crate::codec::Codec + Clone,
 {
     /// Creates a new [`Client`], connecting to `addr`
     ///
     /// # Args
-    /// `bincode_opts` is used for serializing and deserializing messages, see [`Options`] for more info
+    /// `codec` is used for serializing and deserializing messages, see [`Codec`] for more info
     ///
-    /// [`Options`]: bincode::Options
-    pub async fn connect(addr: SocketAddr, bincode_opts: O) -> Result<Self, error::ConnectError> {
+    /// [`Codec`]: crate::codec::Codec
+    pub async fn connect(addr: SocketAddr, codec: C) -> Result<Self, error::ConnectError> {
         let (read_half, write_half) =
-            crate::socket::split_stream(TcpStream::connect(addr).await?, bincode_opts);
+            crate::socket::split_stream(TcpStream::connect(addr).await?, codec);
         let sock_interface = SocketUtils::new(read_half, write_half, addr);
         Ok(Self {
             sock_interface,
         })
     }
+
+    /// Creates a new [`Client`], connecting to `addr` and setting the options in `config` on the socket
+    ///
+    /// # Errors
+    /// if connecting failed, or a option could not be set
+    pub async fn connect_with_config(
+        addr: SocketAddr,
+        codec: C,
+        config: &crate::socket::SocketConfig,
+    ) -> Result<Self, error::ConnectError> {
+        let (read_half, write_half) = crate::socket::split_stream_with_config(
+            TcpStream::connect(addr).await?,
+            codec,
+            config,
+        )?;
+        Ok(Self {
+            sock_interface: SocketUtils::new(read_half, write_half, addr),
+        })
+    }
+
+    /// Creates a new [`Client`], connecting to `addr`, but giving up if the connection has not been made after `timeout`
+    ///
+    /// this is the same as [`connect`], for when the peer may not respond at all (like a host that drops packets)
+    ///
+    /// # Errors
+    /// if connecting failed, or took longer than `timeout` ([`ConnectError::Timeout`])
+    ///
+    /// [`connect`]: Client::connect
+    /// [`ConnectError::Timeout`]: error::ConnectError::Timeout
+    pub async fn connect_timeout(
+        addr: SocketAddr,
+        codec: C,
+        timeout: std::time::Duration,
+    ) -> Result<Self, error::ConnectError> {
+        tokio::time::timeout(timeout, Self::connect(addr, codec))
+            .await
+            .map_err(|_| error::ConnectError::Timeout(timeout))?
+    }
+
+    /// Creates a new [`Client`], connecting to `addr` and starting or resuming a session.
+    ///
+    /// if `resume` is the token from a previous session, the server will try to resume it.
+    /// see the [`session`] module for more info
+    ///
+    /// # Errors
+    /// if connecting or the handshake failed
+    ///
+    /// [`session`]: crate::session
+    pub async fn connect_session(
+        addr: SocketAddr,
+        codec: C,
+        resume: Option<crate::session::ResumeToken>,
+    ) -> Result<(Self, crate::session::SessionStart), error::SessionConnectError<H>> {
+        Self::connect_session_with_config(addr, codec, resume, &crate::socket::SocketConfig::default()).await
+    }
+
+    /// Same as [`connect_session`], but setting the options in `config` on the socket before the handshake
+    ///
+    /// # Errors
+    /// if connecting or the handshake failed, or a option could not be set
+    ///
+    /// [`connect_session`]: Client::connect_session
+    pub async fn connect_session_with_config(
+        addr: SocketAddr,
+        codec: C,
+        resume: Option<crate::session::ResumeToken>,
+        config: &crate::socket::SocketConfig,
+    ) -> Result<(Self, crate::session::SessionStart), error::SessionConnectError<H>> {
+        let mut stream = TcpStream::connect(addr)
+            .await
+            .map_err(error::ConnectError::from)?;
+        config.apply(&stream).map_err(error::ConnectError::from)?;
+        let start = crate::session::client_handshake(&mut stream, &codec, resume).await?;
+        let (read_half, write_half) = crate::socket::split_stream(stream, codec);
+        Ok((
+            Self {
+                sock_interface: SocketUtils::new(read_half, write_half, addr),
+            },
+            start,
+        ))
+    }
+
+    /// Checks that messages serialize correctly with `codec`, by serializing `sample`.
+    ///
+    /// this does no io, so it can be used to catch serde issues or misconfigured options
+    /// before opening a connection
+    ///
+    /// # Returns
+    /// the serialized size of `sample` (not including the header)
+    ///
+    /// # Errors
+    /// if `sample` could not be serialized
+    pub fn validate_options(sample: &M, codec: &C) -> Result<u64, crate::codec::error::CodecError> {
+        let serialized = codec.serialize(sample)?;
+        Ok(serialized.len() as u64)
+    }
+
+    /// Connects to `addr`, creating a [`ReadOnlyConnection`] instead of a [`Client`]
+    ///
+    /// this is for clients that never send anything, see [`ReadOnlyConnection`] for more info
+    ///
+    /// # Args
+    /// `codec` is used for deserializing messages, see [`Codec`] for more info
+    ///
+    /// [`Codec`]: crate::codec::Codec
+    pub async fn connect_read_only(
+        addr: SocketAddr,
+        codec: C,
+    ) -> Result<ReadOnlyConnection<H, M, C>, error::ConnectError> {
+        let (read_half, write_half) = TcpStream::connect(addr).await?.into_split();
+        Ok(ReadOnlyConnection::new(
+            crate::socket::Reader::new(read_half, codec),
+            write_half,
+            addr,
+        ))
+    }
+
+    /// Creates a new [`Client`], resolving `host` and connecting to it on `port`
+    ///
+    /// each address `host` resolves to is tried in order, and the first one that connects is used.
+    /// the address that was used can be retreived with [`addr`]
+    ///
+    /// # Args
+    /// `codec` is used for serializing and deserializing messages, see [`Codec`] for more info
+    ///
+    /// # Errors
+    /// if `host` could not be resolved, or none of the addresses it resolved to could be connected to
+    ///
+    /// [`addr`]: crate::socket::interface::_SocketUtils::addr
+    /// [`Codec`]: crate::codec::Codec
+    pub async fn connect_host(host: &str, port: u16, codec: C) -> Result<Self, error::ConnectError> {
+        let mut last_err = None;
+        for addr in tokio::net::lookup_host((host, port)).await? {
+            match TcpStream::connect(addr).await {
+                Ok(stream) => {
+                    let (read_half, write_half) = crate::socket::split_stream(stream, codec);
+                    return Ok(Self {
+                        sock_interface: SocketUtils::new(read_half, write_half, addr),
+                    });
+                }
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err
+            .unwrap_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("{host} did not resolve to any addresses"),
+                )
+            })
+            .into())
+    }
+
+    /// Connects to `addr`, sends a single message, then cleanly closes the connection.
+    ///
+    /// this is for one-shot, fire-and-forget notifications.
+    /// the function returns once the message has been written and the connection shut down
+    ///
+    /// # Errors
+    /// if connecting, serializing the message, or sending it failed
+    pub async fn send_and_close(
+        addr: SocketAddr,
+        codec: C,
+        message: &crate::msg::MessageWrapper<M, H>,
+    ) -> Result<(), error::SendAndCloseError> {
+        let mut client = Self::connect(addr, codec).await?;
+        client.queue_message(message)?;
+        client.close_after_flush().await?;
+        Ok(())
+    }
+
+    /// Consumes the client, recovering the raw `TcpStream`.
+    ///
+    /// for more info see [`SocketUtils::into_stream`]
+    ///
+    /// # Errors
+    /// if the halfs of the socket could not be joined
+    ///
+    /// [`SocketUtils::into_stream`]: crate::socket::interface::_SocketUtils::into_stream
+    pub fn into_stream(self) -> Result<(TcpStream, bytes::BytesMut), tokio::net::tcp::ReuniteError> {
+        self.sock_interface.into_stream()
+    }
+
+    /// Drains all messages from a [`PersistentSendQueue`], queueing them to be sent by this client.
+    ///
+    /// # Errors
+    /// if a message could not be serialized, or the send queue is full. it and all messages after it are left in `queue`
+    pub fn attach_queue(
+        &mut self,
+        queue: &mut PersistentSendQueue<M, H>,
+    ) -> Result<(), crate::socket::write::error::QueueError> {
+        while let Some(message) = queue.messages.front() {
+            self.sock_interface.queue_message(message)?;
+            queue.messages.pop_front();
+        }
+        Ok(())
+    }
 }
 
-impl<H, M, O> Deref for Client<H, M, O>
+impl<H, M, C> Deref for Client<H, M, C>
 where
     H: crate::header::IsHeader + Debug + Clone,
     M: Serialize + DeserializeOwned,
-    O: bincode::Options + Clone,
+    C: crate::codec::Codec + Clone,
 {
-    type Target = SocketUtils<H, M, O>;
+    type Target = SocketUtils<H, M, C>;
     fn deref(&self) -> &Self::Target {
         &self.sock_interface
     }
 }
 
-impl<H, M, O> DerefMut for Client<H, M, O>
+impl<H, M, C> DerefMut for Client<H, M, C>
 where
     H: crate::header::IsHeader + Debug + Clone,
     M: Serialize + DeserializeOwned,
-    O: bincode::Options + Clone,
+    C: crate::codec::Codec + Clone,
 {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.sock_interface
@@ -0,0 +1,331 @@
+use std::{fmt::Debug, net::SocketAddr, time::Duration};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{error, Client};
+use crate::socket::interface::{error::WaitMessageError, is_write_disconnect};
+
+/// Settings for how a [`ReconnectingClient`] re-dials after losing its connection
+#[derive(Debug, Clone)]
+pub struct ReconnectConfig {
+    /// delay before the first reconnection attempt. each failed attempt doubles it
+    pub base_delay: Duration,
+    /// the delay between attempts never grows past this
+    pub max_delay: Duration,
+    /// how many attempts to make before giving up, or `None` to keep trying forever
+    pub max_attempts: Option<u32>,
+    /// if messages that were queued but not sent on the old connection should be sent on the new one.
+    ///
+    /// a message that was only partially written is always dropped, as there is no way to tell how much of it
+    /// the peer got
+    pub replay_unsent: bool,
+    /// options set on the socket of every connection, including the first one
+    pub socket: crate::socket::SocketConfig,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            max_attempts: Some(10),
+            replay_unsent: true,
+            socket: crate::socket::SocketConfig::default(),
+        }
+    }
+}
+
+impl ReconnectConfig {
+    /// delay before attempt number `attempt` (starting from 1)
+    fn delay_for(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.base_delay
+            .checked_mul(factor)
+            .map_or(self.max_delay, |d| d.min(self.max_delay))
+    }
+}
+
+/// Reported by a [`ReconnectingClient`] through the callback set with [`set_on_event`]
+///
+/// [`set_on_event`]: ReconnectingClient::set_on_event
+#[derive(Debug, Clone)]
+pub enum ReconnectEvent {
+    /// the connection was lost
+    Disconnected,
+    /// a message was part way through being received when the connection was lost.
+    /// the `bytes` of it that were received are dropped, and reading starts fresh on the new connection
+    FrameLostOnReconnect { bytes: usize },
+    /// about to wait `delay`, then try to connect for the `attempt`th time
+    Attempt { attempt: u32, delay: Duration },
+    /// a new connection was made after `attempts` tries. `replayed` messages were moved to it,
+    /// and `dropped_partial` is if a partially written message had to be dropped.
+    /// `resumed` is if the previous session was resumed (always false if the client does not use sessions)
+    Reconnected {
+        attempts: u32,
+        replayed: usize,
+        dropped_partial: bool,
+        resumed: bool,
+    },
+    /// all attempts failed, the client will not try again
+    GaveUp { attempts: u32 },
+}
+
+/// Callback used by [`ReconnectingClient::set_on_event`]
+pub type EventHook = Box<dyn FnMut(&ReconnectEvent) + Send>;
+
+/// A [`Client`] that automatically re-dials its address when the connection is lost.
+///
+/// the connection counts as lost when the peer closes it, or reading or writing fails with a
+/// disconnection error (reset, aborted, broken pipe, etc). attempts are spaced out with exponential backoff,
+/// see [`ReconnectConfig`]
+///
+/// ## Settings
+/// each new connection gets the settings of the one it replaces: reader limits and hooks,
+/// the writers capacity, flush interval and spillover, heartbeats and the max lifetime
+/// (see [`set_heartbeat`] and friends on the [`client`]). socket options come from [`ReconnectConfig::socket`].
+/// buffer sizes set with [`set_recv_buffer_size`] and [`set_send_buffer_size`] are not carried over,
+/// as the OS adjusts them (so the effective size can not be set again as is)
+///
+/// ## Ordering
+/// every new connection starts reading at a frame boundary, so data from the old connection can never
+/// be mixed up with data from the new one. if a message was part way through being received when the
+/// connection was lost, it is dropped and reported with [`ReconnectEvent::FrameLostOnReconnect`].
+/// the peer has to resend it (for example using the [`session`] it resumed) if it matters
+///
+/// [`session`]: crate::session
+/// [`set_heartbeat`]: crate::socket::interface::_SocketUtils::set_heartbeat
+/// [`client`]: ReconnectingClient::client
+/// [`set_recv_buffer_size`]: crate::socket::interface::_SocketUtils::set_recv_buffer_size
+/// [`set_send_buffer_size`]: crate::socket::interface::_SocketUtils::set_send_buffer_size
+pub struct ReconnectingClient<H, M, C>
+where
+    H: crate::header::IsHeader + Clone + Debug,
+    M: Serialize + DeserializeOwned,
+    C: crate::codec::Codec + Clone,
+{
+    addr: SocketAddr,
+    codec: C,
+    config: ReconnectConfig,
+    client: Client<H, M, C>,
+    /// token of the current session, if the client was created with [`connect_session`]
+    ///
+    /// [`connect_session`]: ReconnectingClient::connect_session
+    session: Option<crate::session::ResumeToken>,
+    on_event: Option<EventHook>,
+}
+
+impl<H, M, C> ReconnectingClient<H, M, C>
+where
+    H: crate::header::IsHeader + Clone + Debug,
+    M: Serialize + DeserializeOwned,
+    C: crate::codec::Codec + Clone,
+{
+    /// Connects to `addr`. the first connection is not retried, if it fails the error is returned
+    ///
+    /// # Errors
+    /// if the initial connection could not be made
+    pub async fn connect(
+        addr: SocketAddr,
+        codec: C,
+        config: ReconnectConfig,
+    ) -> Result<Self, error::ConnectError> {
+        let client = Client::connect_with_config(addr, codec.clone(), &config.socket).await?;
+        Ok(Self {
+            addr,
+            codec,
+            config,
+            client,
+            session: None,
+            on_event: None,
+        })
+    }
+
+    /// Connects to `addr` and starts a session, which is resumed every time the client reconnects.
+    /// see the [`session`] module for more info
+    ///
+    /// # Errors
+    /// if the initial connection or handshake failed
+    ///
+    /// [`session`]: crate::session
+    pub async fn connect_session(
+        addr: SocketAddr,
+        codec: C,
+        config: ReconnectConfig,
+    ) -> Result<Self, error::SessionConnectError<H>> {
+        let (client, start) =
+            Client::connect_session_with_config(addr, codec.clone(), None, &config.socket).await?;
+        Ok(Self {
+            addr,
+            codec,
+            config,
+            client,
+            session: Some(start.token),
+            on_event: None,
+        })
+    }
+
+    /// Token of the current session, if the client uses sessions
+    pub fn session(&self) -> Option<crate::session::ResumeToken> {
+        self.session
+    }
+
+    /// Sets a callback that is called with every [`ReconnectEvent`]
+    pub fn set_on_event(&mut self, on_event: impl FnMut(&ReconnectEvent) + Send + 'static) {
+        self.on_event = Some(Box::new(on_event));
+    }
+
+    pub fn config(&self) -> &ReconnectConfig {
+        &self.config
+    }
+
+    pub fn config_mut(&mut self) -> &mut ReconnectConfig {
+        &mut self.config
+    }
+
+    /// The current connection. this is replaced every time the client reconnects
+    pub fn client(&self) -> &Client<H, M, C> {
+        &self.client
+    }
+
+    pub fn client_mut(&mut self) -> &mut Client<H, M, C> {
+        &mut self.client
+    }
+
+    /// Queues a message to be sent on the current connection
+    ///
+    /// # Errors
+    /// if the message could not be serialized, or the send queue is full
+    pub fn queue_message(
+        &mut self,
+        message: &crate::msg::MessageWrapper<M, H>,
+    ) -> Result<(), crate::socket::write::error::QueueError> {
+        self.client.queue_message(message)
+    }
+
+    /// Waits for a message, reconnecting as many times as needed.
+    ///
+    /// # Errors
+    /// if reconnecting gave up, or the connection failed in a way that is not a disconnection
+    /// (like a message failing to deserialize)
+    pub async fn wait_for_message(
+        &mut self,
+    ) -> Result<crate::msg::MessageWrapper<M, H>, error::ReconnectError<H>> {
+        loop {
+            match self.client.wait_for_message().await {
+                Ok(m) => return Ok(m),
+                Err(e) if is_lost(&e) => self.reconnect().await?,
+                Err(e) => return Err(error::ReconnectError::Connection(e)),
+            }
+        }
+    }
+
+    /// Writes everything that is queued, reconnecting as many times as needed.
+    ///
+    /// # Errors
+    /// if reconnecting gave up, or writing failed with an error that is not a disconnection
+    pub async fn flush_all(&mut self) -> Result<usize, error::ReconnectError<H>> {
+        loop {
+            match self.client.flush_all().await {
+                Ok(n) => return Ok(n),
+                Err(e) if is_write_disconnect(&e) => self.reconnect().await?,
+                Err(e) => return Err(error::ReconnectError::Write(e)),
+            }
+        }
+    }
+
+    fn emit(&mut self, event: &ReconnectEvent) {
+        if let Some(on_event) = &mut self.on_event {
+            on_event(event);
+        }
+    }
+
+    /// Re-dials the address untill it connects or runs out of attempts, replacing `client`
+    async fn reconnect(&mut self) -> Result<(), error::ReconnectError<H>> {
+        self.emit(&ReconnectEvent::Disconnected);
+        let reader = self.client.as_reader();
+        if !reader.at_frame_boundary() {
+            let bytes = reader.partial_frame_bytes();
+            self.emit(&ReconnectEvent::FrameLostOnReconnect { bytes });
+        }
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let delay = self.config.delay_for(attempt);
+            self.emit(&ReconnectEvent::Attempt { attempt, delay });
+            tokio::time::sleep(delay).await;
+            let connected = match self.session {
+                Some(token) => Client::connect_session_with_config(
+                    self.addr,
+                    self.codec.clone(),
+                    Some(token),
+                    &self.config.socket,
+                )
+                .await
+                    .map(|(new, start)| {
+                        self.session = Some(start.token);
+                        (new, start.resumed)
+                    }),
+                None => Client::connect_with_config(self.addr, self.codec.clone(), &self.config.socket)
+                    .await
+                    .map(|new| (new, false))
+                    .map_err(error::SessionConnectError::from),
+            };
+            match connected {
+                Ok((new, resumed)) => {
+                    let mut old = std::mem::replace(&mut self.client, new);
+                    let (replayed, dropped_partial) = if self.config.replay_unsent {
+                        let (unsent, dropped_partial) = old.as_writer_mut().take_unsent()?;
+                        let replayed = unsent.len();
+                        for bytes in unsent {
+                            // already framed, and the new connection has no capacity or spillover yet,
+                            // so this should not fail
+                            self.client
+                                .as_writer_mut()
+                                .queue_raw(bytes)
+                                .map_err(error::ReconnectError::Replay)?;
+                        }
+                        (replayed, dropped_partial)
+                    } else {
+                        (0, false)
+                    };
+                    // after replaying, so replayed messages are not held to the capacity
+                    self.client
+                        .sock_interface
+                        .take_settings(&mut old.sock_interface)
+                        .map_err(error::ReconnectError::Settings)?;
+                    self.emit(&ReconnectEvent::Reconnected {
+                        attempts: attempt,
+                        replayed,
+                        dropped_partial,
+                        resumed,
+                    });
+                    return Ok(());
+                }
+                Err(source) => {
+                    if self.config.max_attempts.is_some_and(|max| attempt >= max) {
+                        self.emit(&ReconnectEvent::GaveUp { attempts: attempt });
+                        return Err(error::ReconnectError::GaveUp {
+                            attempts: attempt,
+                            source,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns if an error from waiting for a message means the connection was lost
+fn is_lost<H: crate::header::IsHeader + Debug>(err: &WaitMessageError<H>) -> bool {
+    use crate::socket::interface::error::UpdateError;
+    match err {
+        WaitMessageError::Closed => true,
+        WaitMessageError::Read(e) => crate::socket::interface::is_disconnect(e),
+        WaitMessageError::Update(UpdateError::Closed | UpdateError::Dead { .. }) => true,
+        WaitMessageError::Update(UpdateError::Read(e)) => {
+            crate::socket::interface::is_disconnect(e)
+        }
+        WaitMessageError::Update(UpdateError::Write(e)) => is_write_disconnect(e),
+        WaitMessageError::Update(UpdateError::ReadUpdate(_)) => false,
+    }
+}
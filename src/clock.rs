@@ -0,0 +1,80 @@
+//! Where the time based features (heartbeats, max lifetimes, rate limits) get the current time from.
+//!
+//! by default this is [`TokioClock`], which follows `tokio::time::pause` and `tokio::time::advance`,
+//! so tests with a paused runtime can move time forward without waiting.
+//! a [`ManualClock`] can be set instead (see [`SocketUtils::set_clock`]) to control time directly.
+//!
+//! only checks of how much time has passed go through the clock, waiting always uses tokio's timers:
+//! things that wait (like [`wait_for_message`] sending heartbeats) wait for the remaining time as measured
+//! by the clock, then check again. so with a [`ManualClock`], advancing it is seen the next time anything is checked
+//!
+//! [`SocketUtils::set_clock`]: crate::socket::interface::_SocketUtils::set_clock
+//! [`wait_for_message`]: crate::socket::interface::_SocketUtils::wait_for_message
+
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+/// A source of the current time
+pub trait Clock: Debug + Send + Sync {
+    /// Gets the current time
+    fn now(&self) -> Instant;
+}
+
+/// The clock shared by everything that uses it
+pub type SharedClock = Arc<dyn Clock>;
+
+/// The current time from tokio, see the [module docs](self)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokioClock;
+
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when [`advance`] is called.
+///
+/// clones share the same time, so one can be given to a connection and the other kept to control it
+///
+/// [`advance`]: ManualClock::advance
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl ManualClock {
+    /// Creates a new clock, starting at the current time
+    pub fn new() -> Self {
+        Self {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Moves the clock forward by `by`
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// The clock used when none is set
+pub(crate) fn default_clock() -> SharedClock {
+    Arc::new(TokioClock)
+}
@@ -0,0 +1,276 @@
+use std::io::Write;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+mod depth;
+
+pub mod error {
+    /// A error from serializing or deserializing a message, produced by a [`Codec`]
+    ///
+    /// [`Codec`]: super::Codec
+    #[derive(thiserror::Error, Debug)]
+    #[error(transparent)]
+    pub struct CodecError(Box<dyn std::error::Error + Send + Sync>);
+
+    impl CodecError {
+        /// Creates a new error, from the error produced by the underlying format
+        pub fn new(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+            Self(err.into())
+        }
+
+        /// Gets the error produced by the underlying format
+        pub fn into_inner(self) -> Box<dyn std::error::Error + Send + Sync> {
+            self.0
+        }
+    }
+
+    impl From<bincode::Error> for CodecError {
+        fn from(err: bincode::Error) -> Self {
+            Self::new(err)
+        }
+    }
+
+    impl From<std::io::Error> for CodecError {
+        fn from(err: std::io::Error) -> Self {
+            Self::new(err)
+        }
+    }
+
+    /// Produced (inside a [`CodecError`]) by [`DepthLimited`] when a value is nested too deeply
+    ///
+    /// [`DepthLimited`]: super::DepthLimited
+    #[derive(thiserror::Error, Debug)]
+    #[error("Value is nested more than {limit} levels deep")]
+    pub struct DepthLimitExceeded {
+        pub limit: usize,
+    }
+}
+
+/// A format messages are serialized with (the body of each message, the header is always encoded by [`IsHeader`]).
+///
+/// the crate ships [`BincodeCodec`], other formats (json, postcard, etc.) can be used by implementing this
+///
+/// [`IsHeader`]: crate::header::IsHeader
+pub trait Codec {
+    /// Serializes `value` into a new buffer
+    ///
+    /// # Errors
+    /// if `value` could not be serialized
+    fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, error::CodecError>;
+
+    /// Deserializes a value from `bytes`
+    ///
+    /// # Errors
+    /// if `bytes` did not contain a valid `T`
+    fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, error::CodecError>;
+
+    /// Gets the size `value` will be once serialized
+    ///
+    /// by default this serializes `value` and returns the length, formats that can compute it cheaper should override it
+    ///
+    /// # Errors
+    /// if `value` could not be serialized
+    fn serialized_size<T: Serialize + ?Sized>(&self, value: &T) -> Result<u64, error::CodecError> {
+        Ok(self.serialize(value)?.len() as u64)
+    }
+
+    /// Gets the size `value` will be once serialized, if that can be found without serializing it.
+    ///
+    /// this is used to reserve room for a message before serializing it into the send buffer,
+    /// so it should be much cheaper than [`serialize`]. by default it is `None`, and the buffer grows as needed.
+    /// errors are returned by serializing, not by this
+    ///
+    /// [`serialize`]: Codec::serialize
+    fn size_hint<T: Serialize + ?Sized>(&self, _value: &T) -> Option<u64> {
+        None
+    }
+
+    /// Serializes `value` into `writer`
+    ///
+    /// by default this serializes `value` into a new buffer and writes that,
+    /// formats that can write directly should override it
+    ///
+    /// # Errors
+    /// if `value` could not be serialized, or writing failed
+    fn serialize_into<W: Write, T: Serialize + ?Sized>(
+        &self,
+        mut writer: W,
+        value: &T,
+    ) -> Result<(), error::CodecError> {
+        writer.write_all(&self.serialize(value)?)?;
+        Ok(())
+    }
+}
+
+/// A [`Codec`] that uses bincode, with the provided [`Options`]
+///
+/// [`Options`]: bincode::Options
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BincodeCodec<O> {
+    options: O,
+}
+
+impl<O> BincodeCodec<O>
+where
+    O: bincode::Options + Clone,
+{
+    /// Creates a new codec, using `options`
+    pub fn new(options: O) -> Self {
+        Self { options }
+    }
+
+    /// Gets the options used
+    pub fn options(&self) -> &O {
+        &self.options
+    }
+}
+
+impl<O> Codec for BincodeCodec<O>
+where
+    O: bincode::Options + Clone,
+{
+    fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, error::CodecError> {
+        Ok(self.options.clone().serialize(value)?)
+    }
+
+    fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, error::CodecError> {
+        Ok(self.options.clone().deserialize(bytes)?)
+    }
+
+    fn serialized_size<T: Serialize + ?Sized>(&self, value: &T) -> Result<u64, error::CodecError> {
+        Ok(self.options.clone().serialized_size(value)?)
+    }
+
+    /// bincode sizes values by walking them without allocating, which is cheap next to serializing
+    fn size_hint<T: Serialize + ?Sized>(&self, value: &T) -> Option<u64> {
+        self.options.clone().serialized_size(value).ok()
+    }
+
+    fn serialize_into<W: Write, T: Serialize + ?Sized>(
+        &self,
+        writer: W,
+        value: &T,
+    ) -> Result<(), error::CodecError> {
+        Ok(self.options.clone().serialize_into(writer, value)?)
+    }
+}
+
+/// The codec used when you dont care which one is used, bincode with its default options
+pub type DefaultCodec = BincodeCodec<bincode::DefaultOptions>;
+
+/// A [`Codec`] that sends message bodies as json, for talking to peers that are not written in rust.
+///
+/// the body is plain json text (with no trailing newline), framed by the header like any other body.
+/// json can not be sized without serializing it, so [`serialized_size`] uses the default (serializing to a scratch buffer)
+///
+/// only available with the `json` feature
+///
+/// [`serialized_size`]: Codec::serialized_size
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+#[cfg(feature = "json")]
+impl Codec for JsonCodec {
+    fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, error::CodecError> {
+        serde_json::to_vec(value).map_err(error::CodecError::new)
+    }
+
+    fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, error::CodecError> {
+        serde_json::from_slice(bytes).map_err(error::CodecError::new)
+    }
+
+    fn serialize_into<W: Write, T: Serialize + ?Sized>(
+        &self,
+        writer: W,
+        value: &T,
+    ) -> Result<(), error::CodecError> {
+        serde_json::to_writer(writer, value).map_err(error::CodecError::new)
+    }
+}
+
+/// A [`Codec`] that refuses to serialize values nested more than `max_depth` levels deep.
+///
+/// serde formats (including bincode) serialize nested values recursively, so a deeply nested or recursive
+/// message (like a long linked list of `Option<Box<Node>>`) can overflow the stack. this walks the value
+/// before passing it to `inner`, and returns a [`DepthLimitExceeded`] error instead.
+/// every option, newtype, sequence, tuple, map, and struct counts as one level
+///
+/// ## Limitations
+/// - this only checks values being *serialized*. deserializing can recurse just as deeply,
+///   but with bincode every level of nesting that depends on the data (an enum or option tag, a length prefix)
+///   takes at least one byte, so the depth is bounded by the message size. set a limit with
+///   [`Reader::set_max_message_size`] (or bincode's `with_limit` option) to bound it
+/// - the value is walked twice, once to check it and once to serialize it
+///
+/// [`DepthLimitExceeded`]: error::DepthLimitExceeded
+/// [`Reader::set_max_message_size`]: crate::socket::read::Reader::set_max_message_size
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DepthLimited<C> {
+    inner: C,
+    max_depth: usize,
+}
+
+impl<C> DepthLimited<C>
+where
+    C: Codec,
+{
+    /// Wraps `inner`, limiting values to `max_depth` levels of nesting
+    pub fn new(inner: C, max_depth: usize) -> Self {
+        Self { inner, max_depth }
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Gets the codec that does the actual serialization
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    fn check<T: Serialize + ?Sized>(&self, value: &T) -> Result<(), error::CodecError> {
+        match depth::check(value, self.max_depth) {
+            Err(depth::CheckError::TooDeep) => Err(error::CodecError::new(
+                error::DepthLimitExceeded {
+                    limit: self.max_depth,
+                },
+            )),
+            // other errors are reported by the inner codec
+            Ok(()) | Err(depth::CheckError::Other) => Ok(()),
+        }
+    }
+}
+
+impl<C> Codec for DepthLimited<C>
+where
+    C: Codec,
+{
+    fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, error::CodecError> {
+        self.check(value)?;
+        self.inner.serialize(value)
+    }
+
+    fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, error::CodecError> {
+        self.inner.deserialize(bytes)
+    }
+
+    fn serialized_size<T: Serialize + ?Sized>(&self, value: &T) -> Result<u64, error::CodecError> {
+        self.check(value)?;
+        self.inner.serialized_size(value)
+    }
+
+    /// always `None`, sizing with `inner` would recurse through the value before it is checked
+    fn size_hint<T: Serialize + ?Sized>(&self, _value: &T) -> Option<u64> {
+        None
+    }
+
+    fn serialize_into<W: Write, T: Serialize + ?Sized>(
+        &self,
+        writer: W,
+        value: &T,
+    ) -> Result<(), error::CodecError> {
+        self.check(value)?;
+        self.inner.serialize_into(writer, value)
+    }
+}
@@ -0,0 +1,290 @@
+//! a serializer that produces no output, and only checks how deeply nested a value is
+
+use serde::{ser, Serialize};
+
+#[derive(Debug)]
+pub(super) enum CheckError {
+    /// nesting went past the limit
+    TooDeep,
+    /// the value itself failed to serialize. this is left for the real serializer to report
+    Other,
+}
+
+impl std::fmt::Display for CheckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooDeep => f.write_str("value is nested too deeply"),
+            Self::Other => f.write_str("value failed to serialize"),
+        }
+    }
+}
+
+impl std::error::Error for CheckError {}
+
+impl ser::Error for CheckError {
+    fn custom<T: std::fmt::Display>(_msg: T) -> Self {
+        Self::Other
+    }
+}
+
+/// Walks a value, failing with [`CheckError::TooDeep`] once it nests more than `max` levels.
+///
+/// every option, newtype, sequence, tuple, map, and struct counts as a level
+pub(super) fn check<T: Serialize + ?Sized>(value: &T, max: usize) -> Result<(), CheckError> {
+    value.serialize(&mut DepthCheck { depth: 0, max })
+}
+
+struct DepthCheck {
+    depth: usize,
+    max: usize,
+}
+
+impl DepthCheck {
+    fn enter(&mut self) -> Result<&mut Self, CheckError> {
+        self.depth += 1;
+        if self.depth > self.max {
+            Err(CheckError::TooDeep)
+        } else {
+            Ok(self)
+        }
+    }
+
+    fn nested<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CheckError> {
+        value.serialize(&mut *self.enter()?)?;
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn leave(&mut self) -> Result<(), CheckError> {
+        self.depth -= 1;
+        Ok(())
+    }
+}
+
+macro_rules! ignore_primitives {
+    ($($method:ident: $ty:ty),* $(,)?) => {
+        $(
+            fn $method(self, _v: $ty) -> Result<(), CheckError> {
+                Ok(())
+            }
+        )*
+    };
+}
+
+impl ser::Serializer for &mut DepthCheck {
+    type Ok = ();
+    type Error = CheckError;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    ignore_primitives! {
+        serialize_bool: bool,
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_i128: i128,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_u128: u128,
+        serialize_f32: f32,
+        serialize_f64: f64,
+        serialize_char: char,
+        serialize_str: &str,
+        serialize_bytes: &[u8],
+        serialize_unit_struct: &'static str,
+    }
+
+    fn serialize_none(self) -> Result<(), CheckError> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), CheckError> {
+        self.nested(value)
+    }
+
+    fn serialize_unit(self) -> Result<(), CheckError> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<(), CheckError> {
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), CheckError> {
+        self.nested(value)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<(), CheckError> {
+        self.nested(value)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self, CheckError> {
+        self.enter()
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self, CheckError> {
+        self.enter()
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self, CheckError> {
+        self.enter()
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, CheckError> {
+        self.enter()
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self, CheckError> {
+        self.enter()
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, CheckError> {
+        self.enter()
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, CheckError> {
+        self.enter()
+    }
+}
+
+impl ser::SerializeSeq for &mut DepthCheck {
+    type Ok = ();
+    type Error = CheckError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CheckError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), CheckError> {
+        self.leave()
+    }
+}
+
+impl ser::SerializeTuple for &mut DepthCheck {
+    type Ok = ();
+    type Error = CheckError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CheckError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), CheckError> {
+        self.leave()
+    }
+}
+
+impl ser::SerializeTupleStruct for &mut DepthCheck {
+    type Ok = ();
+    type Error = CheckError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CheckError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), CheckError> {
+        self.leave()
+    }
+}
+
+impl ser::SerializeTupleVariant for &mut DepthCheck {
+    type Ok = ();
+    type Error = CheckError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CheckError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), CheckError> {
+        self.leave()
+    }
+}
+
+impl ser::SerializeMap for &mut DepthCheck {
+    type Ok = ();
+    type Error = CheckError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), CheckError> {
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CheckError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), CheckError> {
+        self.leave()
+    }
+}
+
+impl ser::SerializeStruct for &mut DepthCheck {
+    type Ok = ();
+    type Error = CheckError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), CheckError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), CheckError> {
+        self.leave()
+    }
+}
+
+impl ser::SerializeStructVariant for &mut DepthCheck {
+    type Ok = ();
+    type Error = CheckError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), CheckError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), CheckError> {
+        self.leave()
+    }
+}
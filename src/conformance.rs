@@ -0,0 +1,213 @@
+//! Checking that a header and codec can frame messages end to end.
+//!
+//! [`run`] starts a server and a client on localhost, and sends a battery of message sizes
+//! (empty, one byte, around the header size, and large) through them in both directions,
+//! checking every message comes back byte for byte. this is useful for checking custom [`IsHeader`]
+//! or [`Codec`] implementations, and [`run_builtin`] does it for every header that comes with smalltalk.
+//!
+//! the `conformance` example runs [`run_builtin`] and prints the reports:
+//! `cargo run --example conformance --features conformance`
+//!
+//! only available with the `conformance` feature
+
+use std::{fmt::Debug, time::Duration};
+
+use crate::{codec::Codec, header::IsHeader, msg::MessageWrapper, Client, Server};
+
+/// how long a single case can take before it counts as failed
+const CASE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub mod error {
+    #[derive(Debug, thiserror::Error)]
+    pub enum ConformanceError {
+        #[error("Failed to start the server!\n{0}")]
+        Bind(#[from] crate::server::error::BindServerError),
+        #[error("Failed to get the servers address!\n{0}")]
+        Addr(#[source] std::io::Error),
+        #[error("Failed to connect to the server!\n{0}")]
+        Connect(#[from] crate::client::error::ConnectError),
+        #[error("Failed to accept the client!\n{0}")]
+        Accept(#[from] crate::server::error::AcceptConnectionError),
+    }
+}
+
+/// The result of sending one message size
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseResult {
+    /// size of the payload that was sent, in bytes
+    pub size: usize,
+    /// why the case failed, or `None` if it passed
+    pub error: Option<String>,
+}
+
+impl CaseResult {
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// The results of [`run`] for one header and codec
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// name of the header type that was checked
+    pub header: &'static str,
+    pub cases: Vec<CaseResult>,
+}
+
+impl Report {
+    /// Returns if every case passed
+    pub fn passed(&self) -> bool {
+        self.cases.iter().all(CaseResult::passed)
+    }
+
+    /// Gets the cases that failed
+    pub fn failures(&self) -> impl Iterator<Item = &CaseResult> {
+        self.cases.iter().filter(|case| !case.passed())
+    }
+}
+
+/// prints a line per case, then a summary
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}:", self.header)?;
+        for case in &self.cases {
+            match &case.error {
+                None => writeln!(f, "  pass  {} bytes", case.size)?,
+                Some(e) => writeln!(f, "  FAIL  {} bytes: {}", case.size, e.replace('\n', " "))?,
+            }
+        }
+        let failed = self.failures().count();
+        write!(
+            f,
+            "{}: {} of {} cases passed",
+            if failed == 0 { "PASS" } else { "FAIL" },
+            self.cases.len() - failed,
+            self.cases.len()
+        )
+    }
+}
+
+/// The payload sizes that are checked for the header `H`
+pub fn sizes<H: IsHeader>() -> Vec<usize> {
+    let header_size = H::header_size();
+    let mut sizes = vec![
+        0,
+        1,
+        header_size.saturating_sub(1),
+        header_size,
+        header_size + 1,
+        255,
+        256,
+        u16::MAX as usize,
+        u16::MAX as usize + 1,
+        // big enough that neither side can buffer it all at once
+        4 * 1024 * 1024,
+    ];
+    sizes.sort_unstable();
+    sizes.dedup();
+    sizes
+}
+
+/// Sends every size from [`sizes`] from a client to a server and back, using the header `H` and `codec`
+///
+/// # Errors
+/// if the server or client could not be set up. failing cases are recorded in the [`Report`] instead
+pub async fn run<H, C>(codec: C) -> Result<Report, error::ConformanceError>
+where
+    H: IsHeader + Clone + Debug + Send,
+    C: Codec + Clone + Send,
+{
+    let mut server = Server::bind("127.0.0.1:0", codec.clone()).await?;
+    let addr = server
+        .as_listener()
+        .local_addr()
+        .map_err(error::ConformanceError::Addr)?;
+    let (client, conn) = tokio::join!(
+        Client::<H, Vec<u8>, C>::connect(addr, codec),
+        server.accept::<H, Vec<u8>>()
+    );
+    let (mut client, mut conn) = (client?, conn?);
+    let mut cases = Vec::new();
+    for size in sizes::<H>() {
+        let error = match tokio::time::timeout(
+            CASE_TIMEOUT,
+            round_trip(&mut client, &mut conn, size),
+        )
+        .await
+        {
+            Ok(res) => res.err(),
+            Err(_) => Some(format!("timed out after {CASE_TIMEOUT:?}")),
+        };
+        let failed = error.is_some();
+        cases.push(CaseResult { size, error });
+        if failed {
+            // the stream may be out of sync now, so later cases would fail for the wrong reason
+            break;
+        }
+    }
+    Ok(Report {
+        header: std::any::type_name::<H>(),
+        cases,
+    })
+}
+
+/// Runs [`run`] for every header that comes with smalltalk, using the [`DefaultCodec`]
+///
+/// # Errors
+/// see [`run`]
+///
+/// [`DefaultCodec`]: crate::codec::DefaultCodec
+pub async fn run_builtin() -> Result<Vec<Report>, error::ConformanceError> {
+    let codec = crate::codec::DefaultCodec::default();
+    Ok(vec![
+        run::<crate::header::U32Header, _>(codec).await?,
+        run::<crate::header::U64Header, _>(codec).await?,
+        run::<crate::header::ChecksummedHeader, _>(codec).await?,
+        run::<crate::header::TypedHeader, _>(codec).await?,
+        run::<crate::header::SequencedHeader, _>(codec).await?,
+        run::<crate::header::ExtendedHeader<16>, _>(codec).await?,
+    ])
+}
+
+/// Sends a payload of `size` bytes to the server, and then back to the client, checking it both times
+async fn round_trip<H, C>(
+    client: &mut Client<H, Vec<u8>, C>,
+    conn: &mut crate::server::ClientConnection<H, Vec<u8>, C>,
+    size: usize,
+) -> Result<(), String>
+where
+    H: IsHeader + Clone + Debug + Send,
+    C: Codec + Clone + Send,
+{
+    // not all the same byte, so shifted or reordered data is noticed
+    let payload = (0..size).map(|i| (i % 251) as u8).collect::<Vec<u8>>();
+    client
+        .queue_message(&MessageWrapper::new(payload.clone()))
+        .map_err(|e| e.to_string())?;
+    let (sent, received) = tokio::join!(client.flush_all(), conn.wait_for_message());
+    sent.map_err(|e| format!("client failed to send: {e}"))?;
+    let received = received.map_err(|e| format!("server failed to receive: {e}"))?;
+    check(&payload, received.message(), "server")?;
+
+    conn.queue_message(&received).map_err(|e| e.to_string())?;
+    let (sent, echoed) = tokio::join!(conn.flush_all(), client.wait_for_message());
+    sent.map_err(|e| format!("server failed to send: {e}"))?;
+    let echoed = echoed.map_err(|e| format!("client failed to receive: {e}"))?;
+    check(&payload, echoed.message(), "client")
+}
+
+fn check(expected: &[u8], got: &[u8], side: &str) -> Result<(), String> {
+    if expected == got {
+        return Ok(());
+    }
+    match expected.iter().zip(got).position(|(a, b)| a != b) {
+        Some(at) => Err(format!(
+            "{side} received different data, first difference at byte {at}"
+        )),
+        None => Err(format!(
+            "{side} received {} bytes, expected {}",
+            got.len(),
+            expected.len()
+        )),
+    }
+}
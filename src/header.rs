@@ -1,8 +1,17 @@
 use std::fmt::{Debug, Display};
 
-use bytes::{Bytes, BytesMut};
+use bytes::{BufMut, Bytes, BytesMut};
 
 /// Trait for methods that should be found on header implementations
+///
+/// headers are always encoded with their own fixed layout, through [`as_bytes`] and [`from_bytes`].
+/// the [`Codec`] used for message bodies is never used for the header,
+/// so changing how bodies are encoded does not change how messages are framed.
+/// a header that wants to use serde internally should pick its own fixed codec to do so
+///
+/// [`as_bytes`]: IsHeader::as_bytes
+/// [`from_bytes`]: IsHeader::from_bytes
+/// [`Codec`]: crate::codec::Codec
 pub trait IsHeader {
     type Error: Debug + Display;
 
@@ -21,6 +30,184 @@ pub trait IsHeader {
         Self::new(0)
     }
 
+    /// Create a new header for a serialized message body.
+    ///
+    /// this is what is used when sending messages, so headers that describe the contents
+    /// of the body (like [`ChecksummedHeader`]) can do so. by default this is `new(body.len())`
+    #[must_use]
+    fn for_body(body: &[u8]) -> Self
+    where
+        Self: Sized,
+    {
+        Self::new(body.len() as u64)
+    }
+
+    /// Create a new header, checking that `msg_len` fits in it.
+    ///
+    /// this is what is used when sending messages, so a message that is too large for the header
+    /// fails to queue instead of panicking. by default this is `Ok(new(msg_len))`,
+    /// headers that can not hold every length (like [`U32Header`]) should override it
+    ///
+    /// # Errors
+    /// if `msg_len` does not fit in the header
+    fn try_new(msg_len: u64) -> Result<Self, Self::Error>
+    where
+        Self: Sized,
+    {
+        Ok(Self::new(msg_len))
+    }
+
+    /// Create a new header for a serialized message body, checking that its length fits (see [`try_new`]).
+    ///
+    /// by default this is [`try_new`] (to check the length) followed by [`for_body`]
+    ///
+    /// # Errors
+    /// if the length of `body` does not fit in the header
+    ///
+    /// [`try_new`]: IsHeader::try_new
+    /// [`for_body`]: IsHeader::for_body
+    fn try_for_body(body: &[u8]) -> Result<Self, Self::Error>
+    where
+        Self: Sized,
+    {
+        Self::try_new(body.len() as u64)?;
+        Ok(Self::for_body(body))
+    }
+
+    /// Check that a received message body matches this header, before it is deserialized.
+    ///
+    /// by default this does nothing
+    ///
+    /// # Errors
+    /// if the body does not match the header
+    fn validate_body(&self, _body: &[u8]) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Create the header of a heartbeat, see [`is_heartbeat`]. heartbeats have no body.
+    ///
+    /// heartbeats have to be told apart from real messages (a message can serialize to zero bytes,
+    /// like `()` with bincode, so a empty body is not enough), which needs room in the header to mark them.
+    /// by default headers have no such room, and this is `None`, so heartbeats can not be enabled
+    /// (see [`set_heartbeat`]). headers that can mark them (like [`TypedHeader`], with a reserved kind)
+    /// override this and [`is_heartbeat`]
+    ///
+    /// [`is_heartbeat`]: IsHeader::is_heartbeat
+    /// [`set_heartbeat`]: crate::socket::interface::_SocketUtils::set_heartbeat
+    #[must_use]
+    fn heartbeat() -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// Returns if this is the header of a heartbeat, which only shows the connection is still alive.
+    /// when heartbeats are enabled (see [`set_heartbeat`]) these are never passed to the application.
+    ///
+    /// this should only be true for headers made with [`heartbeat`], by default it is always false
+    ///
+    /// [`set_heartbeat`]: crate::socket::interface::_SocketUtils::set_heartbeat
+    /// [`heartbeat`]: IsHeader::heartbeat
+    fn is_heartbeat(&self) -> bool {
+        false
+    }
+
+    /// Gets the kind of the message, for telling different categories of messages apart
+    /// (auth, data, control, etc.) without deserializing them.
+    ///
+    /// by default headers have no kind, and this is always 0. see [`TypedHeader`]
+    fn kind(&self) -> u16 {
+        0
+    }
+
+    /// Sets the kind of the message, see [`kind`]
+    ///
+    /// # Errors
+    /// if the header can not hold a kind. by default headers have no kind,
+    /// so this only succeeds if `kind` is 0
+    ///
+    /// [`kind`]: IsHeader::kind
+    fn set_kind(&mut self, kind: u16) -> Result<(), error::KindError> {
+        if kind == 0 {
+            Ok(())
+        } else {
+            Err(error::KindError { kind })
+        }
+    }
+
+    /// Create a new header, with a kind (see [`kind`])
+    ///
+    /// # Errors
+    /// if the header can not hold a kind
+    ///
+    /// [`kind`]: IsHeader::kind
+    fn new_with_kind(msg_len: u64, kind: u16) -> Result<Self, error::KindError>
+    where
+        Self: Sized,
+    {
+        let mut header = Self::new(msg_len);
+        header.set_kind(kind)?;
+        Ok(header)
+    }
+
+    /// Gets the sequence number of the message, if the header has one (see [`SequencedHeader`]).
+    ///
+    /// when it does, the [`Writer`] numbers every message it sends, and the [`Reader`]
+    /// checks that they arrive in order without gaps or duplicates. by default headers have none, and this is `None`
+    ///
+    /// [`Writer`]: crate::socket::write::Writer
+    /// [`Reader`]: crate::socket::read::Reader
+    fn sequence(&self) -> Option<u64> {
+        None
+    }
+
+    /// Sets the sequence number of the message, see [`sequence`]. by default this does nothing
+    ///
+    /// [`sequence`]: IsHeader::sequence
+    fn set_sequence(&mut self, _seq: u64) {}
+
+    /// Gets the extension area of the header, for small metadata (like trace context) sent alongside the body.
+    ///
+    /// the extension area has a fixed size, so the header size never changes.
+    /// by default headers have no extension area, and this is empty. see [`ExtendedHeader`]
+    fn extension(&self) -> &[u8] {
+        &[]
+    }
+
+    /// Sets the extension area of the header, see [`extension`]
+    ///
+    /// # Errors
+    /// if `extension` does not fit in the extension area. by default there is no extension area,
+    /// so this only succeeds if `extension` is empty
+    ///
+    /// [`extension`]: IsHeader::extension
+    fn set_extension(&mut self, extension: &[u8]) -> Result<(), error::ExtensionError> {
+        if extension.is_empty() {
+            Ok(())
+        } else {
+            Err(error::ExtensionError {
+                len: extension.len(),
+                capacity: 0,
+            })
+        }
+    }
+
+    /// Create a new header, with a extension (see [`extension`])
+    ///
+    /// # Errors
+    /// if `extension` does not fit in the extension area
+    ///
+    /// [`extension`]: IsHeader::extension
+    fn new_with_extension(msg_len: u64, extension: &[u8]) -> Result<Self, error::ExtensionError>
+    where
+        Self: Sized,
+    {
+        let mut header = Self::new(msg_len);
+        header.set_extension(extension)?;
+        Ok(header)
+    }
+
     /// Get the size of the message contained within
     #[must_use]
     fn size(&self) -> u64;
@@ -46,3 +233,493 @@ pub trait IsHeader {
     #[must_use]
     fn header_size() -> usize;
 }
+
+pub mod error {
+    #[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum U32HeaderError {
+        #[error("Wrong number of bytes for header, expected 4 but got {0}")]
+        WrongByteCount(usize),
+        #[error("Message length {0} does not fit in a u32 header")]
+        LengthOverflow(u64),
+    }
+
+    #[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum U64HeaderError {
+        #[error("Wrong number of bytes for header, expected 8 but got {0}")]
+        WrongByteCount(usize),
+    }
+
+    #[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+    #[error("Header extension of {len} bytes does not fit in the {capacity} byte extension area")]
+    pub struct ExtensionError {
+        pub len: usize,
+        pub capacity: usize,
+    }
+
+    /// the header has no room for a message kind (so only kind 0 can be sent), or `kind` is reserved by it
+    #[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+    #[error("Header can not send messages of kind {kind}, it has no room for a kind (so only kind 0 can be sent) or reserves it")]
+    pub struct KindError {
+        pub kind: u16,
+    }
+
+    #[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum TypedHeaderError {
+        #[error("Wrong number of bytes for header, expected 10 but got {0}")]
+        WrongByteCount(usize),
+    }
+
+    #[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum SequencedHeaderError {
+        #[error("Wrong number of bytes for header, expected 16 but got {0}")]
+        WrongByteCount(usize),
+    }
+
+    #[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ExtendedHeaderError {
+        #[error("Wrong number of bytes for header, expected {expected} but got {actual}")]
+        WrongByteCount { expected: usize, actual: usize },
+    }
+
+    /// Error from headers that use `#[derive(IsHeader)]` (with the `derive` feature)
+    #[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum DerivedHeaderError {
+        #[error("Wrong number of bytes for header, expected {expected} but got {actual}")]
+        WrongByteCount { expected: usize, actual: usize },
+        #[error("Message length {len} does not fit in the `{field}` length field")]
+        LengthOverflow { len: u64, field: &'static str },
+    }
+
+    #[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ChecksummedHeaderError {
+        #[error("Wrong number of bytes for header, expected 12 but got {0}")]
+        WrongByteCount(usize),
+        #[error("Message body is corrupted, expected a CRC32 of {expected:#010x} but got {actual:#010x}")]
+        ChecksumMismatch { expected: u32, actual: u32 },
+    }
+}
+
+/// A header that is only the length of the message, as a big-endian `u32`.
+///
+/// this is enough for most uses, and means you do not have to write your own [`IsHeader`]
+/// just to send messages. it limits messages to `u32::MAX` bytes (4GiB)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct U32Header {
+    len: u32,
+}
+
+impl U32Header {
+    /// Create a new header, checking that `msg_len` fits
+    ///
+    /// # Errors
+    /// if `msg_len` is larger than `u32::MAX`
+    pub fn try_new(msg_len: u64) -> Result<Self, error::U32HeaderError> {
+        match u32::try_from(msg_len) {
+            Ok(len) => Ok(Self { len }),
+            Err(_) => Err(error::U32HeaderError::LengthOverflow(msg_len)),
+        }
+    }
+}
+
+impl IsHeader for U32Header {
+    type Error = error::U32HeaderError;
+
+    /// Create a new header
+    ///
+    /// # Panics
+    /// if `msg_len` is larger than `u32::MAX`, use [`U32Header::try_new`] to check this first
+    fn new(msg_len: u64) -> Self {
+        match U32Header::try_new(msg_len) {
+            Ok(header) => header,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    fn try_new(msg_len: u64) -> Result<Self, Self::Error> {
+        // the inherent method
+        U32Header::try_new(msg_len)
+    }
+
+    fn size(&self) -> u64 {
+        u64::from(self.len)
+    }
+
+    fn as_bytes(&self) -> Bytes {
+        self.as_bytes_mut().freeze()
+    }
+
+    fn as_bytes_mut(&self) -> BytesMut {
+        let mut bytes = BytesMut::with_capacity(4);
+        bytes.put_u32(self.len);
+        bytes
+    }
+
+    fn from_bytes(bytes: Bytes) -> Result<Self, Self::Error> {
+        let bytes: [u8; 4] = bytes[..]
+            .try_into()
+            .map_err(|_| error::U32HeaderError::WrongByteCount(bytes.len()))?;
+        Ok(Self {
+            len: u32::from_be_bytes(bytes),
+        })
+    }
+
+    fn header_size() -> usize {
+        4
+    }
+}
+
+/// A header that is only the length of the message, as a big-endian `u64`.
+///
+/// like [`U32Header`], but for messages larger than 4GiB. every length can be represented,
+/// so unlike [`U32Header`] creating it never fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct U64Header {
+    len: u64,
+}
+
+impl IsHeader for U64Header {
+    type Error = error::U64HeaderError;
+
+    fn new(msg_len: u64) -> Self {
+        Self { len: msg_len }
+    }
+
+    fn size(&self) -> u64 {
+        self.len
+    }
+
+    fn as_bytes(&self) -> Bytes {
+        self.as_bytes_mut().freeze()
+    }
+
+    fn as_bytes_mut(&self) -> BytesMut {
+        let mut bytes = BytesMut::with_capacity(8);
+        bytes.put_u64(self.len);
+        bytes
+    }
+
+    fn from_bytes(bytes: Bytes) -> Result<Self, Self::Error> {
+        let bytes: [u8; 8] = bytes[..]
+            .try_into()
+            .map_err(|_| error::U64HeaderError::WrongByteCount(bytes.len()))?;
+        Ok(Self {
+            len: u64::from_be_bytes(bytes),
+        })
+    }
+
+    fn header_size() -> usize {
+        8
+    }
+}
+
+/// A header holding the length of the message (as a big-endian `u64`), followed by its kind (as a big-endian `u16`).
+///
+/// the kind lets messages be told apart without deserializing them, see [`IsHeader::kind`].
+/// kind [`HEARTBEAT_KIND`] is reserved for heartbeats, so messages can not be sent with it
+///
+/// [`HEARTBEAT_KIND`]: TypedHeader::HEARTBEAT_KIND
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TypedHeader {
+    len: u64,
+    kind: u16,
+}
+
+impl TypedHeader {
+    /// The kind that marks a heartbeat, see [`IsHeader::heartbeat`]
+    pub const HEARTBEAT_KIND: u16 = u16::MAX;
+}
+
+impl IsHeader for TypedHeader {
+    type Error = error::TypedHeaderError;
+
+    fn new(msg_len: u64) -> Self {
+        Self { len: msg_len, kind: 0 }
+    }
+
+    fn kind(&self) -> u16 {
+        self.kind
+    }
+
+    fn set_kind(&mut self, kind: u16) -> Result<(), error::KindError> {
+        if kind == Self::HEARTBEAT_KIND {
+            return Err(error::KindError { kind });
+        }
+        self.kind = kind;
+        Ok(())
+    }
+
+    fn heartbeat() -> Option<Self> {
+        Some(Self {
+            len: 0,
+            kind: Self::HEARTBEAT_KIND,
+        })
+    }
+
+    fn is_heartbeat(&self) -> bool {
+        self.kind == Self::HEARTBEAT_KIND
+    }
+
+    fn size(&self) -> u64 {
+        self.len
+    }
+
+    fn as_bytes(&self) -> Bytes {
+        self.as_bytes_mut().freeze()
+    }
+
+    fn as_bytes_mut(&self) -> BytesMut {
+        let mut bytes = BytesMut::with_capacity(10);
+        bytes.put_u64(self.len);
+        bytes.put_u16(self.kind);
+        bytes
+    }
+
+    fn from_bytes(bytes: Bytes) -> Result<Self, Self::Error> {
+        let bytes: [u8; 10] = bytes[..]
+            .try_into()
+            .map_err(|_| error::TypedHeaderError::WrongByteCount(bytes.len()))?;
+        let (len, kind) = bytes.split_at(8);
+        Ok(Self {
+            len: u64::from_be_bytes(len.try_into().unwrap()),
+            kind: u16::from_be_bytes(kind.try_into().unwrap()),
+        })
+    }
+
+    fn header_size() -> usize {
+        10
+    }
+}
+
+/// A header holding the length of the message (as a big-endian `u64`), followed by its sequence number (as a big-endian `u64`).
+///
+/// the sequence number is set by the [`Writer`] and checked by the [`Reader`] automatically,
+/// see [`IsHeader::sequence`]
+///
+/// [`Writer`]: crate::socket::write::Writer
+/// [`Reader`]: crate::socket::read::Reader
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SequencedHeader {
+    len: u64,
+    seq: u64,
+}
+
+impl IsHeader for SequencedHeader {
+    type Error = error::SequencedHeaderError;
+
+    fn new(msg_len: u64) -> Self {
+        Self { len: msg_len, seq: 0 }
+    }
+
+    fn sequence(&self) -> Option<u64> {
+        Some(self.seq)
+    }
+
+    fn set_sequence(&mut self, seq: u64) {
+        self.seq = seq;
+    }
+
+    fn size(&self) -> u64 {
+        self.len
+    }
+
+    fn as_bytes(&self) -> Bytes {
+        self.as_bytes_mut().freeze()
+    }
+
+    fn as_bytes_mut(&self) -> BytesMut {
+        let mut bytes = BytesMut::with_capacity(16);
+        bytes.put_u64(self.len);
+        bytes.put_u64(self.seq);
+        bytes
+    }
+
+    fn from_bytes(bytes: Bytes) -> Result<Self, Self::Error> {
+        let bytes: [u8; 16] = bytes[..]
+            .try_into()
+            .map_err(|_| error::SequencedHeaderError::WrongByteCount(bytes.len()))?;
+        let (len, seq) = bytes.split_at(8);
+        Ok(Self {
+            len: u64::from_be_bytes(len.try_into().unwrap()),
+            seq: u64::from_be_bytes(seq.try_into().unwrap()),
+        })
+    }
+
+    fn header_size() -> usize {
+        16
+    }
+}
+
+/// A header holding the length of the message (as a big-endian `u64`), followed by a `N` byte extension area.
+///
+/// the extension area carries small metadata (like trace context) outside the message body,
+/// see [`IsHeader::extension`]. it is always `N` bytes, extensions shorter than that are padded with zeros
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExtendedHeader<const N: usize> {
+    len: u64,
+    extension: [u8; N],
+}
+
+impl<const N: usize> IsHeader for ExtendedHeader<N> {
+    type Error = error::ExtendedHeaderError;
+
+    fn new(msg_len: u64) -> Self {
+        Self {
+            len: msg_len,
+            extension: [0; N],
+        }
+    }
+
+    fn extension(&self) -> &[u8] {
+        &self.extension
+    }
+
+    fn set_extension(&mut self, extension: &[u8]) -> Result<(), error::ExtensionError> {
+        if extension.len() > N {
+            return Err(error::ExtensionError {
+                len: extension.len(),
+                capacity: N,
+            });
+        }
+        self.extension = [0; N];
+        self.extension[..extension.len()].copy_from_slice(extension);
+        Ok(())
+    }
+
+    fn size(&self) -> u64 {
+        self.len
+    }
+
+    fn as_bytes(&self) -> Bytes {
+        self.as_bytes_mut().freeze()
+    }
+
+    fn as_bytes_mut(&self) -> BytesMut {
+        let mut bytes = BytesMut::with_capacity(Self::header_size());
+        bytes.put_u64(self.len);
+        bytes.put_slice(&self.extension);
+        bytes
+    }
+
+    fn from_bytes(bytes: Bytes) -> Result<Self, Self::Error> {
+        if bytes.len() != Self::header_size() {
+            return Err(error::ExtendedHeaderError::WrongByteCount {
+                expected: Self::header_size(),
+                actual: bytes.len(),
+            });
+        }
+        let (len, extension) = bytes.split_at(8);
+        Ok(Self {
+            len: u64::from_be_bytes(len.try_into().unwrap()),
+            extension: extension.try_into().unwrap(),
+        })
+    }
+
+    fn header_size() -> usize {
+        8 + N
+    }
+}
+
+/// A header holding the length of the message (as a big-endian `u64`) followed by a CRC32 of the message body.
+///
+/// the checksum is checked when the message is received, so corrupted bodies produce a
+/// [`ChecksumMismatch`] error instead of deserializing into garbage.
+///
+/// the checksum can only be computed from the body, so headers made with [`new`] (which only knows the length)
+/// will not validate. because of this, [`serialize_fixed`] cannot be used with this header
+///
+/// [`ChecksumMismatch`]: error::ChecksummedHeaderError::ChecksumMismatch
+/// [`new`]: IsHeader::new
+/// [`serialize_fixed`]: crate::msg::MessageWrapper::serialize_fixed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChecksummedHeader {
+    len: u64,
+    crc: u32,
+}
+
+impl ChecksummedHeader {
+    /// Gets the CRC32 of the body this header was made for
+    pub fn checksum(&self) -> u32 {
+        self.crc
+    }
+}
+
+impl IsHeader for ChecksummedHeader {
+    type Error = error::ChecksummedHeaderError;
+
+    fn new(msg_len: u64) -> Self {
+        Self { len: msg_len, crc: 0 }
+    }
+
+    fn for_body(body: &[u8]) -> Self {
+        Self {
+            len: body.len() as u64,
+            crc: crc32(body),
+        }
+    }
+
+    fn validate_body(&self, body: &[u8]) -> Result<(), Self::Error> {
+        let actual = crc32(body);
+        if actual == self.crc {
+            Ok(())
+        } else {
+            Err(error::ChecksummedHeaderError::ChecksumMismatch {
+                expected: self.crc,
+                actual,
+            })
+        }
+    }
+
+    fn size(&self) -> u64 {
+        self.len
+    }
+
+    fn as_bytes(&self) -> Bytes {
+        self.as_bytes_mut().freeze()
+    }
+
+    fn as_bytes_mut(&self) -> BytesMut {
+        let mut bytes = BytesMut::with_capacity(12);
+        bytes.put_u64(self.len);
+        bytes.put_u32(self.crc);
+        bytes
+    }
+
+    fn from_bytes(bytes: Bytes) -> Result<Self, Self::Error> {
+        let bytes: [u8; 12] = bytes[..]
+            .try_into()
+            .map_err(|_| error::ChecksummedHeaderError::WrongByteCount(bytes.len()))?;
+        let (len, crc) = bytes.split_at(8);
+        Ok(Self {
+            len: u64::from_be_bytes(len.try_into().unwrap()),
+            crc: u32::from_be_bytes(crc.try_into().unwrap()),
+        })
+    }
+
+    fn header_size() -> usize {
+        12
+    }
+}
+
+/// lookup table for [`crc32`], for the reversed IEEE polynomial
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC32 (IEEE, the same as zlib/ethernet) of `data`
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        CRC32_TABLE[((crc ^ u32::from(byte)) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
@@ -1,14 +1,35 @@
 pub mod client;
+pub mod clock;
+pub mod codec;
+#[cfg(feature = "conformance")]
+pub mod conformance;
 pub mod header;
 pub mod msg;
+mod rate;
 pub mod server;
+pub mod session;
 pub mod socket;
+pub mod stats;
 
-pub use header::IsHeader;
-pub use msg::MessageWrapper;
+pub use codec::{BincodeCodec, Codec, DefaultCodec, DepthLimited};
+#[cfg(feature = "json")]
+pub use codec::JsonCodec;
+pub use header::{
+    ChecksummedHeader, ExtendedHeader, IsHeader, SequencedHeader, TypedHeader, U32Header,
+    U64Header,
+};
+pub use msg::{FixedSizeMessage, MessageWrapper};
 pub use socket::{Reader, Writer};
 pub use server::Server;
 pub use client::Client;
+#[cfg(feature = "derive")]
+pub use smalltalk_derive::IsHeader;
+
+/// used by code generated by `#[derive(IsHeader)]`, not public api
+#[doc(hidden)]
+pub mod __private {
+    pub use bytes;
+}
 
 /// Trait imports for smalltalk.
 ///
@@ -18,5 +39,6 @@ pub use client::Client;
 /// All traits are renamed `_smalltalk_<trait name>` to avoid confusion and
 /// not knowing where imported items came from.
 pub mod prelude {
+    pub use crate::codec::Codec as _smalltalk_Codec;
     pub use crate::header::IsHeader as _smalltalk_IsHeader;
 }
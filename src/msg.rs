@@ -1,15 +1,105 @@
 use std::{fmt::Debug, marker::PhantomData};
 
-use bytes::{BufMut, Bytes};
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use bytes::{BufMut, Bytes, BytesMut};
+use serde::{de::DeserializeOwned, Serialize};
 
-use crate::header::IsHeader;
+use crate::{
+    codec::{error::CodecError, Codec},
+    header::IsHeader,
+};
+
+/// A message with a serialized size that never changes, for example a fixed-layout struct
+/// serialized with bincode's fixint encoding.
+///
+/// this allows building the header without calling `serialized_size` every time the message is sent,
+/// see [`MessageWrapper::serialize_fixed`]
+pub trait FixedSizeMessage: Serialize {
+    /// size of the message once serialized. this *must* be correct for the options used to serialize it,
+    /// or the header sent will not match the message
+    const SERIALIZED_SIZE: u64;
+}
+
+/// Produces the complete framed bytes (header and body) for a message.
+///
+/// this is the same as `MessageWrapper::new(msg).serialize(codec)`, but does not need
+/// to take ownership of the message. the output can be cached and sent repeatedly with [`Writer::queue_raw`]
+///
+/// # Errors
+/// if the message could not be serialized
+///
+/// [`Writer::queue_raw`]: crate::socket::write::Writer::queue_raw
+pub fn frame<M, H>(msg: &M, codec: &impl Codec) -> Result<Bytes, CodecError>
+where
+    M: Serialize,
+    H: IsHeader,
+{
+    let mut buf = BytesMut::new();
+    frame_into::<M, H>(&mut buf, msg, &[], 0, codec)?;
+    Ok(buf.freeze())
+}
+
+/// Appends the complete framed bytes (header and body) for a message to `buf`.
+///
+/// room for the whole frame is reserved up front if the codec can size the message cheaply (see [`Codec::size_hint`]),
+/// then the body is serialized once, directly into `buf` after space for the header, which is filled in afterwards
+/// (as it may depend on the body), so no intermediate buffer is used.
+/// `extension` is put in the headers extension area, see [`IsHeader::extension`],
+/// and `kind` is the kind of the message, see [`IsHeader::kind`]
+pub(crate) fn frame_into<M, H>(
+    buf: &mut BytesMut,
+    msg: &M,
+    extension: &[u8],
+    kind: u16,
+    codec: &impl Codec,
+) -> Result<(), CodecError>
+where
+    M: Serialize,
+    H: IsHeader,
+{
+    let start = buf.len();
+    let header_size = H::header_size();
+    let body_size = codec
+        .size_hint(msg)
+        .and_then(|size| usize::try_from(size).ok())
+        .unwrap_or(0);
+    buf.reserve(header_size.saturating_add(body_size));
+    buf.put_bytes(0, header_size);
+    if let Err(e) = codec.serialize_into(buf.writer(), msg) {
+        // dont leave a partial frame behind
+        buf.truncate(start);
+        return Err(e);
+    }
+    match header_for::<H>(&buf[start + header_size..], extension, kind) {
+        Ok(header) => {
+            buf[start..start + header_size].copy_from_slice(&header.as_bytes());
+            Ok(())
+        }
+        Err(e) => {
+            buf.truncate(start);
+            Err(e)
+        }
+    }
+}
+
+/// Creates the header sent in front of `body`, with the extension and kind of the message
+fn header_for<H: IsHeader>(body: &[u8], extension: &[u8], kind: u16) -> Result<H, CodecError> {
+    let mut header = H::try_for_body(body).map_err(|e| CodecError::new(e.to_string()))?;
+    header.set_extension(extension).map_err(CodecError::new)?;
+    header.set_kind(kind).map_err(CodecError::new)?;
+    Ok(header)
+}
 
 pub struct MessageWrapper<M, H>
 where
     M: Serialize,
 {
     inner: M,
+    /// body bytes the message was deserialized from, if the reader was set to retain them
+    raw_body: Option<Bytes>,
+    /// contents of the headers extension area, see [`IsHeader::extension`]
+    extension: Option<Bytes>,
+    /// kind of the message, see [`IsHeader::kind`]
+    kind: u16,
     _header_type: PhantomData<H>,
 }
 
@@ -22,38 +112,115 @@ where
     pub fn new(msg: M) -> Self {
         Self {
             inner: msg,
+            raw_body: None,
+            extension: None,
+            kind: 0,
             _header_type: PhantomData,
         }
     }
 
-    /// Create a header of the contained message
+    /// Create a header of the contained message, the same as the one [`serialize`] sends
+    /// (including its extension and kind)
     ///
     /// # Errors
-    /// if the wrappers message could not be serialized
-    pub fn header(&self, options: impl bincode::Options) -> Result<impl IsHeader, bincode::Error> {
-        Ok(H::new(options.serialized_size(&self.inner)?))
+    /// if the wrappers message could not be serialized, or the header could not hold its length, extension or kind
+    ///
+    /// [`serialize`]: MessageWrapper::serialize
+    pub fn header(&self, codec: &impl Codec) -> Result<H, CodecError> {
+        header_for::<H>(&codec.serialize(&self.inner)?, self.extension_bytes(), self.kind)
+    }
+
+    /// Serialize only the header (the length-prefix) of the contained message.
+    ///
+    /// This is for building frames manually,
+    /// `header_bytes` followed by [`serialize_self`] is equal to [`serialize`]
+    ///
+    /// # Errors
+    /// see [`header`]
+    ///
+    /// [`header`]: MessageWrapper::header
+    /// [`serialize_self`]: MessageWrapper::serialize_self
+    /// [`serialize`]: MessageWrapper::serialize
+    pub fn header_bytes(&self, codec: &impl Codec) -> Result<Bytes, CodecError> {
+        Ok(self.header(codec)?.as_bytes())
     }
 
     /// Serialize the contained message, but only that, do not include the header
     #[allow(clippy::missing_errors_doc)]
     pub fn serialize_self(
         &self,
-        options: impl bincode::Options,
-    ) -> Result<Vec<u8>, bincode::Error> {
-        options.serialize(&self.inner)
+        codec: &impl Codec,
+    ) -> Result<Vec<u8>, CodecError> {
+        codec.serialize(&self.inner)
     }
 
     /// Serialize and combine the header and message
+    ///
+    /// only the message is serialized with `codec`, the header is encoded with [`IsHeader::as_bytes`]
     #[allow(clippy::missing_errors_doc)]
-    pub fn serialize(
-        &self,
-        options: impl bincode::Options + Clone,
-    ) -> Result<Bytes, bincode::Error> {
-        let mut header = self.header(options.clone())?.as_bytes_mut();
-        let serialized_self = self.serialize_self(options)?;
-        header.reserve(serialized_self.len());
-        header.put_slice(&serialized_self);
-        Ok(header.freeze())
+    pub fn serialize(&self, codec: &impl Codec) -> Result<Bytes, CodecError> {
+        let mut buf = BytesMut::new();
+        frame_into::<M, H>(
+            &mut buf,
+            &self.inner,
+            self.extension_bytes(),
+            self.kind,
+            codec,
+        )?;
+        Ok(buf.freeze())
+    }
+
+    /// Sets the data sent in the headers extension area, see [`IsHeader::extension`].
+    ///
+    /// the header type has to have a extension area large enough (like [`ExtendedHeader`]),
+    /// or serializing the message fails
+    ///
+    /// [`ExtendedHeader`]: crate::header::ExtendedHeader
+    #[must_use]
+    pub fn with_extension(mut self, extension: impl Into<Bytes>) -> Self {
+        self.set_extension(extension);
+        self
+    }
+
+    /// Sets the data sent in the headers extension area, see [`with_extension`]
+    ///
+    /// [`with_extension`]: MessageWrapper::with_extension
+    pub fn set_extension(&mut self, extension: impl Into<Bytes>) {
+        self.extension = Some(extension.into());
+    }
+
+    /// Gets the headers extension data. for received messages this is the extension area of the header they were sent with
+    /// (if the header type has one, it is always its full size)
+    pub fn extension(&self) -> Option<&Bytes> {
+        self.extension.as_ref()
+    }
+
+    pub(crate) fn extension_bytes(&self) -> &[u8] {
+        self.extension.as_deref().unwrap_or_default()
+    }
+
+    /// Sets the kind of the message, see [`IsHeader::kind`].
+    ///
+    /// the header type has to be able to hold a kind (like [`TypedHeader`]),
+    /// or serializing a message with a kind other than 0 fails
+    ///
+    /// [`TypedHeader`]: crate::header::TypedHeader
+    #[must_use]
+    pub fn with_kind(mut self, kind: u16) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Sets the kind of the message, see [`with_kind`]
+    ///
+    /// [`with_kind`]: MessageWrapper::with_kind
+    pub fn set_kind(&mut self, kind: u16) {
+        self.kind = kind;
+    }
+
+    /// Gets the kind of the message. for received messages this is the kind from the header they were sent with
+    pub fn kind(&self) -> u16 {
+        self.kind
     }
 
     /// Consumes self, producing the contained message
@@ -65,6 +232,19 @@ where
         &self.inner
     }
 
+    /// Gets the raw body bytes this message was deserialized from.
+    ///
+    /// this is only retained for received messages, when the reader was set to keep it with [`set_retain_raw_body`]
+    ///
+    /// [`set_retain_raw_body`]: crate::socket::read::Reader::set_retain_raw_body
+    pub fn raw_body(&self) -> Option<&Bytes> {
+        self.raw_body.as_ref()
+    }
+
+    pub(crate) fn set_raw_body(&mut self, raw_body: Bytes) {
+        self.raw_body = Some(raw_body);
+    }
+
     /// Mutable reference to the contained message.
     /// ## WARNING!
     /// if you serialized or retreived a header before doing this, it is now incorrect!
@@ -78,28 +258,62 @@ where
     /// if the message could not be deserialized
     pub fn from_bytes<NH, NM>(
         data: &Bytes,
-        options: impl bincode::Options,
-    ) -> Result<MessageWrapper<NM, NH>, bincode::Error>
+        codec: &impl Codec,
+    ) -> Result<MessageWrapper<NM, NH>, CodecError>
     where
         NH: IsHeader,
         NM: Serialize + DeserializeOwned,
     {
-        Ok(MessageWrapper::new(options.deserialize(data)?))
+        Ok(MessageWrapper::new(codec.deserialize(data)?))
     }
 
     /// Attempts to deserialize a message from the provided data
     ///
     /// # Errors
     /// if the message could not be deserialized
-    pub fn from_slice<'nde, NH, NM>(
-        data: &'nde &[u8],
-        options: impl bincode::Options,
-    ) -> Result<MessageWrapper<NM, NH>, bincode::Error>
+    pub fn from_slice<NH, NM>(
+        data: &[u8],
+        codec: &impl Codec,
+    ) -> Result<MessageWrapper<NM, NH>, CodecError>
     where
         NH: IsHeader,
-        NM: Serialize + Deserialize<'nde>,
+        NM: Serialize + DeserializeOwned,
     {
-        Ok(MessageWrapper::new(options.deserialize(data)?))
+        Ok(MessageWrapper::new(codec.deserialize(data)?))
+    }
+}
+
+impl<M, H> MessageWrapper<M, H>
+where
+    M: FixedSizeMessage,
+    H: IsHeader,
+{
+    /// Serialize and combine the header and message,
+    /// using [`FixedSizeMessage::SERIALIZED_SIZE`] for the header instead of computing the size
+    #[allow(clippy::missing_errors_doc)]
+    pub fn serialize_fixed(&self, codec: &impl Codec) -> Result<Bytes, CodecError> {
+        let mut header = H::try_new(M::SERIALIZED_SIZE).map_err(|e| CodecError::new(e.to_string()))?;
+        header.set_extension(self.extension_bytes()).map_err(CodecError::new)?;
+        header.set_kind(self.kind).map_err(CodecError::new)?;
+        let mut buf = header.as_bytes_mut();
+        // the size is known, so everything can be allocated up front
+        buf.reserve(usize::try_from(M::SERIALIZED_SIZE).unwrap_or(0));
+        codec.serialize_into((&mut buf).writer(), &self.inner)?;
+        Ok(buf.freeze())
+    }
+}
+
+impl<M, H> MessageWrapper<M, H>
+where
+    M: Serialize + Default,
+    H: IsHeader,
+{
+    /// Creates a new message wrapper around the default value of the message.
+    ///
+    /// this is mostly useful for control messages with no contents, like `()`
+    /// which is sent as a header with a body size of zero
+    pub fn empty() -> Self {
+        Self::new(M::default())
     }
 }
 
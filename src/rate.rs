@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::clock::SharedClock;
+
+/// A token bucket, for limiting how often something happens.
+///
+/// the bucket holds up to `per_sec` tokens (allowing bursts of that size),
+/// and refills at `per_sec` tokens per second
+#[derive(Debug, Clone)]
+pub(crate) struct TokenBucket {
+    per_sec: f64,
+    tokens: f64,
+    last_refill: Instant,
+    clock: SharedClock,
+}
+
+impl TokenBucket {
+    /// Creates a new, full, bucket that refills by the time from `clock`.
+    /// a rate of 0 is raised to 1, as a bucket that never refills would block forever
+    pub(crate) fn new(per_sec: u32, clock: SharedClock) -> Self {
+        let per_sec = f64::from(per_sec.max(1));
+        Self {
+            per_sec,
+            tokens: per_sec,
+            last_refill: clock.now(),
+            clock,
+        }
+    }
+
+    /// Gets the rate the bucket refills at
+    pub(crate) fn per_sec(&self) -> u32 {
+        // always created from a u32
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let per_sec = self.per_sec as u32;
+        per_sec
+    }
+
+    fn refill(&mut self) {
+        let now = self.clock.now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_sec).min(self.per_sec);
+        self.last_refill = now;
+    }
+
+    /// Takes a token if one is available
+    pub(crate) fn try_take(&mut self) -> bool {
+        self.refill();
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Gets how long untill a token is available
+    pub(crate) fn time_until_token(&mut self) -> Duration {
+        self.refill();
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / self.per_sec)
+        }
+    }
+
+    /// Waits untill a token is available, without taking it
+    pub(crate) async fn wait_for_token(&mut self) {
+        let wait = self.time_until_token();
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Waits untill a token is available, then takes it
+    pub(crate) async fn take(&mut self) {
+        while !self.try_take() {
+            self.wait_for_token().await;
+        }
+    }
+}
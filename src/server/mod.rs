@@ -1,17 +1,25 @@
 use std::net::SocketAddr;
 use std::fmt::Debug;
 use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use serde::{de::DeserializeOwned, Serialize};
-use tokio::net::{TcpListener, ToSocketAddrs};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
 
-use crate::socket::{self, interface::SocketUtils};
+use crate::socket::{self, interface::SocketUtils, ReadOnlyConnection};
+
+mod registry;
+mod stream;
+pub use registry::{ConnectionRegistry, Failed};
+pub use stream::MessageStream;
 
 pub mod error {
     #[derive(thiserror::Error, Debug)]
     pub enum BindServerError {
         #[error("Failed to bind listener!\n{0}")]
         ListenerBindError(#[from] std::io::Error),
+        #[error("Failed to bind listener, the address {addr} is already in use!")]
+        AddressInUse { addr: std::net::SocketAddr },
     }
 
     #[derive(thiserror::Error, Debug)]
@@ -21,102 +29,750 @@ pub mod error {
         #[from]
         source: std::io::Error,
     }
+
+    #[derive(thiserror::Error, Debug)]
+    pub enum AcceptSessionError<H: crate::header::IsHeader + std::fmt::Debug> {
+        #[error("{0}")]
+        Accept(#[from] AcceptConnectionError),
+        #[error("Handshake with client failed!\n{0}")]
+        Handshake(#[from] crate::session::error::HandshakeError<H>),
+    }
+}
+
+/// A id that is unique to each connection, for correlating logs over a connections lifetime.
+///
+/// by default ids are assigned in increasing order as connections are accepted, and are never reused for the life of the process.
+/// a server can use a different scheme with [`Server::set_id_generator`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConnectionId(u128);
+
+impl ConnectionId {
+    /// Creates a id from a number, for example one produced by a [`IdGenerator`]
+    pub fn new(id: u128) -> Self {
+        Self(id)
+    }
+
+    /// Gets the id as a number
+    pub fn as_u128(&self) -> u128 {
+        self.0
+    }
 }
 
+impl std::fmt::Display for ConnectionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "conn#{}", self.0)
+    }
+}
+
+/// A source of unique ids, used wherever the crate assigns them (currently [`ConnectionId`]s).
+///
+/// this is for setups where ids have to be unique across many processes or machines,
+/// for example by producing UUIDs or snowflake ids instead of counting up
+pub trait IdGenerator: Send + Sync {
+    /// Produces a new id, that has not been produced before
+    fn next_id(&self) -> u128;
+}
+
+/// A [`IdGenerator`] that counts up from zero
+#[derive(Debug, Default)]
+pub struct CounterIdGenerator {
+    next: AtomicU64,
+}
+
+impl CounterIdGenerator {
+    /// Creates a new generator, starting at zero
+    pub const fn new() -> Self {
+        Self {
+            next: AtomicU64::new(0),
+        }
+    }
+}
+
+impl IdGenerator for CounterIdGenerator {
+    fn next_id(&self) -> u128 {
+        u128::from(self.next.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// generator used when no other one is set, shared so ids are unique across all servers in the process
+static GLOBAL_IDS: CounterIdGenerator = CounterIdGenerator::new();
+
 /// A connection to a client.
 /// 
 /// produced by the servers accept method
-pub struct ClientConnection<H, M, O>
+pub struct ClientConnection<H, M, C>
 where
     H: crate::header::IsHeader + Debug + Clone,
     M: Serialize + DeserializeOwned,
-    O: bincode::Options + Clone,
+    C: crate::codec::Codec + Clone,
 {
-    sock_interface: SocketUtils<H, M, O>
+    sock_interface: SocketUtils<H, M, C>,
+    id: ConnectionId,
+    /// slot in the servers connection limit, released when the connection is dropped
+    permit: Option<tokio::sync::OwnedSemaphorePermit>,
 }
 
-impl<H, M, O> ClientConnection<H, M, O>
+impl<H, M, C> ClientConnection<H, M, C>
 where
     H: crate::header::IsHeader + Debug + Clone,
     M: Serialize + DeserializeOwned,
-    O: bincode::Options + Clone,
+    C: crate::codec::Codec + Clone,
 {
-    pub(crate) fn new(addr: SocketAddr, reader: socket::Reader<H, M, O>, writer: socket::Writer<H, M, O>) -> Self {
+    pub(crate) fn new(
+        addr: SocketAddr,
+        reader: socket::Reader<H, M, C>,
+        writer: socket::Writer<H, M, C>,
+        id: ConnectionId,
+    ) -> Self {
         Self {
-            sock_interface: SocketUtils::new(reader, writer, addr)
+            sock_interface: SocketUtils::new(reader, writer, addr),
+            id,
+            permit: None,
         }
     }
+
+    fn with_permit(mut self, permit: Option<tokio::sync::OwnedSemaphorePermit>) -> Self {
+        self.permit = permit;
+        self
+    }
+
+    /// Gets the id of this connection, which stays the same for as long as the connection is open
+    pub fn id(&self) -> ConnectionId {
+        self.id
+    }
+
+    /// Turns the connection into a stream of received messages, see [`MessageStream`]
+    ///
+    /// this is for simple servers, where handlers only need the message itself
+    pub fn into_message_stream(self) -> MessageStream<H, M, C> {
+        MessageStream::new(self)
+    }
+
+    /// Consumes the connection, recovering the raw `TcpStream`.
+    ///
+    /// for more info see [`SocketUtils::into_stream`].
+    /// the connection no longer counts towards [`Server::set_max_connections`] after this
+    ///
+    /// # Errors
+    /// if the halfs of the socket could not be joined
+    ///
+    /// [`SocketUtils::into_stream`]: crate::socket::interface::_SocketUtils::into_stream
+    pub fn into_stream(self) -> Result<(TcpStream, bytes::BytesMut), tokio::net::tcp::ReuniteError> {
+        self.sock_interface.into_stream()
+    }
+
+    /// Creates a connection from a already accepted stream, for example one from [`Server::accept_raw`]
+    ///
+    /// # Args
+    /// `addr` is the address of the client, and
+    /// `codec` is used for serializing and deserializing messages, see [`Codec`] for more info
+    ///
+    /// [`Codec`]: crate::codec::Codec
+    pub fn from_stream(stream: TcpStream, addr: SocketAddr, codec: C) -> Self {
+        let (read_half, write_half) = socket::split_stream(stream, codec);
+        Self::new(addr, read_half, write_half, ConnectionId::new(GLOBAL_IDS.next_id()))
+    }
 }
 
-impl<H, M, O> Deref for ClientConnection<H, M, O>
+impl<H, M, C> Deref for ClientConnection<H, M, C>
 where
     H: crate::header::IsHeader + Debug + Clone,
     M: Serialize + DeserializeOwned,
-    O: bincode::Options + Clone,
+    C: crate::codec::Codec + Clone,
 {
-    type Target = SocketUtils<H, M, O>;
+    type Target = SocketUtils<H, M, C>;
     fn deref(&self) -> &Self::Target {
         &self.sock_interface
     }
 }
 
-impl<H, M, O> DerefMut for ClientConnection<H, M, O>
+impl<H, M, C> DerefMut for ClientConnection<H, M, C>
 where
     H: crate::header::IsHeader + Debug + Clone,
     M: Serialize + DeserializeOwned,
-    O: bincode::Options + Clone,
+    C: crate::codec::Codec + Clone,
 {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.sock_interface
     }
 }
 
+/// Returns if an error from accepting a connection only affects that connection,
+/// and the listener can keep accepting new ones
+pub fn is_transient_accept_error(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        std::io::ErrorKind::ConnectionAborted | std::io::ErrorKind::Interrupted
+    )
+}
+
+/// Calls `accept` untill it succeeds or returns an error that is not transient
+async fn skip_transient<T, F, Fut>(mut accept: F) -> std::io::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = std::io::Result<T>>,
+{
+    loop {
+        match accept().await {
+            Err(e) if is_transient_accept_error(&e) => continue,
+            res => return res,
+        }
+    }
+}
+
+/// What a [`Server`] does with new connections when it already has the maximum number,
+/// see [`Server::set_max_connections`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AtCapacity {
+    /// stop accepting untill a connection is closed. new connections wait in the OS listen backlog
+    #[default]
+    Wait,
+    /// accept new connections and immediately close them, counting them in [`Server::rejected_connections`]
+    Reject,
+}
+
+/// A handle that stops a running [`Server`], see [`Server::shutdown_handle`]
+///
+/// clones all refer to the same server
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownHandle {
+    token: tokio_util::sync::CancellationToken,
+}
+
+impl ShutdownHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stops the server. this can be called any number of times
+    pub fn shutdown(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Waits untill [`shutdown`] is called
+    ///
+    /// [`shutdown`]: ShutdownHandle::shutdown
+    pub async fn wait(&self) {
+        self.token.cancelled().await;
+    }
+}
+
 /// A Server wrapping a TcpListener,
 /// with utils for accepting new clients.
-pub struct Server<O>
+pub struct Server<C>
 where
-    O: bincode::Options + Clone,
+    C: crate::codec::Codec + Clone,
 {
     listener: TcpListener,
-    bincode_options: O,
+    codec: C,
+    socket_config: socket::SocketConfig,
+    /// limits how many connections are accepted per second
+    accept_limiter: Option<crate::rate::TokenBucket>,
+    /// used to assign ids to accepted connections, `GLOBAL_IDS` if `None`
+    id_generator: Option<std::sync::Arc<dyn IdGenerator>>,
+    /// triggered to stop [`Server::run`] and [`Server::run_until`]
+    shutdown: ShutdownHandle,
+    /// permits for open connections, and the maximum number
+    connection_limit: Option<(std::sync::Arc<tokio::sync::Semaphore>, usize)>,
+    at_capacity: AtCapacity,
+    rejected: u64,
+    /// connections closed because the socket config could not be applied
+    setup_failures: u64,
+    /// used by the accept rate limit, and given to accepted connections
+    clock: crate::clock::SharedClock,
 }
 
-impl<O> Server<O>
+impl<C> Server<C>
 where
-    O: bincode::Options + Clone + Send,
+    C: crate::codec::Codec + Clone + Send,
 {
     /// Binds the server to the provided adress.
-    /// The server does not listen for new connections imediataly, for that you need `.listen()`
-    ///
-    /// The provided callback will be used when running the server,
-    /// it is run in a new task, so it can be asynchronously blocking, although should not be non async blocking
+    /// The server does not accept new connections imediataly, for that use [`accept`] or [`run`]
     ///
     /// # Errors
-    /// if it could not sucessfully bind to the provided adress
+    /// if it could not sucessfully bind to the provided adress.
+    /// if the address is already in use (for example by another server) [`AddressInUse`] is returned,
+    /// so that can be handled separately (for example by picking another port)
+    ///
+    /// [`AddressInUse`]: error::BindServerError::AddressInUse
+    /// [`accept`]: Server::accept
+    /// [`run`]: Server::run
     pub async fn bind<A: ToSocketAddrs>(
         addr: A,
-        bincode_opts: O,
-    ) -> Result<Server<O>, error::BindServerError> {
-        let listener = TcpListener::bind(addr).await?;
+        codec: C,
+    ) -> Result<Server<C>, error::BindServerError> {
+        let mut last_err = None;
+        let mut listener = None;
+        for addr in tokio::net::lookup_host(addr).await? {
+            match TcpListener::bind(addr).await {
+                Ok(l) => {
+                    listener = Some(l);
+                    break;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+                    last_err = Some(error::BindServerError::AddressInUse { addr });
+                }
+                Err(e) => last_err = Some(e.into()),
+            }
+        }
+        let listener = match listener {
+            Some(listener) => listener,
+            None => {
+                return Err(last_err.unwrap_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "could not resolve to any addresses",
+                    )
+                    .into()
+                }))
+            }
+        };
         Ok(Self {
             listener,
-            bincode_options: bincode_opts,
+            codec,
+            socket_config: socket::SocketConfig::default(),
+            accept_limiter: None,
+            id_generator: None,
+            shutdown: ShutdownHandle::new(),
+            connection_limit: None,
+            at_capacity: AtCapacity::default(),
+            rejected: 0,
+            setup_failures: 0,
+            clock: crate::clock::default_clock(),
         })
     }
 
+    /// Sets the receive buffer size (`SO_RCVBUF`) used for newly accepted connections.
+    ///
+    /// this is set on the listening socket, and accepted connections inherit it from there,
+    /// so it is already in place during the handshake (which is where the TCP window scale is picked).
+    /// connections that finished their handshake before this was called keep the old size.
+    /// the OS may round or otherwise adjust the size, use [`recv_buffer_size`] to get the effective value
+    ///
+    /// # Errors
+    /// if the OS rejects the option
+    ///
+    /// [`recv_buffer_size`]: Server::recv_buffer_size
+    pub fn set_recv_buffer_size(&mut self, size: usize) -> std::io::Result<()> {
+        socket2::SockRef::from(&self.listener).set_recv_buffer_size(size)
+    }
+
+    /// Gets the receive buffer size of the listening socket, which newly accepted connections inherit
+    ///
+    /// the effective size of a connection can be read from it with [`recv_buffer_size`]
+    ///
+    /// [`recv_buffer_size`]: crate::socket::interface::_SocketUtils::recv_buffer_size
+    pub fn recv_buffer_size(&self) -> std::io::Result<usize> {
+        socket2::SockRef::from(&self.listener).recv_buffer_size()
+    }
+
+    /// Sets the send buffer size (`SO_SNDBUF`) used for newly accepted connections.
+    ///
+    /// like [`set_recv_buffer_size`] this is set on the listening socket and inherited by accepted connections
+    ///
+    /// # Errors
+    /// if the OS rejects the option
+    ///
+    /// [`set_recv_buffer_size`]: Server::set_recv_buffer_size
+    pub fn set_send_buffer_size(&mut self, size: usize) -> std::io::Result<()> {
+        socket2::SockRef::from(&self.listener).set_send_buffer_size(size)
+    }
+
+    /// Gets the send buffer size of the listening socket, which newly accepted connections inherit
+    ///
+    /// the effective size of a connection can be read from it with [`send_buffer_size`]
+    ///
+    /// [`send_buffer_size`]: crate::socket::interface::_SocketUtils::send_buffer_size
+    pub fn send_buffer_size(&self) -> std::io::Result<usize> {
+        socket2::SockRef::from(&self.listener).send_buffer_size()
+    }
+
+    /// Sets the options (`TCP_NODELAY`, keepalive) set on newly accepted connections, see [`SocketConfig`]
+    ///
+    /// if setting them fails for a connection, that connection is closed and the server moves on to the next one,
+    /// counting it in [`setup_failures`]
+    ///
+    /// [`setup_failures`]: Server::setup_failures
+    /// [`SocketConfig`]: socket::SocketConfig
+    pub fn set_socket_config(&mut self, config: socket::SocketConfig) {
+        self.socket_config = config;
+    }
+
+    pub fn socket_config(&self) -> &socket::SocketConfig {
+        &self.socket_config
+    }
+
+    /// Limits how many connections are accepted per second, or `None` for no limit.
+    ///
+    /// this uses a token bucket, allowing bursts of up to `per_sec` connections.
+    /// once the limit is hit the accept methods wait before accepting the next connection.
+    /// while waiting, new connections are queued by the OS in the listeners backlog
+    /// (and once that is full, further connection attempts are refused or dropped, depending on the OS),
+    /// so a flood of connections is absorbed there instead of by spawning a task for each one.
+    /// a limit of 0 would stop accepting for good, so it is raised to 1 connection per second
+    /// (and [`accept_rate`] reports 1)
+    ///
+    /// [`accept_rate`]: Server::accept_rate
+    pub fn set_accept_rate(&mut self, per_sec: Option<u32>) {
+        self.accept_limiter =
+            per_sec.map(|per_sec| crate::rate::TokenBucket::new(per_sec, self.clock.clone()));
+    }
+
+    /// Gets the limit on connections accepted per second, after raising a limit of 0 to 1
+    pub fn accept_rate(&self) -> Option<u32> {
+        self.accept_limiter.as_ref().map(crate::rate::TokenBucket::per_sec)
+    }
+
+    /// Sets where the server gets the current time from, for the accept rate limit (see [`set_accept_rate`]).
+    /// connections accepted after this is set use it too, see [`SocketUtils::set_clock`]
+    ///
+    /// [`set_accept_rate`]: Server::set_accept_rate
+    /// [`SocketUtils::set_clock`]: crate::socket::interface::_SocketUtils::set_clock
+    pub fn set_clock(&mut self, clock: crate::clock::SharedClock) {
+        self.clock = clock;
+        self.set_accept_rate(self.accept_rate());
+    }
+
+    /// Sets the generator used to assign [`ConnectionId`]s to accepted connections.
+    ///
+    /// by default a counter shared by the whole process is used
+    pub fn set_id_generator(&mut self, generator: std::sync::Arc<dyn IdGenerator>) {
+        self.id_generator = Some(generator);
+    }
+
+    /// Limits how many connections can be open at once, or `None` for no limit.
+    ///
+    /// each [`ClientConnection`] from [`accept`], [`accept_session`], [`run`] and [`run_until`] takes a slot,
+    /// which is freed when it is dropped. what happens when there are no free slots is set with [`set_at_capacity`].
+    /// connections from [`accept_raw`] and [`accept_read_only`] are not counted.
+    ///
+    /// changing the limit only affects connections accepted after it is changed
+    ///
+    /// [`accept`]: Server::accept
+    /// [`accept_session`]: Server::accept_session
+    /// [`run`]: Server::run
+    /// [`run_until`]: Server::run_until
+    /// [`set_at_capacity`]: Server::set_at_capacity
+    /// [`accept_raw`]: Server::accept_raw
+    /// [`accept_read_only`]: Server::accept_read_only
+    pub fn set_max_connections(&mut self, max: Option<usize>) {
+        self.connection_limit =
+            max.map(|max| (std::sync::Arc::new(tokio::sync::Semaphore::new(max)), max));
+    }
+
+    pub fn max_connections(&self) -> Option<usize> {
+        self.connection_limit.as_ref().map(|(_, max)| *max)
+    }
+
+    /// Gets the number of counted connections that are currently open, see [`set_max_connections`]
+    ///
+    /// [`set_max_connections`]: Server::set_max_connections
+    pub fn active_connections(&self) -> Option<usize> {
+        self.connection_limit
+            .as_ref()
+            .map(|(slots, max)| max - slots.available_permits())
+    }
+
+    /// Sets what happens to new connections when the limit is reached, see [`AtCapacity`]
+    pub fn set_at_capacity(&mut self, policy: AtCapacity) {
+        self.at_capacity = policy;
+    }
+
+    pub fn at_capacity(&self) -> AtCapacity {
+        self.at_capacity
+    }
+
+    /// Gets the number of connections closed because the server was at capacity, with [`AtCapacity::Reject`]
+    pub fn rejected_connections(&self) -> u64 {
+        self.rejected
+    }
+
+    /// Gets the number of connections closed because applying the [`SocketConfig`] to them failed
+    ///
+    /// [`SocketConfig`]: socket::SocketConfig
+    pub fn setup_failures(&self) -> u64 {
+        self.setup_failures
+    }
+
+    /// Accepts a new connection, taking a slot from the connection limit if there is one
+    async fn accept_limited(
+        &mut self,
+    ) -> Result<(TcpStream, SocketAddr, Option<tokio::sync::OwnedSemaphorePermit>), error::AcceptConnectionError> {
+        let Some((slots, _)) = &self.connection_limit else {
+            let (stream, addr) = self.accept_stream().await?;
+            return Ok((stream, addr, None));
+        };
+        let slots = slots.clone();
+        match self.at_capacity {
+            AtCapacity::Wait => {
+                // wait for a slot before accepting, so connections queue up in the backlog
+                let permit = slots
+                    .acquire_owned()
+                    .await
+                    .expect("the semaphore is never closed");
+                let (stream, addr) = self.accept_stream().await?;
+                Ok((stream, addr, Some(permit)))
+            }
+            AtCapacity::Reject => loop {
+                let (stream, addr) = self.accept_stream().await?;
+                match slots.clone().try_acquire_owned() {
+                    Ok(permit) => return Ok((stream, addr, Some(permit))),
+                    Err(_) => {
+                        // dropping the stream closes it
+                        drop(stream);
+                        self.rejected += 1;
+                    }
+                }
+            },
+        }
+    }
+
+    /// Creates a empty [`ConnectionRegistry`], that uses the same codec as this server
+    pub fn new_registry<H, M>(&self) -> ConnectionRegistry<H, M, C>
+    where
+        H: crate::header::IsHeader + Debug + Clone,
+        M: Serialize + DeserializeOwned,
+    {
+        ConnectionRegistry::new(self.codec.clone())
+    }
+
+    /// Wraps a newly accepted stream, giving it an id and the servers clock
+    fn new_connection<H, M>(
+        &self,
+        stream: TcpStream,
+        addr: SocketAddr,
+        permit: Option<tokio::sync::OwnedSemaphorePermit>,
+    ) -> ClientConnection<H, M, C>
+    where
+        H: crate::header::IsHeader + Debug + Clone,
+        M: Serialize + DeserializeOwned,
+    {
+        let (read_half, write_half) = socket::split_stream(stream, self.codec.clone());
+        let mut conn = ClientConnection::new(addr, read_half, write_half, self.next_connection_id())
+            .with_permit(permit);
+        conn.sock_interface.set_clock(self.clock.clone());
+        conn
+    }
+
+    /// Produces the id for a newly accepted connection
+    fn next_connection_id(&self) -> ConnectionId {
+        match &self.id_generator {
+            Some(generator) => ConnectionId::new(generator.next_id()),
+            None => ConnectionId::new(GLOBAL_IDS.next_id()),
+        }
+    }
+
+    /// Accepts a new connection from a client.
+    ///
+    /// Errors from the listener are classified as either transient or fatal:
+    /// - transient errors (`ConnectionAborted`, `Interrupted`) are caused by a single
+    ///   connection (for example a client that closed during the handshake) and are skipped,
+    ///   `accept` then keeps waiting for the next connection
+    /// - any other error (for example `EMFILE`, running out of file descriptors) is fatal and returned
+    ///
+    /// # Errors
+    /// if the listener returns a fatal error
     pub async fn accept<H, M>(
         &mut self,
     ) -> Result<
-        ClientConnection<H, M, O>,
+        ClientConnection<H, M, C>,
         error::AcceptConnectionError,
     >
     where
         H: crate::header::IsHeader + Clone + Send + Debug,
         M: Serialize + DeserializeOwned + Send,
     {
-        let conn = self.listener.accept().await?;
-        let (read_half, write_half) = socket::split_stream(conn.0, self.bincode_options.clone());
-        Ok(ClientConnection::new(conn.1, read_half, write_half))
+        let (stream, addr, permit) = self.accept_limited().await?;
+        Ok(self.new_connection(stream, addr, permit))
+    }
+
+    /// Accepts a new connection from a client, returning the raw stream without wrapping it.
+    ///
+    /// this allows inspecting the stream before commiting to the framing, for example peeking
+    /// at the first bytes (with `TcpStream::peek`) to tell protocols apart on a shared port.
+    /// the stream can then be wrapped with [`ClientConnection::from_stream`].
+    ///
+    /// errors are handled the same way as [`accept`], see it for more info
+    ///
+    /// # Errors
+    /// if the listener returns a fatal error
+    ///
+    /// [`accept`]: Server::accept
+    pub async fn accept_raw(&mut self) -> Result<(TcpStream, SocketAddr), error::AcceptConnectionError> {
+        self.accept_stream().await
+    }
+
+    /// Accepts a new connection from a client, and starts or resumes its session in `sessions`.
+    ///
+    /// this waits for the handshake to complete, so a slow client holds up accepting other connections.
+    /// servers that care should use [`accept_raw`] and do the handshake with [`SessionRegistry::handshake`] in another task.
+    /// see the [`session`] module for more info
+    ///
+    /// # Errors
+    /// if the listener returns a fatal error, or the handshake failed
+    ///
+    /// [`accept_raw`]: Server::accept_raw
+    /// [`SessionRegistry::handshake`]: crate::session::SessionRegistry::handshake
+    /// [`session`]: crate::session
+    pub async fn accept_session<H, M, S>(
+        &mut self,
+        sessions: &mut crate::session::SessionRegistry<S>,
+        new_session: impl FnOnce() -> S,
+    ) -> Result<
+        (ClientConnection<H, M, C>, crate::session::SessionStart),
+        error::AcceptSessionError<H>,
+    >
+    where
+        H: crate::header::IsHeader + Clone + Send + Debug,
+        M: Serialize + DeserializeOwned + Send,
+    {
+        let (mut stream, addr, permit) = self.accept_limited().await?;
+        let start = sessions
+            .handshake(&mut stream, &self.codec, new_session)
+            .await?;
+        Ok((self.new_connection(stream, addr, permit), start))
+    }
+
+    /// Accepts a new connection from a client, that will only be read from.
+    ///
+    /// errors are handled the same way as [`accept`], see it for more info
+    ///
+    /// # Errors
+    /// if the listener returns a fatal error
+    ///
+    /// [`accept`]: Server::accept
+    pub async fn accept_read_only<H, M>(
+        &mut self,
+    ) -> Result<ReadOnlyConnection<H, M, C>, error::AcceptConnectionError>
+    where
+        H: crate::header::IsHeader + Clone + Send + Debug,
+        M: Serialize + DeserializeOwned + Send,
+    {
+        let (stream, addr) = self.accept_stream().await?;
+        let (read_half, write_half) = stream.into_split();
+        let mut reader = socket::Reader::new(read_half, self.codec.clone());
+        reader.set_clock(self.clock.clone());
+        Ok(ReadOnlyConnection::new(reader, write_half, addr))
+    }
+
+    /// Accepts a new connection, skipping transient errors and configuring the socket
+    async fn accept_stream(&mut self) -> Result<(TcpStream, SocketAddr), error::AcceptConnectionError> {
+        if let Some(limiter) = &mut self.accept_limiter {
+            limiter.take().await;
+        }
+        loop {
+            let listener = &self.listener;
+            let (stream, addr) = skip_transient(|| listener.accept()).await?;
+            // this only affects this connection, so it is dropped instead of stopping the server
+            if self.socket_config.apply(&stream).is_err() {
+                drop(stream);
+                self.setup_failures += 1;
+                continue;
+            }
+            return Ok((stream, addr));
+        }
+    }
+
+    /// Gets a handle that stops [`run`] and [`run_until`] when it is triggered.
+    ///
+    /// this can be cloned and triggered from anywhere (for example a ctrl-c handler)
+    ///
+    /// [`run`]: Server::run
+    /// [`run_until`]: Server::run_until
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Accepts connections, running `handler` for each one in a new task.
+    ///
+    /// if a handler panics only its task is affected, the server keeps accepting connections.
+    /// the loop runs untill the [`shutdown_handle`] is triggered, handlers that are still running
+    /// when it stops are left running in the background. to wait for them use [`run_until`]
+    ///
+    /// # Errors
+    /// if the listener returns a fatal error, see [`accept`]
+    ///
+    /// [`shutdown_handle`]: Server::shutdown_handle
+    /// [`run_until`]: Server::run_until
+    /// [`accept`]: Server::accept
+    pub async fn run<H, M, F, Fut>(mut self, handler: F) -> Result<(), error::AcceptConnectionError>
+    where
+        H: crate::header::IsHeader + Clone + Send + Debug + 'static,
+        M: Serialize + DeserializeOwned + Send + 'static,
+        C: 'static,
+        F: Fn(ClientConnection<H, M, C>) -> Fut,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let shutdown = self.shutdown.clone();
+        loop {
+            let conn = tokio::select! {
+                biased;
+                () = shutdown.wait() => return Ok(()),
+                conn = self.accept() => conn?,
+            };
+            // panics are caught by the runtime, and only end that task
+            tokio::spawn(handler(conn));
+        }
+    }
+
+    /// Accepts connections, running `handler` for each one in a new task, untill `shutdown` completes
+    /// (or the [`shutdown_handle`] is triggered).
+    ///
+    /// once shutdown starts no new connections are accepted, and running handlers get up to `drain`
+    /// to finish. any still running after that are aborted. the listener is closed when this returns,
+    /// and a connection is never left half accepted (accepting is cancelation safe).
+    /// handler panics are handled the same way as [`run`]
+    ///
+    /// # Returns
+    /// the number of handlers that had to be aborted
+    ///
+    /// # Errors
+    /// if the listener returns a fatal error, see [`accept`]. running handlers are aborted in that case
+    ///
+    /// [`shutdown_handle`]: Server::shutdown_handle
+    /// [`run`]: Server::run
+    /// [`accept`]: Server::accept
+    pub async fn run_until<H, M, F, Fut>(
+        mut self,
+        handler: F,
+        shutdown: impl std::future::Future<Output = ()>,
+        drain: std::time::Duration,
+    ) -> Result<usize, error::AcceptConnectionError>
+    where
+        H: crate::header::IsHeader + Clone + Send + Debug + 'static,
+        M: Serialize + DeserializeOwned + Send + 'static,
+        C: 'static,
+        F: Fn(ClientConnection<H, M, C>) -> Fut,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let handle = self.shutdown.clone();
+        let mut tasks = tokio::task::JoinSet::new();
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                biased;
+                () = &mut shutdown => break,
+                () = handle.wait() => break,
+                // reap finished handlers, so the set does not grow forever
+                Some(_) = tasks.join_next(), if !tasks.is_empty() => {}
+                conn = self.accept() => {
+                    tasks.spawn(handler(conn?));
+                }
+            }
+        }
+        // stop accepting before draining
+        drop(self);
+        let _ = tokio::time::timeout(drain, async {
+            while tasks.join_next().await.is_some() {}
+        })
+        .await;
+        let aborted = tasks.len();
+        tasks.shutdown().await;
+        Ok(aborted)
     }
 
     pub fn as_listener(&self) -> &TcpListener {
@@ -131,3 +787,49 @@ where
         self.listener
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, collections::VecDeque, future::Ready, io, rc::Rc};
+
+    use super::*;
+
+    /// Returns the scripted results in order like a listener would, and how many times it was called
+    fn mock(results: Vec<io::Result<u32>>) -> (impl FnMut() -> Ready<io::Result<u32>>, Rc<Cell<usize>>) {
+        let mut results = VecDeque::from(results);
+        let calls = Rc::new(Cell::new(0));
+        let counted = calls.clone();
+        let accept = move || {
+            counted.set(counted.get() + 1);
+            std::future::ready(results.pop_front().expect("accept called too many times"))
+        };
+        (accept, calls)
+    }
+
+    #[tokio::test]
+    async fn transient_accept_errors_are_skipped() {
+        let (accept, calls) = mock(vec![
+            Err(io::ErrorKind::ConnectionAborted.into()),
+            Err(io::ErrorKind::Interrupted.into()),
+            Err(io::ErrorKind::ConnectionAborted.into()),
+            Ok(1),
+            Ok(2),
+        ]);
+        assert_eq!(skip_transient(accept).await.unwrap(), 1);
+        assert_eq!(calls.get(), 4);
+    }
+
+    #[tokio::test]
+    async fn fatal_accept_errors_are_returned() {
+        // EMFILE, out of file descriptors
+        let (accept, calls) = mock(vec![
+            Err(io::ErrorKind::Interrupted.into()),
+            Err(io::Error::from_raw_os_error(24)),
+            Ok(1),
+        ]);
+        let err = skip_transient(accept).await.unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(24));
+        assert_eq!(calls.get(), 2);
+        assert!(!is_transient_accept_error(&io::ErrorKind::PermissionDenied.into()));
+    }
+}
@@ -0,0 +1,142 @@
+use std::{collections::HashMap, fmt::Debug};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{ClientConnection, ConnectionId};
+use crate::socket::write::{
+    error::{QueueError, SeriError, WriteError},
+    QueueSnapshot,
+};
+
+/// Connections removed from a [`ConnectionRegistry`] because they failed, along with why
+pub type Failed<H, M, C, E> = Vec<(ClientConnection<H, M, C>, E)>;
+
+/// A set of connections, keyed by their [`ConnectionId`]s, that messages can be broadcast to.
+///
+/// broadcast messages are serialized once, and the same buffer is queued on every connection,
+/// so broadcasting to many clients costs about the same as sending to one (plus the writes)
+pub struct ConnectionRegistry<H, M, C>
+where
+    H: crate::header::IsHeader + Debug + Clone,
+    M: Serialize + DeserializeOwned,
+    C: crate::codec::Codec + Clone,
+{
+    connections: HashMap<ConnectionId, ClientConnection<H, M, C>>,
+    codec: C,
+}
+
+impl<H, M, C> ConnectionRegistry<H, M, C>
+where
+    H: crate::header::IsHeader + Debug + Clone,
+    M: Serialize + DeserializeOwned,
+    C: crate::codec::Codec + Clone,
+{
+    /// Creates a new, empty registry. `codec` is used to serialize broadcast messages,
+    /// and should be the same one the connections use
+    pub fn new(codec: C) -> Self {
+        Self {
+            connections: HashMap::new(),
+            codec,
+        }
+    }
+
+    /// Adds a connection, returning its id
+    pub fn insert(&mut self, conn: ClientConnection<H, M, C>) -> ConnectionId {
+        let id = conn.id();
+        self.connections.insert(id, conn);
+        id
+    }
+
+    pub fn remove(&mut self, id: ConnectionId) -> Option<ClientConnection<H, M, C>> {
+        self.connections.remove(&id)
+    }
+
+    pub fn get(&self, id: ConnectionId) -> Option<&ClientConnection<H, M, C>> {
+        self.connections.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: ConnectionId) -> Option<&mut ClientConnection<H, M, C>> {
+        self.connections.get_mut(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.connections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.connections.is_empty()
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = ConnectionId> + '_ {
+        self.connections.keys().copied()
+    }
+
+    pub fn iter_mut(
+        &mut self,
+    ) -> impl Iterator<Item = (ConnectionId, &mut ClientConnection<H, M, C>)> {
+        self.connections.iter_mut().map(|(id, conn)| (*id, conn))
+    }
+
+    /// Gets a summary of what is queued across every connection, see [`Writer::queue_snapshot`]
+    ///
+    /// [`Writer::queue_snapshot`]: crate::socket::write::Writer::queue_snapshot
+    pub fn queue_snapshot(&self) -> QueueSnapshot {
+        self.connections
+            .values()
+            .map(|conn| conn.as_writer().queue_snapshot())
+            .fold(QueueSnapshot::default(), QueueSnapshot::merge)
+    }
+
+    /// Queues `message` on every connection, serializing it only once.
+    ///
+    /// this only queues the message, use [`flush_all`] to send it.
+    /// connections that fail to queue it (because their send queue is full, or spilling over to disk failed)
+    /// are removed from the registry, and returned along with their errors
+    ///
+    /// if `H` has sequence numbers (see [`IsHeader::sequence`]) each connection numbers the message itself.
+    /// only the header is rebuilt for that, the body is still shared by every connection and not copied
+    ///
+    /// # Errors
+    /// if the message could not be serialized. nothing is queued in that case
+    ///
+    /// [`flush_all`]: ConnectionRegistry::flush_all
+    /// [`IsHeader::sequence`]: crate::header::IsHeader::sequence
+    pub fn broadcast(
+        &mut self,
+        message: &crate::msg::MessageWrapper<M, H>,
+    ) -> Result<Failed<H, M, C, QueueError>, SeriError> {
+        let bytes = message.serialize(&self.codec)?;
+        let mut failed = Vec::new();
+        for (id, conn) in &mut self.connections {
+            // cloning the bytes only bumps a refcount
+            if let Err(e) = conn.as_writer_mut().queue_raw(bytes.clone()) {
+                failed.push((*id, e));
+            }
+        }
+        Ok(self.remove_failed(failed))
+    }
+
+    /// Writes everything queued on every connection, one connection at a time.
+    ///
+    /// connections that fail to write (for example because the client disconnected)
+    /// are removed from the registry, and returned along with their errors
+    pub async fn flush_all(&mut self) -> Failed<H, M, C, WriteError> {
+        let mut failed = Vec::new();
+        for (id, conn) in &mut self.connections {
+            if let Err(e) = conn.flush_all().await {
+                failed.push((*id, e));
+            }
+        }
+        self.remove_failed(failed)
+    }
+
+    fn remove_failed<E>(
+        &mut self,
+        failed: Vec<(ConnectionId, E)>,
+    ) -> Failed<H, M, C, E> {
+        failed
+            .into_iter()
+            .filter_map(|(id, e)| Some((self.connections.remove(&id)?, e)))
+            .collect()
+    }
+}
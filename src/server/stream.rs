@@ -0,0 +1,109 @@
+use std::{
+    fmt::Debug,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_core::Stream;
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::ClientConnection;
+use crate::socket::interface::error::{UpdateError, WaitMessageError};
+
+type Pending<H, M, C> = Pin<
+    Box<
+        dyn Future<Output = (Box<ClientConnection<H, M, C>>, Result<M, UpdateError<H>>)>
+            + Send,
+    >,
+>;
+
+enum StreamState<H, M, C>
+where
+    H: crate::header::IsHeader + Debug + Clone,
+    M: Serialize + DeserializeOwned,
+    C: crate::codec::Codec + Clone,
+{
+    Idle(Box<ClientConnection<H, M, C>>),
+    Waiting(Pending<H, M, C>),
+    Done,
+}
+
+/// A [`ClientConnection`] as a stream of received messages, yielding the plain message instead of a [`MessageWrapper`].
+///
+/// produced by [`ClientConnection::into_message_stream`].
+/// the stream ends when the peer closes the connection between messages, or after the first error
+///
+/// [`MessageWrapper`]: crate::msg::MessageWrapper
+pub struct MessageStream<H, M, C>
+where
+    H: crate::header::IsHeader + Debug + Clone,
+    M: Serialize + DeserializeOwned,
+    C: crate::codec::Codec + Clone,
+{
+    state: StreamState<H, M, C>,
+}
+
+impl<H, M, C> MessageStream<H, M, C>
+where
+    H: crate::header::IsHeader + Debug + Clone,
+    M: Serialize + DeserializeOwned,
+    C: crate::codec::Codec + Clone,
+{
+    pub(crate) fn new(conn: ClientConnection<H, M, C>) -> Self {
+        Self {
+            state: StreamState::Idle(Box::new(conn)),
+        }
+    }
+
+    /// Gets the connection back, if the stream is not waiting for a message and has not ended
+    pub fn into_inner(self) -> Option<ClientConnection<H, M, C>> {
+        match self.state {
+            StreamState::Idle(conn) => Some(*conn),
+            _ => None,
+        }
+    }
+}
+
+impl<H, M, C> Stream for MessageStream<H, M, C>
+where
+    H: crate::header::IsHeader + Debug + Clone + Send + 'static,
+    H::Error: Send,
+    M: Serialize + DeserializeOwned + Send + 'static,
+    C: crate::codec::Codec + Clone + Send + 'static,
+{
+    type Item = Result<M, UpdateError<H>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match std::mem::replace(&mut self.state, StreamState::Done) {
+                StreamState::Idle(mut conn) => {
+                    self.state = StreamState::Waiting(Box::pin(async move {
+                        let res = match conn.wait_for_message().await {
+                            Ok(message) => Ok(message.into_message()),
+                            Err(WaitMessageError::Update(e)) => Err(e),
+                            Err(WaitMessageError::Read(e)) => Err(UpdateError::Read(e)),
+                            Err(WaitMessageError::Closed) => Err(UpdateError::Closed),
+                        };
+                        (conn, res)
+                    }));
+                }
+                StreamState::Waiting(mut fut) => {
+                    return match fut.as_mut().poll(cx) {
+                        Poll::Pending => {
+                            self.state = StreamState::Waiting(fut);
+                            Poll::Pending
+                        }
+                        Poll::Ready((conn, Ok(message))) => {
+                            self.state = StreamState::Idle(conn);
+                            Poll::Ready(Some(Ok(message)))
+                        }
+                        Poll::Ready((_conn, Err(UpdateError::Closed))) => Poll::Ready(None),
+                        Poll::Ready((_conn, Err(e))) => Poll::Ready(Some(Err(e))),
+                    };
+                }
+                StreamState::Done => return Poll::Ready(None),
+            }
+        }
+    }
+}
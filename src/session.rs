@@ -0,0 +1,248 @@
+//! Resuming sessions across reconnects.
+//!
+//! a client that loses its connection can present a [`ResumeToken`] when it reconnects,
+//! so the server can associate the new connection with the state it kept for the old one,
+//! instead of treating it as a brand new client.
+//!
+//! this is done with a handshake on the raw stream, before it is wrapped in a [`Client`] or [`ClientConnection`]:
+//! 1. the client sends [`HandshakeFrame::Hello`], with the token from its last session (if any)
+//! 2. the server looks the token up in its [`SessionRegistry`], and answers with [`HandshakeFrame::Welcome`],
+//!    containing the token for this session and if the old session was resumed
+//!
+//! handshake frames are framed with the same header type and encoded with the same codec as normal messages.
+//! use [`Client::connect_session`] and [`Server::accept_session`] to do this
+//!
+//! [`Client`]: crate::client::Client
+//! [`ClientConnection`]: crate::server::ClientConnection
+//! [`Client::connect_session`]: crate::client::Client::connect_session
+//! [`Server::accept_session`]: crate::server::Server::accept_session
+
+use std::{collections::HashMap, fmt::Debug};
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{codec::Codec, header::IsHeader};
+
+/// handshake frames are tiny, anything bigger than this is not a handshake
+const MAX_HANDSHAKE_SIZE: u64 = 1024;
+
+pub mod error {
+    use std::fmt::Debug;
+
+    #[derive(Debug, thiserror::Error)]
+    pub enum HandshakeError<H: crate::header::IsHeader + Debug> {
+        #[error("Failed to send or receive handshake!\n{0}")]
+        Io(#[from] std::io::Error),
+        #[error("Failed to encode or decode handshake!\n{0}")]
+        Codec(#[from] crate::codec::error::CodecError),
+        #[error("Failed to parse handshake header {0}")]
+        Header(H::Error),
+        #[error("Handshake frame claimed to be {size} bytes, which is too large")]
+        TooLarge { size: u64 },
+        #[error("Peer sent the wrong handshake frame")]
+        Unexpected,
+    }
+}
+
+/// A token identifying a session, issued by the server when the session is created.
+///
+/// tokens are random, but *not* cryptographically secure. they stop sessions being mixed up by accident,
+/// but should not be used to authenticate a client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ResumeToken([u8; 16]);
+
+impl ResumeToken {
+    /// Generates a new random token
+    pub fn generate() -> Self {
+        use std::hash::{BuildHasher, Hasher};
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let mut bytes = [0u8; 16];
+        for half in bytes.chunks_mut(8) {
+            let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+            hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+            half.copy_from_slice(&hasher.finish().to_le_bytes());
+        }
+        Self(bytes)
+    }
+
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+}
+
+/// The frames sent during the handshake, see the [module docs](self) for the order they are sent in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum HandshakeFrame {
+    /// sent by the client, with the token of the session it wants to resume
+    Hello { resume: Option<ResumeToken> },
+    /// sent by the server, with the token of the session the connection now belongs to
+    Welcome { token: ResumeToken, resumed: bool },
+}
+
+/// The result of a handshake
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionStart {
+    /// token for the session, present this when reconnecting to resume it
+    pub token: ResumeToken,
+    /// if a previous session was resumed. if this is false, a new session was created
+    /// (either no token was presented, or the server did not know it)
+    pub resumed: bool,
+}
+
+/// Server side state for every session, keyed by their tokens.
+///
+/// sessions are kept untill they are removed, since the server can not tell
+/// if a disconnected client is going to come back. remove sessions that are done with [`remove`]
+///
+/// [`remove`]: SessionRegistry::remove
+#[derive(Debug)]
+pub struct SessionRegistry<S> {
+    sessions: HashMap<ResumeToken, S>,
+}
+
+impl<S> SessionRegistry<S> {
+    pub fn new() -> Self {
+        Self {
+            sessions: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    pub fn get(&self, token: &ResumeToken) -> Option<&S> {
+        self.sessions.get(token)
+    }
+
+    pub fn get_mut(&mut self, token: &ResumeToken) -> Option<&mut S> {
+        self.sessions.get_mut(token)
+    }
+
+    /// Removes a session, so it can no longer be resumed
+    pub fn remove(&mut self, token: &ResumeToken) -> Option<S> {
+        self.sessions.remove(token)
+    }
+
+    /// Does the server side of the handshake on `stream`.
+    ///
+    /// if the client presents a known token that session is resumed,
+    /// otherwise a new session is created with the state from `new_session`.
+    /// the state can then be accessed with [`get_mut`]
+    ///
+    /// # Errors
+    /// if sending or receiving the handshake failed, or the client did not send a valid [`HandshakeFrame::Hello`]
+    ///
+    /// [`get_mut`]: SessionRegistry::get_mut
+    pub async fn handshake<H, C>(
+        &mut self,
+        stream: &mut (impl AsyncRead + AsyncWrite + Unpin),
+        codec: &C,
+        new_session: impl FnOnce() -> S,
+    ) -> Result<SessionStart, error::HandshakeError<H>>
+    where
+        H: IsHeader + Debug,
+        C: Codec,
+    {
+        let resume = match read_frame::<H, _>(stream, codec).await? {
+            HandshakeFrame::Hello { resume } => resume,
+            HandshakeFrame::Welcome { .. } => return Err(error::HandshakeError::Unexpected),
+        };
+        let start = match resume {
+            Some(token) if self.sessions.contains_key(&token) => SessionStart {
+                token,
+                resumed: true,
+            },
+            _ => {
+                let token = ResumeToken::generate();
+                self.sessions.insert(token, new_session());
+                SessionStart {
+                    token,
+                    resumed: false,
+                }
+            }
+        };
+        let welcome = HandshakeFrame::Welcome {
+            token: start.token,
+            resumed: start.resumed,
+        };
+        write_frame::<H, _>(stream, &welcome, codec).await?;
+        Ok(start)
+    }
+}
+
+impl<S> Default for SessionRegistry<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Does the client side of the handshake on `stream`, presenting `resume` if it is set
+///
+/// # Errors
+/// if sending or receiving the handshake failed, or the server did not send a valid [`HandshakeFrame::Welcome`]
+pub async fn client_handshake<H, C>(
+    stream: &mut (impl AsyncRead + AsyncWrite + Unpin),
+    codec: &C,
+    resume: Option<ResumeToken>,
+) -> Result<SessionStart, error::HandshakeError<H>>
+where
+    H: IsHeader + Debug,
+    C: Codec,
+{
+    write_frame::<H, _>(stream, &HandshakeFrame::Hello { resume }, codec).await?;
+    match read_frame::<H, _>(stream, codec).await? {
+        HandshakeFrame::Welcome { token, resumed } => Ok(SessionStart { token, resumed }),
+        HandshakeFrame::Hello { .. } => Err(error::HandshakeError::Unexpected),
+    }
+}
+
+async fn write_frame<H, C>(
+    stream: &mut (impl AsyncWrite + Unpin),
+    frame: &HandshakeFrame,
+    codec: &C,
+) -> Result<(), error::HandshakeError<H>>
+where
+    H: IsHeader + Debug,
+    C: Codec,
+{
+    let bytes = crate::msg::frame::<_, H>(frame, codec)?;
+    stream.write_all(&bytes).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+async fn read_frame<H, C>(
+    stream: &mut (impl AsyncRead + Unpin),
+    codec: &C,
+) -> Result<HandshakeFrame, error::HandshakeError<H>>
+where
+    H: IsHeader + Debug,
+    C: Codec,
+{
+    let mut header_bytes = vec![0u8; H::header_size()];
+    stream.read_exact(&mut header_bytes).await?;
+    let header = H::from_bytes(header_bytes.into()).map_err(error::HandshakeError::Header)?;
+    let size = header.size();
+    if size > MAX_HANDSHAKE_SIZE {
+        return Err(error::HandshakeError::TooLarge { size });
+    }
+    // checked above, so this always fits
+    let mut body = vec![0u8; size as usize];
+    stream.read_exact(&mut body).await?;
+    header
+        .validate_body(&body)
+        .map_err(error::HandshakeError::Header)?;
+    Ok(codec.deserialize(&body)?)
+}
@@ -0,0 +1,44 @@
+use std::ops::{Deref, DerefMut};
+
+/// A [`Reader`] or [`Writer`] that has given up the ability to be joined back into a `TcpStream`.
+///
+/// produced by [`Reader::detach`] and [`Writer::detach`]. this is for when the halves are moved
+/// to different tasks for good, and makes that ownership explicit in the type:
+/// everything on the inner half can still be used through `Deref` and `DerefMut`,
+/// but the half itself can not be moved back out, so it can not be passed to [`join_stream`].
+/// dropping it closes that direction of the connection, as usual
+///
+/// this states intent, it does not lock the socket away: since `DerefMut` gives full access to the half,
+/// its socket can still be swapped out with `replace_socket` (see [`Reader::replace_socket`]
+/// and [`Writer::replace_socket`]). don't do that, the point of detaching is that the socket stays with the half
+///
+/// [`Reader`]: super::Reader
+/// [`Writer`]: super::Writer
+/// [`Reader::detach`]: super::Reader::detach
+/// [`Writer::detach`]: super::Writer::detach
+/// [`join_stream`]: super::join_stream
+/// [`Reader::replace_socket`]: super::Reader::replace_socket
+/// [`Writer::replace_socket`]: super::Writer::replace_socket
+#[derive(Debug)]
+pub struct Detached<T> {
+    inner: T,
+}
+
+impl<T> Detached<T> {
+    pub(crate) fn new(inner: T) -> Self {
+        Self { inner }
+    }
+}
+
+impl<T> Deref for Detached<T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for Detached<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
@@ -3,7 +3,7 @@ use std::fmt::Debug;
 
 use serde::{Serialize, de::DeserializeOwned};
 
-use super::read::Reader;
+use super::read::{ReadStatus, Reader};
 use super::write::Writer;
 
 
@@ -18,6 +18,11 @@ pub mod error{
         ReadUpdate(crate::socket::read::error::UpdateError<H>),
         #[error("Failed to write data to socket!\n{0}")]
         Write(crate::socket::write::error::WriteError),
+        #[error("The connection was closed by the peer")]
+        Closed,
+        /// heartbeats are enabled, and nothing was received from the peer for longer than the timeout
+        #[error("Nothing was received from the peer for {idle:?}, the connection is dead")]
+        Dead { idle: std::time::Duration },
     }
 
     #[derive(Debug, thiserror::Error)]
@@ -26,26 +31,95 @@ pub mod error{
         Update(#[from] UpdateError<H>),
         #[error("Failed to read from socket while waiting for a message!\n{0}")]
         Read(#[from] std::io::Error),
+        #[error("The connection was closed by the peer while waiting for a message")]
+        Closed,
     }
+
+    /// heartbeats were enabled, but the header can not tell them apart from messages, see [`IsHeader::heartbeat`]
+    ///
+    /// [`IsHeader::heartbeat`]: crate::header::IsHeader::heartbeat
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+    #[error("Can not enable heartbeats, the header has no way to mark them apart from messages")]
+    pub struct HeartbeatUnsupported;
 }
 
 pub mod res {
     #[derive(Debug, Clone)]
     pub struct UpdateStatus {
         new_message: bool,
+        lifetime_exceeded: bool,
     }
 
     impl UpdateStatus {
-        pub fn new(new_message: bool) -> Self {
-            Self { new_message }
+        pub fn new(new_message: bool, lifetime_exceeded: bool) -> Self {
+            Self {
+                new_message,
+                lifetime_exceeded,
+            }
         }
 
         pub fn new_msg(&self) -> bool {
             self.new_message
         }
+
+        /// If the connection is older than its max lifetime, and should be closed
+        pub fn lifetime_exceeded(&self) -> bool {
+            self.lifetime_exceeded
+        }
+    }
+
+    /// What happened first when waiting with [`next_or`]
+    ///
+    /// [`next_or`]: super::_SocketUtils::next_or
+    pub enum NextOutcome<M, H>
+    where
+        M: serde::Serialize,
+    {
+        /// A message arrived
+        Message(crate::msg::MessageWrapper<M, H>),
+        /// The timeout elapsed before a message arrived
+        Timeout,
+        /// The shutdown token was cancelled
+        Shutdown,
+        /// The connection was closed
+        Disconnected,
+    }
+
+    impl<M, H> std::fmt::Debug for NextOutcome<M, H>
+    where
+        M: serde::Serialize + std::fmt::Debug,
+        H: crate::header::IsHeader + std::fmt::Debug,
+    {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Self::Message(m) => f.debug_tuple("Message").field(m).finish(),
+                Self::Timeout => write!(f, "Timeout"),
+                Self::Shutdown => write!(f, "Shutdown"),
+                Self::Disconnected => write!(f, "Disconnected"),
+            }
+        }
     }
 }
 
+/// Returns if a write error means the connection was closed
+pub(crate) fn is_write_disconnect(err: &crate::socket::write::error::WriteError) -> bool {
+    match err {
+        crate::socket::write::error::WriteError::Disconnected => true,
+        crate::socket::write::error::WriteError::IOError(e) => is_disconnect(e),
+    }
+}
+
+/// Returns if an io error means the connection was closed
+pub(crate) fn is_disconnect(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        std::io::ErrorKind::UnexpectedEof
+            | std::io::ErrorKind::ConnectionReset
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::BrokenPipe
+    )
+}
+
 /// Utilities for reading and writing from a socket.
 /// 
 /// This is a solution to the problem of how do you have a common pre-defined api
@@ -56,34 +130,76 @@ pub mod res {
 /// and then implement Deref and DerefMut for that type, with Target = SocketUtils,
 /// and rust will autoderef to acess methods on this struct for that
 /// 
-pub struct _SocketUtils<H, M, O>
+pub struct _SocketUtils<H, M, C>
 where
     H: crate::header::IsHeader,
     M: Serialize + DeserializeOwned,
-    O: bincode::Options + Clone,
+    C: crate::codec::Codec + Clone,
 {
-    reader: Reader<H, M, O>,
-    writer: Writer<H, M, O>,
+    reader: Reader<H, M, C>,
+    writer: Writer<H, M, C>,
     addr: SocketAddr,
+    created: tokio::time::Instant,
+    max_lifetime: Option<std::time::Duration>,
+    heartbeat: Option<super::HeartbeatConfig>,
+    /// when a heartbeat was last queued (or heartbeats were enabled)
+    last_heartbeat: tokio::time::Instant,
+    /// where the age and heartbeat timing get the time from, shared with the reader
+    clock: crate::clock::SharedClock,
 }
 
 // so only in the crate can it be used as a nice name
 pub(crate) use _SocketUtils as SocketUtils;
 
-impl<H, M, O> _SocketUtils<H, M, O>
+impl<H, M, C> _SocketUtils<H, M, C>
 where
     H: crate::header::IsHeader + Debug + Clone,
     M: Serialize + DeserializeOwned,
-    O: bincode::Options + Clone,
+    C: crate::codec::Codec + Clone,
 {
-    pub(crate) fn new(reader: Reader<H, M, O>, writer: Writer<H, M, O>, addr: SocketAddr) -> Self {
+    pub(crate) fn new(mut reader: Reader<H, M, C>, writer: Writer<H, M, C>, addr: SocketAddr) -> Self {
+        let clock = crate::clock::default_clock();
+        reader.set_clock(clock.clone());
         Self {
             reader,
             writer,
             addr,
+            created: clock.now(),
+            max_lifetime: None,
+            heartbeat: None,
+            last_heartbeat: clock.now(),
+            clock,
         }
     }
 
+    /// Sets where the connection gets the current time from, for its age, heartbeats
+    /// and the readers idle time and rate limit. see the [`clock`] module.
+    ///
+    /// this should be set right after the connection is made,
+    /// the age and heartbeat timing restart from the current time of `clock`
+    ///
+    /// [`clock`]: crate::clock
+    pub fn set_clock(&mut self, clock: crate::clock::SharedClock) {
+        self.created = clock.now();
+        self.last_heartbeat = clock.now();
+        self.reader.set_clock(clock.clone());
+        self.clock = clock;
+    }
+
+    /// Moves the settings of `old` over to this connection, for when it replaces a lost one.
+    /// see [`Reader::take_settings`] and [`Writer::take_settings`] for what is carried over
+    ///
+    /// # Errors
+    /// if the writers settings could not be applied
+    pub(crate) fn take_settings(&mut self, old: &mut Self) -> std::io::Result<()> {
+        self.set_clock(old.clock.clone());
+        // same header as `old`, so this can not be unsupported
+        let _ = self.set_heartbeat(old.heartbeat);
+        self.max_lifetime = old.max_lifetime;
+        self.reader.take_settings(&mut old.reader);
+        self.writer.take_settings(&old.writer)
+    }
+
     /// Attempt to read some data from the socket,
     /// blocking untill at least a little bit of data has been read
     ///
@@ -92,7 +208,7 @@ where
     /// for more info see [`Reader.read()`]
     ///
     /// [`Reader.read()`]: crate::socket::read::Reader
-    pub async fn update_read(&mut self) -> std::io::Result<()> {
+    pub async fn update_read(&mut self) -> std::io::Result<crate::socket::read::ReadStatus> {
         self.reader.read().await
     }
 
@@ -100,31 +216,48 @@ where
     /// deserializing incoming messages if there are any
     /// and writing data to the socket.
     ///
-    /// this should *not* take long to finish, as it does not wait for anything
+    /// this should *not* take long to finish, as it does not wait for anything.
+    /// if no messages are queued the writer is not touched at all
     ///
     /// for more info see [`Reader::update`] and [`Writer::write`]
     ///
     /// # Returns
-    /// if sucsesfull, weather or not a new message is ready to be read.
+    /// if sucsesfull, weather or not a new message is ready to be read,
+    /// and if the connection has exceeded its max lifetime.
+    ///
+    /// if heartbeats are enabled (see [`set_heartbeat`]), this is also where they are sent
     ///
     /// # Errors
-    /// if deserializing a incoming message or writing to the inner [`Writer`] fails
+    /// if deserializing a incoming message or writing to the inner [`Writer`] fails,
+    /// or [`Dead`] if heartbeats are enabled and nothing has been received for too long
     ///
     /// [`Reader::update`]: crate::socket::read::Reader
     /// [`Writer::write`]: crate::socket::write::Writer
+    /// [`set_heartbeat`]: _SocketUtils::set_heartbeat
+    /// [`Dead`]: error::UpdateError::Dead
     pub async fn update(&mut self) -> Result<res::UpdateStatus, error::UpdateError<H>> {
-        let new_message;
-        match self.reader.update().await {
-            Ok(nm) => {
-                new_message = nm;
-            }
+        let new_message = match self.reader.update().await {
+            Ok(nm) => nm,
             Err(e) => return Err(error::UpdateError::ReadUpdate(e)),
+        };
+        if let Some(heartbeat) = self.heartbeat {
+            let idle = self.reader.idle_time();
+            if idle >= heartbeat.timeout {
+                return Err(error::UpdateError::Dead { idle });
+            }
+            if self.since_heartbeat() >= heartbeat.interval {
+                self.writer.queue_heartbeat();
+                self.last_heartbeat = self.clock.now();
+            }
         }
-        match self.writer.write().await {
-            Ok(_) => {}
-            Err(e) => return Err(error::UpdateError::Write(e)),
+        // skip writing entirely if there is nothing to write
+        if self.writer.queued_messages() > 0 {
+            match self.writer.write().await {
+                Ok(_) => {}
+                Err(e) => return Err(error::UpdateError::Write(e)),
+            }
         }
-        Ok(res::UpdateStatus::new(new_message))
+        Ok(res::UpdateStatus::new(new_message, self.lifetime_exceeded()))
     }
 
     /// Repeatedly reads from the socket and updates the client,
@@ -132,28 +265,262 @@ where
     ///
     /// This is mostly a convenice function, but it should be fine to use in real code
     ///
-    /// # Panics
-    /// it shouldent, so please do tell if it does
+    /// # Errors
+    /// if reading or updating the client fails. if the peer closes the connection
+    /// this returns [`Closed`] (or a io error of kind `UnexpectedEof`, if it was part way through a message)
+    /// instead of waiting forever
+    ///
+    /// [`Closed`]: error::WaitMessageError::Closed
     pub async fn wait_for_message(
         &mut self,
     ) -> Result<crate::msg::MessageWrapper<M, H>, error::WaitMessageError<H>> {
         loop {
-            self.update_read().await?;
-            if self.update().await?.new_msg() {
+            // update before reading, in case data for a message is already buffered
+            self.update().await?;
+            if let Some(m) = self.reader.latest_message() {
+                return Ok(m);
+            }
+            if self.read_or_heartbeat().await? == ReadStatus::Closed {
+                return Err(error::WaitMessageError::Closed);
+            }
+        }
+    }
+
+    /// Reads like [`update_read`], but if heartbeats are enabled gives up (returning [`ReadStatus::Open`])
+    /// once it is time to send one or the connection times out, so the next [`update`] can handle it
+    ///
+    /// [`update_read`]: _SocketUtils::update_read
+    /// [`update`]: _SocketUtils::update
+    async fn read_or_heartbeat(&mut self) -> std::io::Result<ReadStatus> {
+        let Some(heartbeat) = self.heartbeat else {
+            return self.update_read().await;
+        };
+        // measured with the clock, but waited for with tokio's timers
+        let wait = heartbeat
+            .timeout
+            .saturating_sub(self.reader.idle_time())
+            .min(heartbeat.interval.saturating_sub(self.since_heartbeat()));
+        let deadline = tokio::time::Instant::now() + wait;
+        // reading is cancelation safe, so nothing is lost when the deadline is hit
+        match tokio::time::timeout_at(deadline, self.update_read()).await {
+            Ok(res) => res,
+            Err(_elapsed) => Ok(ReadStatus::Open),
+        }
+    }
+
+    /// Same as [`wait_for_message`], but gives up once `timeout` has elapsed.
+    ///
+    /// this is for request/response flows, where a peer that stops responding (without closing the connection)
+    /// would otherwise block forever. reading and writing are both cancelation safe,
+    /// so a message that was part way through being received is kept and finished by the next call.
+    /// this is [`recv_timeout`], but with the same errors as [`wait_for_message`]
+    ///
+    /// # Returns
+    /// `Ok(None)` if no complete message arrived before the timeout
+    ///
+    /// # Errors
+    /// the same as [`wait_for_message`]
+    ///
+    /// [`wait_for_message`]: _SocketUtils::wait_for_message
+    /// [`recv_timeout`]: _SocketUtils::recv_timeout
+    pub async fn wait_for_message_timeout(
+        &mut self,
+        timeout: std::time::Duration,
+    ) -> Result<Option<crate::msg::MessageWrapper<M, H>>, error::WaitMessageError<H>> {
+        self.recv_timeout(timeout).await.map_err(|e| match e {
+            error::UpdateError::Closed => error::WaitMessageError::Closed,
+            error::UpdateError::Read(e) => error::WaitMessageError::Read(e),
+            e => error::WaitMessageError::Update(e),
+        })
+    }
+
+    /// Reads from the socket and updates the client untill a new message is available,
+    /// or `timeout` has elapsed.
+    ///
+    /// unlike [`wait_for_message`] this does not block forever, and any partialy received data
+    /// is kept when the timeout is hit, so the next call can finish reading it.
+    /// the timeout also interrupts writing queued messages (whatever was not written stays queued),
+    /// so a peer that stops reading can not hold this up
+    ///
+    /// # Returns
+    /// `Ok(None)` if no message arrived before the timeout
+    ///
+    /// # Errors
+    /// if reading from the socket or updating the client fails,
+    /// or [`Closed`] if the peer closed the connection
+    ///
+    /// [`Closed`]: error::UpdateError::Closed
+    ///
+    /// [`wait_for_message`]: _SocketUtils::wait_for_message
+    pub async fn recv_timeout(
+        &mut self,
+        timeout: std::time::Duration,
+    ) -> Result<Option<crate::msg::MessageWrapper<M, H>>, error::UpdateError<H>> {
+        // writing can block for as long as the peer is not reading, so it is bounded by the timeout too.
+        // both reading and writing are cancelation safe, so nothing is lost if the timeout is hit
+        let wait = async {
+            loop {
+                self.update().await?;
                 if let Some(m) = self.reader.latest_message() {
                     return Ok(m);
-                } else {
-                    panic!("This should not happen, and if it does please submit a bug report\nSaying that SocketUtils::update() incorrectly returned that there was a message when there was not");
                 }
+                let status = self.read_or_heartbeat().await.map_err(error::UpdateError::Read)?;
+                if status == ReadStatus::Closed {
+                    return Err(error::UpdateError::Closed);
+                }
+            }
+        };
+        match tokio::time::timeout(timeout, wait).await {
+            Ok(res) => res.map(Some),
+            Err(_elapsed) => Ok(None),
+        }
+    }
+
+    /// Writes everything that is queued
+    ///
+    /// for more info see [`Writer::flush_all`]
+    ///
+    /// [`Writer::flush_all`]: crate::socket::write::Writer::flush_all
+    pub async fn flush_all(&mut self) -> Result<usize, crate::socket::write::error::WriteError> {
+        self.writer.flush_all().await
+    }
+
+    /// Writes everything that is queued, then shuts down the sending side of the connection
+    ///
+    /// for more info see [`Writer::close_after_flush`]
+    ///
+    /// [`Writer::close_after_flush`]: crate::socket::write::Writer::close_after_flush
+    pub async fn close_after_flush(&mut self) -> Result<(), crate::socket::write::error::WriteError> {
+        self.writer.close_after_flush().await
+    }
+
+    /// Reads exactly `n` messages, and returns them.
+    ///
+    /// no data after the last of them is processed, so this can be followed by [`into_stream`]
+    /// for protocols that start with some framed messages (a handshake, authentication, etc.)
+    /// and then switch to streaming raw bytes
+    ///
+    /// [`into_stream`]: _SocketUtils::into_stream
+    pub async fn take_first(
+        &mut self,
+        n: usize,
+    ) -> Result<Vec<crate::msg::MessageWrapper<M, H>>, error::WaitMessageError<H>> {
+        let mut messages = Vec::with_capacity(n);
+        while messages.len() < n {
+            if let Some(m) = self.reader.latest_message() {
+                messages.push(m);
+                continue;
+            }
+            // only decode one message at a time, so nothing after the last one is processed
+            let new_message = self
+                .reader
+                .update_one()
+                .await
+                .map_err(error::UpdateError::ReadUpdate)?;
+            if self.writer.queued_messages() > 0 {
+                self.writer.write().await.map_err(error::UpdateError::Write)?;
+            }
+            if !new_message && self.read_or_heartbeat().await? == ReadStatus::Closed {
+                return Err(error::WaitMessageError::Closed);
+            }
+        }
+        Ok(messages)
+    }
+
+    /// Consumes self, recovering the raw `TcpStream`.
+    ///
+    /// data that was read from the socket but not processed is returned with it,
+    /// and should be treated as coming before anything else read from the stream.
+    /// this should be done at a frame boundary (for example after [`take_first`]).
+    /// anything still queued to be written is dropped, so flush it first.
+    ///
+    /// # Errors
+    /// if the halfs of the socket could not be joined
+    ///
+    /// [`take_first`]: _SocketUtils::take_first
+    pub fn into_stream(
+        self,
+    ) -> Result<(tokio::net::TcpStream, bytes::BytesMut), tokio::net::tcp::ReuniteError> {
+        let (read_half, leftover) = self.reader.into_socket_with_buffer();
+        let stream = read_half.reunite(self.writer.into_socket())?;
+        Ok((stream, leftover))
+    }
+
+    /// Waits for a message, a timeout, or a shutdown signal, whichever happens first.
+    ///
+    /// this packages the common event loop pattern of selecting over these in one place.
+    /// if the connection is closed while waiting (noticed by either reading or writing),
+    /// [`NextOutcome::Disconnected`] is returned
+    ///
+    /// the timeout and shutdown signal also interrupt writing queued messages,
+    /// so a peer that stops reading can not hold this up. whatever was not written stays queued
+    ///
+    /// ## Cancelation Saftey
+    /// this method IS cancelation safe, partially received messages are kept
+    /// when it returns without a message, or is canceled
+    /// (unless a sink is set on the reader, see [`Reader::update`])
+    ///
+    /// # Errors
+    /// if reading from the socket (other than the connection being closed) or updating the client fails
+    ///
+    /// [`NextOutcome::Disconnected`]: res::NextOutcome::Disconnected
+    /// [`Reader::update`]: crate::socket::read::Reader::update
+    pub async fn next_or(
+        &mut self,
+        timeout: std::time::Duration,
+        shutdown: &tokio_util::sync::CancellationToken,
+    ) -> Result<res::NextOutcome<M, H>, error::UpdateError<H>> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            // writing can block for as long as the peer is not reading, so it has to be raced too.
+            // both reading and writing are cancelation safe, so nothing is lost when the step is dropped
+            tokio::select! {
+                biased;
+                res = self.next_step() => if let Some(outcome) = res? {
+                    return Ok(outcome);
+                },
+                () = shutdown.cancelled() => return Ok(res::NextOutcome::Shutdown),
+                () = tokio::time::sleep_until(deadline) => return Ok(res::NextOutcome::Timeout),
             }
         }
     }
 
+    /// One round of [`next_or`]: updates the client, and reads once if that did not produce a message
+    ///
+    /// [`next_or`]: _SocketUtils::next_or
+    async fn next_step(&mut self) -> Result<Option<res::NextOutcome<M, H>>, error::UpdateError<H>> {
+        match self.update().await {
+            Ok(_) => {}
+            Err(error::UpdateError::Write(e)) if is_write_disconnect(&e) => {
+                return Ok(Some(res::NextOutcome::Disconnected))
+            }
+            Err(e) => return Err(e),
+        }
+        if let Some(m) = self.reader.latest_message() {
+            return Ok(Some(res::NextOutcome::Message(m)));
+        }
+        match self.read_or_heartbeat().await {
+            Ok(ReadStatus::Open) => Ok(None),
+            Ok(ReadStatus::Closed) => Ok(Some(res::NextOutcome::Disconnected)),
+            Err(e) if is_disconnect(&e) => Ok(Some(res::NextOutcome::Disconnected)),
+            Err(e) => Err(error::UpdateError::Read(e)),
+        }
+    }
+
     /// Gets all incoming messages that have been received
-    pub fn get_messages(&mut self) -> std::vec::Drain<crate::msg::MessageWrapper<M, H>> {
+    pub fn get_messages(&mut self) -> std::vec::Drain<'_, crate::msg::MessageWrapper<M, H>> {
         self.reader.ready_messages()
     }
 
+    /// Gets the incoming messages of one kind that have been received, leaving messages of other kinds.
+    ///
+    /// for more info see [`Reader::take_messages_of_kind`]
+    ///
+    /// [`Reader::take_messages_of_kind`]: crate::socket::read::Reader::take_messages_of_kind
+    pub fn get_messages_of_kind(&mut self, kind: u16) -> Vec<crate::msg::MessageWrapper<M, H>> {
+        self.reader.take_messages_of_kind(kind)
+    }
+
     /// Gets the latest incoming message received
     pub fn get_latest_message(&mut self) -> Option<crate::msg::MessageWrapper<M, H>> {
         self.reader.latest_message()
@@ -161,36 +528,262 @@ where
 
     /// Queues a [`Message`] to be sent
     ///
+    /// # Errors
+    /// if the message could not be serialized, or the send queue is full (see [`Writer::with_capacity`])
+    ///
     /// [`Message`]: crate::msg::MessageWrapper
+    /// [`Writer::with_capacity`]: crate::socket::write::Writer::with_capacity
     pub fn queue_message(
         &mut self,
         message: &crate::msg::MessageWrapper<M, H>,
-    ) -> Result<(), crate::socket::write::error::SeriError> {
+    ) -> Result<(), crate::socket::write::error::QueueError> {
         self.writer.queue(message)
     }
 
+    /// Queues already framed bytes to be sent
+    ///
+    /// for more info see [`Writer::queue_raw`]
+    ///
+    /// [`Writer::queue_raw`]: crate::socket::write::Writer::queue_raw
+    pub fn queue_raw(&mut self, bytes: bytes::Bytes) -> Result<(), crate::socket::write::error::QueueError> {
+        self.writer.queue_raw(bytes)
+    }
+
+    /// Queues a group of [`Message`]s to be sent as a single unit
+    ///
+    /// for more info see [`Writer::queue_group`]
+    ///
+    /// [`Message`]: crate::msg::MessageWrapper
+    /// [`Writer::queue_group`]: crate::socket::write::Writer::queue_group
+    pub fn queue_group(
+        &mut self,
+        messages: &[crate::msg::MessageWrapper<M, H>],
+    ) -> Result<(), crate::socket::write::error::QueueError> {
+        self.writer.queue_group(messages)
+    }
+
+    /// Gets a snapshot of how much has been sent and received on this connection.
+    ///
+    /// the counters are only ever added to, so throughput can be found by polling this
+    /// and comparing it to the previous snapshot
+    pub fn stats(&self) -> crate::stats::ConnectionStats {
+        crate::stats::ConnectionStats {
+            bytes_read: self.reader.bytes_read(),
+            messages_received: self.reader.messages_received(),
+            bytes_written: self.writer.bytes_written(),
+            messages_sent: self.writer.messages_sent(),
+        }
+    }
+
+    /// Stops accepting new messages, for draining the connection.
+    ///
+    /// after this [`queue_message`] fails with [`QueueError::Quiescing`], but [`update`] and [`flush_all`]
+    /// keep sending what was already queued, and receiving messages like normal.
+    /// for more info see [`Writer::quiesce`]
+    ///
+    /// [`queue_message`]: _SocketUtils::queue_message
+    /// [`update`]: _SocketUtils::update
+    /// [`flush_all`]: _SocketUtils::flush_all
+    /// [`QueueError::Quiescing`]: crate::socket::write::error::QueueError::Quiescing
+    /// [`Writer::quiesce`]: crate::socket::write::Writer::quiesce
+    pub fn quiesce(&mut self) {
+        self.writer.quiesce();
+    }
+
+    /// Returns if [`quiesce`] has been called
+    ///
+    /// [`quiesce`]: _SocketUtils::quiesce
+    pub fn is_quiescing(&self) -> bool {
+        self.writer.is_quiescing()
+    }
+
+    /// Sets the interval queued messages are automatically flushed on, or `None` to disable it.
+    ///
+    /// this only has an effect when [`flush_tick`] is being awaited, for example in a `select!` in
+    /// a event loop. for more info see [`Writer::set_flush_interval`]
+    ///
+    /// [`flush_tick`]: _SocketUtils::flush_tick
+    /// [`Writer::set_flush_interval`]: crate::socket::write::Writer::set_flush_interval
+    pub fn set_flush_interval(&mut self, interval: Option<std::time::Duration>) {
+        self.writer.set_flush_interval(interval);
+    }
+
+    /// Waits for the next tick of the flush interval, then writes everything that is queued.
+    ///
+    /// for more info see [`Writer::flush_tick`]
+    ///
+    /// [`Writer::flush_tick`]: crate::socket::write::Writer::flush_tick
+    pub async fn flush_tick(&mut self) -> Result<(), crate::socket::write::error::WriteError> {
+        self.writer.flush_tick().await
+    }
+
     /// Gets the address the client is connected to
     pub fn addr(&self) -> SocketAddr {
         self.addr
     }
 
-    pub fn as_reader(&self) -> &Reader<H, M, O> {
+    /// Gets how long ago the connection was created
+    ///
+    /// this uses the connections clock (see [`set_clock`]), which by default is tokio's,
+    /// so it follows `tokio::time::pause` and `advance` in tests
+    ///
+    /// [`set_clock`]: _SocketUtils::set_clock
+    pub fn age(&self) -> std::time::Duration {
+        self.clock.now().saturating_duration_since(self.created)
+    }
+
+    /// Gets how long ago a heartbeat was last queued (or heartbeats were enabled)
+    fn since_heartbeat(&self) -> std::time::Duration {
+        self.clock.now().saturating_duration_since(self.last_heartbeat)
+    }
+
+    /// Sets the max lifetime of the connection, or `None` for no limit.
+    ///
+    /// once the connection is older than this, [`update`] reports it in its status
+    /// so that the connection can be closed gracefully (flushing what is queued, then closing it).
+    /// nothing is closed automatically.
+    ///
+    /// [`update`]: _SocketUtils::update
+    pub fn set_max_lifetime(&mut self, max_lifetime: Option<std::time::Duration>) {
+        self.max_lifetime = max_lifetime;
+    }
+
+    /// Enables sending and checking heartbeats, or disables them with `None`.
+    ///
+    /// TCP keepalive only notices a dead connection after a long time (and only if the OS is set up for it),
+    /// heartbeats do it at the application level: every `interval` a heartbeat is queued (if nothing else is),
+    /// and if nothing at all is read from the peer for `timeout`, [`update`] fails with [`Dead`].
+    /// only data that has actually been read counts, so the connection has to be read from regularly.
+    /// [`wait_for_message`] and the other methods that wait for messages do this, and keep sending heartbeats while they wait.
+    ///
+    /// received heartbeats are dropped instead of being returned by [`get_messages`], see [`IsHeader::is_heartbeat`]
+    /// for how they are told apart from messages. both sides should enable heartbeats
+    ///
+    /// the timeout counts from when this is called, not from when data was last read,
+    /// so enabling heartbeats on a connection that has been quiet for a while does not make it [`Dead`] right away
+    ///
+    /// # Errors
+    /// if `heartbeat` is `Some`, and `H` can not mark heartbeats apart from messages (see [`IsHeader::heartbeat`]).
+    /// nothing is changed in that case
+    ///
+    /// [`update`]: _SocketUtils::update
+    /// [`Dead`]: error::UpdateError::Dead
+    /// [`wait_for_message`]: _SocketUtils::wait_for_message
+    /// [`get_messages`]: _SocketUtils::get_messages
+    /// [`IsHeader::is_heartbeat`]: crate::header::IsHeader::is_heartbeat
+    /// [`IsHeader::heartbeat`]: crate::header::IsHeader::heartbeat
+    pub fn set_heartbeat(
+        &mut self,
+        heartbeat: Option<super::HeartbeatConfig>,
+    ) -> Result<(), error::HeartbeatUnsupported> {
+        if heartbeat.is_some() && H::heartbeat().is_none() {
+            return Err(error::HeartbeatUnsupported);
+        }
+        self.heartbeat = heartbeat;
+        self.reader.set_skip_heartbeats(heartbeat.is_some());
+        self.reader.reset_idle();
+        self.last_heartbeat = self.clock.now();
+        Ok(())
+    }
+
+    pub fn heartbeat(&self) -> Option<super::HeartbeatConfig> {
+        self.heartbeat
+    }
+
+    /// Gets the max lifetime of the connection
+    pub fn max_lifetime(&self) -> Option<std::time::Duration> {
+        self.max_lifetime
+    }
+
+    /// Returns if the connection is older than its max lifetime
+    pub fn lifetime_exceeded(&self) -> bool {
+        self.max_lifetime
+            .is_some_and(|max_lifetime| self.age() >= max_lifetime)
+    }
+
+    /// Gets the effective size of the sockets receive buffer (`SO_RCVBUF`)
+    ///
+    /// # Errors
+    /// if the option could not be read from the socket
+    pub fn recv_buffer_size(&self) -> std::io::Result<usize> {
+        socket2::SockRef::from(self.reader.as_socket().as_ref()).recv_buffer_size()
+    }
+
+    /// Sets the size of the sockets receive buffer (`SO_RCVBUF`).
+    ///
+    /// The OS may round or otherwise adjust the size, use [`recv_buffer_size`] to get the effective value
+    ///
+    /// # Errors
+    /// if the option could not be set on the socket
+    ///
+    /// [`recv_buffer_size`]: _SocketUtils::recv_buffer_size
+    pub fn set_recv_buffer_size(&self, size: usize) -> std::io::Result<()> {
+        socket2::SockRef::from(self.reader.as_socket().as_ref()).set_recv_buffer_size(size)
+    }
+
+    /// Gets the effective size of the sockets send buffer (`SO_SNDBUF`)
+    ///
+    /// # Errors
+    /// if the option could not be read from the socket
+    pub fn send_buffer_size(&self) -> std::io::Result<usize> {
+        socket2::SockRef::from(self.writer.as_socket().as_ref()).send_buffer_size()
+    }
+
+    /// Sets the size of the sockets send buffer (`SO_SNDBUF`).
+    ///
+    /// The OS may round or otherwise adjust the size, use [`send_buffer_size`] to get the effective value
+    ///
+    /// # Errors
+    /// if the option could not be set on the socket
+    ///
+    /// [`send_buffer_size`]: _SocketUtils::send_buffer_size
+    pub fn set_send_buffer_size(&self, size: usize) -> std::io::Result<()> {
+        socket2::SockRef::from(self.writer.as_socket().as_ref()).set_send_buffer_size(size)
+    }
+
+    /// Gets a summary of the options in effect on this connection, for debugging misconfigurations
+    ///
+    /// the socket options are read back from the OS, so they show what was actually applied
+    ///
+    /// # Errors
+    /// if a option could not be read from the socket
+    pub fn config(&self) -> std::io::Result<super::EffectiveConfig> {
+        let stream = self.reader.as_socket().as_ref();
+        let sock = socket2::SockRef::from(stream);
+        Ok(super::EffectiveConfig {
+            nodelay: stream.nodelay()?,
+            keepalive: sock.keepalive()?,
+            recv_buffer_size: sock.recv_buffer_size()?,
+            send_buffer_size: sock.send_buffer_size()?,
+            max_message_size: self.reader.max_message_size(),
+            max_total_buffered: self.reader.max_total_buffered(),
+            max_frames_per_sec: self.reader.max_frames_per_sec(),
+            retain_raw_body: self.reader.retain_raw_body(),
+            skip_heartbeats: self.reader.skip_heartbeats(),
+            max_queued_bytes: self.writer.max_queued_bytes(),
+            flush_interval: self.writer.flush_interval(),
+            heartbeat: self.heartbeat,
+            max_lifetime: self.max_lifetime,
+        })
+    }
+
+    pub fn as_reader(&self) -> &Reader<H, M, C> {
         &self.reader
     }
 
-    pub fn as_writer(&self) -> &Writer<H, M, O> {
+    pub fn as_writer(&self) -> &Writer<H, M, C> {
         &self.writer
     }
 
-    pub fn as_reader_mut(&mut self) -> &mut Reader<H, M, O> {
+    pub fn as_reader_mut(&mut self) -> &mut Reader<H, M, C> {
         &mut self.reader
     }
 
-    pub fn as_writer_mut(&mut self) -> &mut Writer<H, M, O> {
+    pub fn as_writer_mut(&mut self) -> &mut Writer<H, M, C> {
         &mut self.writer
     }
 
-    pub fn into_rw(self) -> (Reader<H, M, O>, Writer<H, M, O>) {
+    pub fn into_rw(self) -> (Reader<H, M, C>, Writer<H, M, C>) {
         (self.reader, self.writer)
     }
 }
\ No newline at end of file
@@ -1,24 +1,143 @@
 pub mod read;
 pub mod write;
 pub mod interface;
+pub mod read_only;
+mod detached;
+mod stream;
+#[cfg(feature = "spillover")]
+mod spill;
 
 use serde::{de::DeserializeOwned, Serialize};
 use tokio::net::TcpStream;
 
 pub use read::Reader;
 pub use write::Writer;
+pub use read_only::ReadOnlyConnection;
+pub use detached::Detached;
+pub use stream::ReaderStream;
+
+/// Options set on a `TcpStream` when a connection is made, before it is split
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SocketConfig {
+    /// disables Nagle's algorithm (`TCP_NODELAY`), so small messages are sent right away
+    /// instead of being batched. this lowers latency for request/response protocols
+    pub nodelay: bool,
+    /// enables TCP keepalive (`SO_KEEPALIVE`), sending probes after the connection has been idle this long,
+    /// so dead peers are noticed even when nothing is being sent. `None` leaves the OS default
+    pub keepalive: Option<std::time::Duration>,
+}
+
+impl SocketConfig {
+    /// Applies the options to `stream`
+    ///
+    /// # Errors
+    /// if setting a option failed
+    pub fn apply(&self, stream: &TcpStream) -> std::io::Result<()> {
+        stream.set_nodelay(self.nodelay)?;
+        if let Some(time) = self.keepalive {
+            socket2::SockRef::from(stream)
+                .set_tcp_keepalive(&socket2::TcpKeepalive::new().with_time(time))?;
+        }
+        Ok(())
+    }
+}
+
+/// Settings for sending and checking application level heartbeats, see [`SocketUtils::set_heartbeat`]
+///
+/// [`SocketUtils::set_heartbeat`]: interface::_SocketUtils::set_heartbeat
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeartbeatConfig {
+    /// how often to send a heartbeat, when nothing else is queued
+    pub interval: std::time::Duration,
+    /// how long the peer can go without sending anything before the connection counts as dead.
+    /// this should be a few times the peers `interval`, so a single late heartbeat is not fatal
+    pub timeout: std::time::Duration,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            interval: std::time::Duration::from_secs(5),
+            timeout: std::time::Duration::from_secs(15),
+        }
+    }
+}
+
+/// A summary of the options in effect on a connection, see [`SocketUtils::config`]
+///
+/// this is a snapshot, later changes to the connection are not reflected in it
+///
+/// [`SocketUtils::config`]: interface::_SocketUtils::config
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EffectiveConfig {
+    /// if Nagle's algorithm is disabled (`TCP_NODELAY`)
+    pub nodelay: bool,
+    /// if TCP keepalive (`SO_KEEPALIVE`) is enabled
+    pub keepalive: bool,
+    /// the effective receive buffer size (`SO_RCVBUF`), as adjusted by the OS
+    pub recv_buffer_size: usize,
+    /// the effective send buffer size (`SO_SNDBUF`), as adjusted by the OS
+    pub send_buffer_size: usize,
+    /// see [`Reader::set_max_message_size`]
+    pub max_message_size: Option<u64>,
+    /// see [`Reader::set_max_total_buffered`]
+    pub max_total_buffered: Option<usize>,
+    /// see [`Reader::set_max_frames_per_sec`]
+    pub max_frames_per_sec: Option<u32>,
+    /// see [`Reader::set_retain_raw_body`]
+    pub retain_raw_body: bool,
+    /// see [`Reader::set_skip_heartbeats`]
+    pub skip_heartbeats: bool,
+    /// see [`Writer::set_max_queued_bytes`]
+    pub max_queued_bytes: Option<usize>,
+    /// see [`Writer::set_flush_interval`]
+    pub flush_interval: Option<std::time::Duration>,
+    /// see [`SocketUtils::set_heartbeat`]
+    ///
+    /// [`SocketUtils::set_heartbeat`]: interface::_SocketUtils::set_heartbeat
+    pub heartbeat: Option<HeartbeatConfig>,
+    /// see [`SocketUtils::set_max_lifetime`]
+    ///
+    /// [`SocketUtils::set_max_lifetime`]: interface::_SocketUtils::set_max_lifetime
+    pub max_lifetime: Option<std::time::Duration>,
+}
+
+/// The halves of a connection, as produced by [`split_stream`]
+pub type Halves<H, M, C> = (Reader<H, M, C>, Writer<H, M, C>);
+
+/// Applies `config` to a `TcpStream`, then splits it like [`split_stream`]
+///
+/// # Errors
+/// if applying the options failed
+pub fn split_stream_with_config<H, M, C>(
+    stream: TcpStream,
+    codec: C,
+    config: &SocketConfig,
+) -> std::io::Result<Halves<H, M, C>>
+where
+    H: crate::header::IsHeader + Clone,
+    M: Serialize + DeserializeOwned,
+    C: crate::codec::Codec + Clone,
+{
+    config.apply(&stream)?;
+    Ok(split_stream(stream, codec))
+}
 
 /// Splits a `TcpStream` into a `Reader` and `Writer`
-pub fn split_stream<H, M, O>(stream: TcpStream, seri_opt: O) -> (Reader<H, M, O>, Writer<H, M, O>)
+///
+/// each half owns its direction of the connection, and can be moved to its own task.
+/// they can be joined back together with [`join_stream`], or if that will never be done,
+/// made into a [`Detached`] half with `detach` so the type says so
+pub fn split_stream<H, M, C>(stream: TcpStream, codec: C) -> (Reader<H, M, C>, Writer<H, M, C>)
 where
     H: crate::header::IsHeader + Clone,
     M: Serialize + DeserializeOwned,
-    O: bincode::Options + Clone,
+    C: crate::codec::Codec + Clone,
 {
     let (read_half, write_half) = stream.into_split();
     (
-        Reader::new(read_half, seri_opt.clone()),
-        Writer::new(write_half, seri_opt),
+        Reader::new(read_half, codec.clone()),
+        Writer::new(write_half, codec),
     )
 }
 
@@ -26,14 +145,14 @@ where
 ///
 /// # Errors
 /// if the halfs did not originate from the same `TcpStream`
-pub fn join_stream<H, M, O>(
-    read_half: Reader<H, M, O>,
-    write_half: Writer<H, M, O>,
+pub fn join_stream<H, M, C>(
+    read_half: Reader<H, M, C>,
+    write_half: Writer<H, M, C>,
 ) -> Result<TcpStream, tokio::net::tcp::ReuniteError>
 where
     H: crate::header::IsHeader + Clone,
     M: Serialize + DeserializeOwned,
-    O: bincode::Options + Clone,
+    C: crate::codec::Codec + Clone,
 {
     read_half.into_socket().reunite(write_half.into_socket())
 }
@@ -1,28 +1,25 @@
-use std::fmt::Debug;
+use std::{
+    fmt::Debug,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
-use bytes::BytesMut;
+use bytes::{Buf, Bytes, BytesMut};
 use serde::{de::DeserializeOwned, Serialize};
-use tokio::{io::AsyncReadExt, net::tcp::OwnedReadHalf};
+use tokio::net::tcp::OwnedReadHalf;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 enum ReaderState<H>
 where
     H: crate::header::IsHeader,
 {
+    #[default]
     Ready,
     ReadingHeader,
     ProcessHeader,
     ReadingMessage { header: H },
     ProcessMessage { header: H },
-}
-
-impl<H> Default for ReaderState<H>
-where
-    H: crate::header::IsHeader,
-{
-    fn default() -> Self {
-        Self::Ready
-    }
+    /// the stream can not be read any further, untill the state is cleared
+    Poisoned,
 }
 
 pub mod error {
@@ -31,131 +28,946 @@ pub mod error {
     where
         H: crate::header::IsHeader,
     {
+        /// the header could not be parsed, so where the next message starts is unknown.
+        /// this poisons the reader (see [`Reader::is_poisoned`])
+        ///
+        /// [`Reader::is_poisoned`]: super::Reader::is_poisoned
         #[error("Failed to parse header {0}")]
         HeaderParser(H::Error),
-        #[error("Failed to deserialize message {0}")]
-        MessageDeseri(#[from] bincode::Error),
+        #[error("Failed to deserialize message of kind {kind} (body of {body_len} bytes, header of {header_size} bytes) {source}")]
+        MessageDeseri {
+            #[source]
+            source: crate::codec::error::CodecError,
+            /// kind of the message, see [`IsHeader::kind`]
+            ///
+            /// [`IsHeader::kind`]: crate::header::IsHeader::kind
+            kind: u16,
+            /// length of the body that failed to deserialize, as claimed in its header
+            body_len: usize,
+            /// size of the header before the body
+            header_size: usize,
+        },
+        #[error("Message claims to be {claimed} bytes, which is larger than the limit of {limit}")]
+        MessageTooLarge { claimed: u64, limit: u64 },
+        #[error("Message claims to be {size} bytes, which is too large to be stored on this platform")]
+        SizeOverflow { size: u64 },
+        #[error("Message of {size} bytes was rejected by the header hook")]
+        HeaderRejected { size: u64 },
+        #[error("Message body did not match its header {0}")]
+        BodyValidation(H::Error),
+        #[error("The channel set with set_sink was closed, the message was dropped")]
+        SinkClosed,
+        /// some messages were never received. the message after the gap is kept,
+        /// and returned by the next update
+        #[error("Message sequence skipped from {expected} to {got}, {} messages were lost", got.wrapping_sub(*expected))]
+        SequenceGap { expected: u64, got: u64 },
+        /// the message was already received (or is out of order), it was dropped
+        #[error("Message with sequence {seq} was received again (expected {expected}), it was dropped")]
+        DuplicateSequence { seq: u64, expected: u64 },
+    }
+
+    #[derive(thiserror::Error, Debug)]
+    pub enum ReadError<H>
+    where
+        H: crate::header::IsHeader,
+    {
+        #[error("Failed to read from socket {0}")]
+        Io(#[from] std::io::Error),
+        #[error("Failed to read message {0}")]
+        Update(#[from] UpdateError<H>),
+    }
+
+    #[derive(thiserror::Error, Debug)]
+    pub enum UpdateWithError<H, E>
+    where
+        H: crate::header::IsHeader,
+    {
+        #[error("Failed to read message {0}")]
+        Frame(#[from] UpdateError<H>),
+        #[error("Failed to process message {0}")]
+        Process(E),
+    }
+}
+
+/// a decoded message, and the size of its body
+type Decoded<M, H> = (crate::msg::MessageWrapper<M, H>, usize);
+
+fn poisoned_error() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        "the reader is poisoned, and can not read any more data",
+    )
+}
+
+/// If the connection is still open after [`Reader::read`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadStatus {
+    /// the connection is still open (data may or may not have been read)
+    Open,
+    /// the peer closed the connection cleanly, between messages
+    Closed,
+}
+
+/// Callback run on each message decoded by a [`Reader`], see [`Reader::set_on_message`]
+pub type MessageHook<M, H> = Box<dyn FnMut(&crate::msg::MessageWrapper<M, H>) + Send>;
+
+/// Callback run on each header decoded by a [`Reader`], see [`Reader::set_on_header`]
+pub type HeaderHook<H> = Box<dyn FnMut(&H) -> bool + Send>;
+
+/// A handle that pauses and resumes a [`Reader`] from anywhere, see [`Reader::pause_handle`]
+///
+/// clones all control the same reader
+#[derive(Debug, Clone)]
+pub struct PauseHandle {
+    paused: std::sync::Arc<tokio::sync::watch::Sender<bool>>,
+}
+
+impl PauseHandle {
+    fn new() -> Self {
+        Self {
+            paused: std::sync::Arc::new(tokio::sync::watch::channel(false).0),
+        }
+    }
+
+    /// Pauses the reader, see [`Reader::pause`]
+    pub fn pause(&self) {
+        self.paused.send_replace(true);
+    }
+
+    /// Resumes the reader, waking a [`Reader::read`] that is waiting for it
+    pub fn resume(&self) {
+        self.paused.send_replace(false);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// Waits untill the reader is not paused
+    pub(crate) async fn wait_resumed(&self) {
+        let mut paused = self.paused.subscribe();
+        // the sender is kept alive by `self`, so this never fails
+        while *paused.borrow_and_update() && paused.changed().await.is_ok() {}
     }
 }
 
-pub struct Reader<H, M, O>
+pub struct Reader<H, M, C>
 where
     H: crate::header::IsHeader,
     M: Serialize + DeserializeOwned,
-    O: bincode::Options,
+    C: crate::codec::Codec,
 {
     socket: OwnedReadHalf,
     databuffer: BytesMut,
     state: ReaderState<H>,
     ready_messages: Vec<crate::msg::MessageWrapper<M, H>>,
-    serialization_settings: O,
+    /// body sizes of the messages in `ready_messages`, used to estimate their memory usage
+    ready_sizes: std::collections::VecDeque<usize>,
+    /// total of `ready_sizes`
+    ready_bytes: usize,
+    /// highest value of `buffered_bytes()` seen
+    peak_buffered: usize,
+    max_total_buffered: Option<usize>,
+    max_message_size: Option<u64>,
+    retain_raw_body: bool,
+    /// limits how many frames are decoded per second
+    frame_limiter: Option<crate::rate::TokenBucket>,
+    codec: C,
     /// convenience for `H::header_size()`
     header_size: usize,
+    on_message: Option<MessageHook<M, H>>,
+    on_header: Option<HeaderHook<H>>,
+    /// channel decoded messages are sent to instead of `ready_messages`, if set
+    sink: Option<tokio::sync::mpsc::Sender<crate::msg::MessageWrapper<M, H>>>,
+    pause: PauseHandle,
+    decode_errors: crate::stats::DecodeErrors,
+    /// total bytes read from the socket
+    bytes_read: AtomicU64,
+    /// total complete frames received
+    messages_received: AtomicU64,
+    /// if heartbeats are dropped instead of decoded, see [`Reader::set_skip_heartbeats`]
+    skip_heartbeats: bool,
+    /// when data was last read from the socket (or when the reader was created)
+    last_read: tokio::time::Instant,
+    /// where `last_read` and the frame limiter get the time from
+    clock: crate::clock::SharedClock,
+    /// sequence number of the last message received, see [`IsHeader::sequence`]
+    ///
+    /// [`IsHeader::sequence`]: crate::header::IsHeader::sequence
+    last_seq: Option<u64>,
 }
 
-impl<H, M, O> Reader<H, M, O>
+impl<H, M, C> Reader<H, M, C>
 where
     H: crate::header::IsHeader + Clone,
     M: Serialize + DeserializeOwned,
-    O: bincode::Options + Clone,
+    C: crate::codec::Codec + Clone,
 {
-    pub fn new(socket: OwnedReadHalf, seri_settings: O) -> Self {
+    pub fn new(socket: OwnedReadHalf, codec: C) -> Self {
+        let clock = crate::clock::default_clock();
         Self {
             socket,
             databuffer: BytesMut::new(),
             state: ReaderState::default(),
             ready_messages: vec![],
-            serialization_settings: seri_settings,
+            ready_sizes: std::collections::VecDeque::new(),
+            ready_bytes: 0,
+            peak_buffered: 0,
+            max_total_buffered: None,
+            max_message_size: None,
+            retain_raw_body: false,
+            frame_limiter: None,
+            codec,
             header_size: H::header_size(),
+            on_message: None,
+            on_header: None,
+            sink: None,
+            pause: PauseHandle::new(),
+            decode_errors: crate::stats::DecodeErrors::default(),
+            bytes_read: AtomicU64::new(0),
+            messages_received: AtomicU64::new(0),
+            skip_heartbeats: false,
+            last_read: clock.now(),
+            clock,
+            last_seq: None,
+        }
+    }
+
+    /// Sets a callback that is run on every message, right after it is decoded in [`update`]
+    /// and before it is stored to be retreived.
+    ///
+    /// this is for things that should happen for every inbound message (logging, metrics, validation)
+    /// without the application having to do them when it handles the message
+    ///
+    /// [`update`]: Reader::update
+    pub fn set_on_message(
+        &mut self,
+        hook: impl FnMut(&crate::msg::MessageWrapper<M, H>) + Send + 'static,
+    ) {
+        self.on_message = Some(Box::new(hook));
+    }
+
+    /// Removes the callback set with [`set_on_message`], if there is one
+    ///
+    /// [`set_on_message`]: Reader::set_on_message
+    pub fn clear_on_message(&mut self) {
+        self.on_message = None;
+    }
+
+    /// Sets a callback that is run on every header, as soon as it is decoded in [`update`]
+    /// and before the body of its message has been received.
+    ///
+    /// this lets routing decisions be made early, for example preparing resources for a large message.
+    /// returning `false` rejects the message: [`update`] returns [`HeaderRejected`], and as the body is not skipped
+    /// the reader is poisoned (see [`is_poisoned`]) so the connection should be closed
+    ///
+    /// [`update`]: Reader::update
+    /// [`HeaderRejected`]: error::UpdateError::HeaderRejected
+    /// [`is_poisoned`]: Reader::is_poisoned
+    pub fn set_on_header(&mut self, hook: impl FnMut(&H) -> bool + Send + 'static) {
+        self.on_header = Some(Box::new(hook));
+    }
+
+    /// Removes the callback set with [`set_on_header`], if there is one
+    ///
+    /// [`set_on_header`]: Reader::set_on_header
+    pub fn clear_on_header(&mut self) {
+        self.on_header = None;
+    }
+
+    /// Sets a channel that decoded messages are sent to directly from [`update`],
+    /// instead of being stored to be retreived with [`ready_messages`]. `None` goes back to storing them.
+    ///
+    /// this is for pipelines that forward messages imediately, and avoids buffering them in the reader.
+    /// if the channel is full [`update`] waits for space, so a slow consumer applies backpressure
+    /// to the reader (and, through TCP, to the peer)
+    ///
+    /// [`update`]: Reader::update
+    /// [`ready_messages`]: Reader::ready_messages
+    pub fn set_sink(
+        &mut self,
+        sink: Option<tokio::sync::mpsc::Sender<crate::msg::MessageWrapper<M, H>>>,
+    ) {
+        self.sink = sink;
+    }
+
+    /// Sets if the raw body bytes of decoded messages should be kept, for auditing or re-forwarding them.
+    /// they can be retreived with [`MessageWrapper::raw_body`]
+    ///
+    /// this is off by default, as it keeps the memory the body was read into alive
+    /// as long as the message is
+    ///
+    /// [`MessageWrapper::raw_body`]: crate::msg::MessageWrapper::raw_body
+    pub fn set_retain_raw_body(&mut self, retain: bool) {
+        self.retain_raw_body = retain;
+    }
+
+    /// Moves the settings and hooks of `old` over to this reader, for when it replaces a lost connection.
+    ///
+    /// the pause handle is shared, so handles given out by `old` control this reader too.
+    /// decoding state, buffered data and counters are not carried over
+    pub(crate) fn take_settings(&mut self, old: &mut Self) {
+        self.set_clock(old.clock.clone());
+        self.max_total_buffered = old.max_total_buffered;
+        self.max_message_size = old.max_message_size;
+        self.retain_raw_body = old.retain_raw_body;
+        self.set_max_frames_per_sec(old.max_frames_per_sec());
+        self.on_message = old.on_message.take();
+        self.on_header = old.on_header.take();
+        self.sink = old.sink.take();
+        self.pause = old.pause.clone();
+        self.skip_heartbeats = old.skip_heartbeats;
+    }
+
+    /// Restarts [`idle_time`] from now, as if data was just read
+    ///
+    /// [`idle_time`]: Reader::idle_time
+    pub(crate) fn reset_idle(&mut self) {
+        self.last_read = self.clock.now();
+    }
+
+    /// Sets where the reader gets the current time from, see the [`clock`] module.
+    ///
+    /// [`idle_time`] and the frame rate limit (see [`set_max_frames_per_sec`]) restart from the current time of `clock`
+    ///
+    /// [`clock`]: crate::clock
+    /// [`idle_time`]: Reader::idle_time
+    /// [`set_max_frames_per_sec`]: Reader::set_max_frames_per_sec
+    pub fn set_clock(&mut self, clock: crate::clock::SharedClock) {
+        self.last_read = clock.now();
+        self.clock = clock;
+        self.set_max_frames_per_sec(self.max_frames_per_sec());
+    }
+
+    /// Gets if the raw body bytes of decoded messages are kept, see [`set_retain_raw_body`]
+    ///
+    /// [`set_retain_raw_body`]: Reader::set_retain_raw_body
+    pub fn retain_raw_body(&self) -> bool {
+        self.retain_raw_body
+    }
+
+    /// Gets the estimated memory used by the reader: unprocessed data,
+    /// plus the size of the bodies of decoded messages that have not been retreived yet
+    pub fn buffered_bytes(&self) -> usize {
+        self.databuffer.len() + self.ready_bytes
+    }
+
+    /// Gets the number of bytes received for messages that are not complete yet (including their headers)
+    pub fn partial_frame_bytes(&self) -> usize {
+        match self.state {
+            // the header has already been taken out of the buffer
+            ReaderState::ReadingMessage { .. } | ReaderState::ProcessMessage { .. } => {
+                H::header_size() + self.databuffer.len()
+            }
+            _ => self.databuffer.len(),
         }
     }
 
+    /// Gets the number of errors hit while decoding messages, see [`DecodeErrors`]
+    ///
+    /// [`DecodeErrors`]: crate::stats::DecodeErrors
+    pub fn decode_errors(&self) -> crate::stats::DecodeErrors {
+        self.decode_errors
+    }
+
+    /// Resets the counts returned by [`decode_errors`] to zero, for example to measure a error rate
+    ///
+    /// [`decode_errors`]: Reader::decode_errors
+    pub fn reset_decode_errors(&mut self) {
+        self.decode_errors = crate::stats::DecodeErrors::default();
+    }
+
+    /// Gets the total number of bytes that have been read from the socket (including headers)
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read.load(Ordering::Relaxed)
+    }
+
+    /// Gets the total number of complete messages that have been received.
+    ///
+    /// this includes messages that failed to deserialize, those are also counted in [`decode_errors`].
+    /// skipped heartbeats (see [`set_skip_heartbeats`]) are not included
+    ///
+    /// [`set_skip_heartbeats`]: Reader::set_skip_heartbeats
+    /// [`decode_errors`]: Reader::decode_errors
+    pub fn messages_received(&self) -> u64 {
+        self.messages_received.load(Ordering::Relaxed)
+    }
+
+    /// Sets if heartbeats (see [`IsHeader::is_heartbeat`]) are dropped when they are received,
+    /// instead of being decoded like any other message. off by default
+    ///
+    /// this is set by [`SocketUtils::set_heartbeat`], which also sends them
+    ///
+    /// [`IsHeader::is_heartbeat`]: crate::header::IsHeader::is_heartbeat
+    /// [`SocketUtils::set_heartbeat`]: crate::socket::interface::_SocketUtils::set_heartbeat
+    pub fn set_skip_heartbeats(&mut self, skip: bool) {
+        self.skip_heartbeats = skip;
+    }
+
+    pub fn skip_heartbeats(&self) -> bool {
+        self.skip_heartbeats
+    }
+
+    /// Gets how long it has been since any data was read from the socket
+    /// (or since the reader was created, or heartbeats were enabled on it)
+    pub fn idle_time(&self) -> std::time::Duration {
+        self.clock.now().saturating_duration_since(self.last_read)
+    }
+
+    /// Gets the sequence number of the last message received, or `None` if none has been
+    /// (or the header type has no sequence numbers, see [`IsHeader::sequence`])
+    ///
+    /// [`IsHeader::sequence`]: crate::header::IsHeader::sequence
+    pub fn recv_sequence(&self) -> Option<u64> {
+        self.last_seq
+    }
+
+    /// Gets the highest value of [`buffered_bytes`] that has been seen
+    ///
+    /// [`buffered_bytes`]: Reader::buffered_bytes
+    pub fn peak_buffered_bytes(&self) -> usize {
+        self.peak_buffered
+    }
+
+    /// Sets a cap on [`buffered_bytes`], or `None` for no cap.
+    ///
+    /// while the cap is reached, [`read`] does not read from the socket (so TCP backpressure
+    /// slows the peer down) untill messages are processed with [`update`] and retreived.
+    /// if a single incomplete message is larger than the cap reading continues anyway,
+    /// as otherwise it could never be completed.
+    ///
+    /// [`buffered_bytes`]: Reader::buffered_bytes
+    /// [`read`]: Reader::read
+    /// [`update`]: Reader::update
+    pub fn set_max_total_buffered(&mut self, max: Option<usize>) {
+        self.max_total_buffered = max;
+    }
+
+    /// Gets the cap on [`buffered_bytes`]
+    ///
+    /// [`buffered_bytes`]: Reader::buffered_bytes
+    pub fn max_total_buffered(&self) -> Option<usize> {
+        self.max_total_buffered
+    }
+
+    /// Sets the largest message body that will be accepted, or `None` for no limit (the default).
+    ///
+    /// this is checked as soon as a header is decoded, so a peer claiming a huge body is rejected
+    /// with [`MessageTooLarge`] before any memory is used for it. after that the stream can not be
+    /// read further (the body is not skipped), so the connection should be closed, or the reader reset with [`clear_state`]
+    ///
+    /// [`MessageTooLarge`]: error::UpdateError::MessageTooLarge
+    /// [`clear_state`]: Reader::clear_state
+    pub fn set_max_message_size(&mut self, max: Option<u64>) {
+        self.max_message_size = max;
+    }
+
+    /// Sets the largest message body that will be accepted, see [`set_max_message_size`]
+    ///
+    /// [`set_max_message_size`]: Reader::set_max_message_size
+    #[must_use]
+    pub fn with_max_message_size(mut self, max: Option<u64>) -> Self {
+        self.set_max_message_size(max);
+        self
+    }
+
+    /// Gets the largest message body that will be accepted
+    pub fn max_message_size(&self) -> Option<u64> {
+        self.max_message_size
+    }
+
+    /// Returns if the reader has hit a error it can not recover from (for example [`MessageTooLarge`] or [`HeaderParser`]),
+    /// and will not read any more data untill [`clear_state`] is called
+    ///
+    /// [`MessageTooLarge`]: error::UpdateError::MessageTooLarge
+    /// [`HeaderParser`]: error::UpdateError::HeaderParser
+    /// [`clear_state`]: Reader::clear_state
+    pub fn is_poisoned(&self) -> bool {
+        matches!(self.state, ReaderState::Poisoned)
+    }
+
+    /// Pauses the reader, untill [`resume`] is called.
+    ///
+    /// while paused nothing is read from the socket, so the OS buffers fill up and TCP backpressure slows the peer down.
+    /// data that was already received is kept, but not decoded: [`read`] waits untill the reader is resumed
+    /// (without reading anything), and [`update`] does nothing. once resumed, buffered data is processed first,
+    /// then reading continues as normal
+    ///
+    /// [`resume`]: Reader::resume
+    /// [`read`]: Reader::read
+    /// [`update`]: Reader::update
+    pub fn pause(&self) {
+        self.pause.pause();
+    }
+
+    /// Resumes a reader paused with [`pause`]
+    ///
+    /// [`pause`]: Reader::pause
+    pub fn resume(&self) {
+        self.pause.resume();
+    }
+
+    /// Returns if the reader was paused with [`pause`]
+    ///
+    /// [`pause`]: Reader::pause
+    pub fn is_paused(&self) -> bool {
+        self.pause.is_paused()
+    }
+
+    /// Gets a handle that can pause and resume this reader, for example from another task
+    /// while this one is waiting in [`read`]
+    ///
+    /// [`read`]: Reader::read
+    pub fn pause_handle(&self) -> PauseHandle {
+        self.pause.clone()
+    }
+
+    /// Returns if reading is paused because the cap set with [`set_max_total_buffered`] was reached
+    ///
+    /// [`set_max_total_buffered`]: Reader::set_max_total_buffered
+    pub fn is_read_paused(&self) -> bool {
+        let can_free_space = self.ready_bytes > 0
+            || matches!(
+                self.state,
+                ReaderState::ProcessHeader | ReaderState::ProcessMessage { .. }
+            );
+        self.max_total_buffered
+            .is_some_and(|max| self.buffered_bytes() >= max && can_free_space)
+    }
+
+    /// Limits the number of frames decoded per second, or `None` for no limit.
+    ///
+    /// this uses a token bucket, allowing bursts of up to `max` frames.
+    /// once the limit is hit, [`update`] stops decoding and [`read`] waits untill another frame
+    /// is allowed instead of reading more data, so TCP backpressure slows the peer down.
+    /// this protects against floods of tiny messages, that are each cheap to send but not to decode.
+    /// a limit of 0 would stop the reader for good, so it is raised to 1 frame per second
+    /// (and [`max_frames_per_sec`] reports 1)
+    ///
+    /// [`update`]: Reader::update
+    /// [`read`]: Reader::read
+    /// [`max_frames_per_sec`]: Reader::max_frames_per_sec
+    pub fn set_max_frames_per_sec(&mut self, max: Option<u32>) {
+        self.frame_limiter = max.map(|max| crate::rate::TokenBucket::new(max, self.clock.clone()));
+    }
+
+    /// Gets the limit on frames decoded per second, after raising a limit of 0 to 1
+    pub fn max_frames_per_sec(&self) -> Option<u32> {
+        self.frame_limiter.as_ref().map(crate::rate::TokenBucket::per_sec)
+    }
+
+    fn record_peak(&mut self) {
+        self.peak_buffered = self.peak_buffered.max(self.buffered_bytes());
+    }
+
     /// attempts to read and store data. this does NOT attempt to read more than once,
     /// and does NOT process the data.
     ///
+    /// if the reader was paused with [`pause`] this waits untill it is resumed, and returns without reading.
+    /// if reading is paused (see [`set_max_total_buffered`]) this returns imediately without reading.
+    /// if the frame rate limit (see [`set_max_frames_per_sec`]) has been hit while a message is ready to be decoded,
+    /// this waits untill it can be decoded and returns without reading
+    ///
     /// ## Cancelation Saftey
     /// this method IS cancelation safe. no data will be lost if it is canceled
     ///
+    /// ## Returns
+    /// [`ReadStatus::Closed`] if the peer closed the connection between messages (including before sending anything),
+    /// otherwise [`ReadStatus::Open`]
+    ///
     /// ## Errors
-    /// when the underlying socket.read() returns a io error
-    pub async fn read(&mut self) -> std::io::Result<()> {
-        self.socket.read_buf(&mut self.databuffer).await?;
-
-        // here, only the reading variants are used.
-        // a reading variant, like ReadingHeader, should have the option to progress to the processing variant,
-        // like ProcessHeader, once it receives enough data
-        // processing stages are dealt with elsewhere
-        match self.state {
-            ReaderState::Ready => {
-                self.state = ReaderState::ReadingHeader;
+    /// when the underlying socket.read() returns a io error,
+    /// with `UnexpectedEof` if the peer closed the connection part way through a message,
+    /// or with `InvalidData` if the reader is poisoned (see [`is_poisoned`])
+    ///
+    /// [`pause`]: Reader::pause
+    /// [`set_max_total_buffered`]: Reader::set_max_total_buffered
+    /// [`set_max_frames_per_sec`]: Reader::set_max_frames_per_sec
+    /// [`is_poisoned`]: Reader::is_poisoned
+    pub async fn read(&mut self) -> std::io::Result<ReadStatus> {
+        if self.is_poisoned() {
+            return Err(poisoned_error());
+        }
+        if self.is_paused() {
+            self.pause.wait_resumed().await;
+            return Ok(ReadStatus::Open);
+        }
+        if self.is_read_paused() {
+            return Ok(ReadStatus::Open);
+        }
+        if let Some(wait) = self.frame_wait() {
+            tokio::time::sleep(wait).await;
+            return Ok(ReadStatus::Open);
+        }
+        std::future::poll_fn(|cx| self.poll_read_socket(cx)).await
+    }
+
+    /// Gets how long untill the next message can be decoded,
+    /// if one is ready to be decoded but the frame rate limit has been hit
+    pub(crate) fn frame_wait(&mut self) -> Option<std::time::Duration> {
+        match (&mut self.frame_limiter, &self.state) {
+            (Some(limiter), ReaderState::ProcessMessage { .. }) => {
+                Some(limiter.time_until_token()).filter(|wait| !wait.is_zero())
             }
-            ReaderState::ReadingHeader => {
-                if self.databuffer.len() >= self.header_size {
-                    // we art r e a d y
-                    self.state = ReaderState::ProcessHeader;
-                }
+            _ => None,
+        }
+    }
+
+    /// Reads from the socket once, like [`read`] but without checking if reading is paused or rate limited
+    ///
+    /// [`read`]: Reader::read
+    pub(crate) fn poll_read_socket(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<ReadStatus>> {
+        if self.is_poisoned() {
+            return std::task::Poll::Ready(Err(poisoned_error()));
+        }
+        let read = std::task::ready!(tokio_util::io::poll_read_buf(
+            std::pin::Pin::new(&mut self.socket),
+            cx,
+            &mut self.databuffer,
+        ))?;
+        if read == 0 {
+            // the buffer always has space for more data, so this is end-of-file
+            if self.at_frame_boundary() {
+                return std::task::Poll::Ready(Ok(ReadStatus::Closed));
             }
-            ReaderState::ReadingMessage { ref header } => {
-                //TODO make this not use .expect()
-                if self.databuffer.len()
-                    >= header
-                        .size()
-                        .try_into()
-                        .expect("Cannot convert u64 to usize, this is probably a 32bit system")
-                {
-                    // dun dun done
-                    self.state = ReaderState::ProcessMessage {
-                        header: header.clone(),
-                    };
-                }
+            return std::task::Poll::Ready(Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "the connection was closed by the peer part way through a message",
+            )));
+        }
+        self.bytes_read.fetch_add(read as u64, Ordering::Relaxed);
+        self.last_read = self.clock.now();
+        self.on_data();
+        std::task::Poll::Ready(Ok(ReadStatus::Open))
+    }
+
+    /// Updates the state after data was added to the buffer
+    fn on_data(&mut self) {
+        self.record_peak();
+
+        if let ReaderState::Ready = self.state {
+            self.state = ReaderState::ReadingHeader;
+        }
+        self.check_buffered();
+    }
+
+    /// Injects raw bytes into the reader, as if they were read from the socket.
+    ///
+    /// this is for debugging, for example replaying a byte stream from a packet capture
+    /// to reproduce a framing bug. after feeding data, call [`update`] to decode the messages in it.
+    ///
+    /// the bytes are added after any data already buffered, so mixing this with reading
+    /// from a socket that is still receiving data will probably corrupt the stream
+    ///
+    /// [`update`]: Reader::update
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.databuffer.extend_from_slice(bytes);
+        self.on_data();
+    }
+
+    /// Progresses a reading state to its processing state, if enough data has been buffered.
+    ///
+    /// here, only the reading variants are used.
+    /// a reading variant, like ReadingHeader, should have the option to progress to the processing variant,
+    /// like ProcessHeader, once it receives enough data
+    /// processing stages are dealt with elsewhere
+    fn check_buffered(&mut self) {
+        match self.state {
+            ReaderState::Ready | ReaderState::ReadingHeader
+                if self.databuffer.len() >= self.header_size =>
+            {
+                // we art r e a d y
+                self.state = ReaderState::ProcessHeader;
+            }
+            // the size was checked to fit in a usize when the header was processed
+            ReaderState::ReadingMessage { ref header }
+                if usize::try_from(header.size())
+                    .is_ok_and(|size| self.databuffer.len() >= size) =>
+            {
+                // dun dun done
+                self.state = ReaderState::ProcessMessage {
+                    header: header.clone(),
+                };
             }
             _ => {}
         }
-
-        Ok(())
     }
 
     /// Updates the reader.
     ///
-    /// does not read any bytes from the socket, but instead checks if
-    /// a message is ready to be deserialized.
+    /// does not read any bytes from the socket, but instead deserializes
+    /// every message that has been completely buffered.
+    ///
+    /// if a sink is set (see [`set_sink`]) the messages are sent to it instead of the result queue.
+    ///
+    /// ## Cancelation Saftey
+    /// without a sink this is cancelation safe. with one, if it is canceled while waiting for space
+    /// in the channel the message being sent is lost
     ///
     /// # Returns
-    /// if there is a new message in the result queue or not
+    /// if there is at least one new message in the result queue (or sent to the sink) or not
     ///
     /// # Errors
-    /// if the message or header could not be decoded
+    /// if a message or header could not be decoded,
+    /// or [`SinkClosed`] if the receiving side of the sink was dropped.
+    /// messages decoded before the error are kept
+    ///
+    /// [`set_sink`]: Reader::set_sink
+    /// [`SinkClosed`]: error::UpdateError::SinkClosed
     pub async fn update(&mut self) -> Result<bool, error::UpdateError<H>> {
-        match self.state {
-            ReaderState::ProcessHeader => {
-                let header_dat = self.databuffer.split_to(self.header_size).freeze();
-                match H::from_bytes(header_dat) {
-                    Ok(header) => {
-                        self.state = ReaderState::ReadingMessage { header };
-                        Ok(false)
-                    }
-                    Err(e) => Err(error::UpdateError::HeaderParser(e)),
+        let mut new_message = false;
+        while self.update_one().await? {
+            new_message = true;
+        }
+        Ok(new_message)
+    }
+
+    /// Updates the reader, like [`update`], but deserializes at most one message.
+    ///
+    /// data after that message is left buffered, so this can be used to stop processing
+    /// at a exact message, for example before switching the stream to a different protocol.
+    /// if the body of a message is already buffered when its header is processed
+    /// (for example a zero-length body) it is deserialized in the same call
+    ///
+    /// # Returns
+    /// if there is a new message in the result queue (or sent to the sink) or not
+    ///
+    /// # Errors
+    /// see [`update`]
+    ///
+    /// [`update`]: Reader::update
+    pub async fn update_one(&mut self) -> Result<bool, error::UpdateError<H>> {
+        match self.decode_one()? {
+            Some((message, body_len)) => {
+                if let Some(sink) = &self.sink {
+                    return match sink.send(message).await {
+                        Ok(()) => Ok(true),
+                        Err(_) => Err(error::UpdateError::SinkClosed),
+                    };
                 }
-            }
-            ReaderState::ProcessMessage { ref header } => {
-                //TODO remove .expect()
-                let message_dat = self.databuffer.split_to(usize::try_from(header.size()).expect("Converted u64 to usize. if this fails, you are probably not on a 64 bit system and sending LARGE messages")).freeze();
-                let message: crate::msg::MessageWrapper<M, H> =
-                    crate::msg::MessageWrapper::<M, H>::from_bytes(
-                        &message_dat,
-                        self.serialization_settings.clone(),
-                    )?;
                 self.ready_messages.push(message);
+                self.ready_sizes.push_back(body_len);
+                self.ready_bytes += body_len;
+                self.record_peak();
                 Ok(true)
             }
-            _ => {
-                /* ignore other things because they are related to processing messages */
-                Ok(false)
+            None => Ok(false),
+        }
+    }
+
+    /// Deserializes the next message, if it has been completely buffered, and runs the message hook on it
+    ///
+    /// # Returns
+    /// the message, and the size of its body
+    pub(crate) fn decode_one(
+        &mut self,
+    ) -> Result<Option<Decoded<M, H>>, error::UpdateError<H>> {
+        let Some((header, message_dat)) = self.next_frame()? else {
+            return Ok(None);
+        };
+        let mut message: crate::msg::MessageWrapper<M, H> =
+            crate::msg::MessageWrapper::<M, H>::from_bytes(&message_dat, &self.codec).map_err(
+                |source| {
+                    self.decode_errors.deserialize_errors += 1;
+                    error::UpdateError::MessageDeseri {
+                        source,
+                        kind: header.kind(),
+                        body_len: message_dat.len(),
+                        header_size: self.header_size,
+                    }
+                },
+            )?;
+        if self.retain_raw_body {
+            message.set_raw_body(message_dat.clone());
+        }
+        message.set_kind(header.kind());
+        if !header.extension().is_empty() {
+            message.set_extension(Bytes::copy_from_slice(header.extension()));
+        }
+        if let Some(hook) = &mut self.on_message {
+            hook(&message);
+        }
+        Ok(Some((message, message_dat.len())))
+    }
+
+    /// Updates the reader, like [`update`], but instead of deserializing the message
+    /// the body bytes are passed to `process`, which can deserialize them however it likes
+    /// (reusing buffers, deserializing into an existing allocation, etc.)
+    ///
+    /// the message is not stored in the reader, or passed to the hook set with [`set_on_message`]
+    ///
+    /// # Returns
+    /// the result of `process` if a message was read
+    ///
+    /// # Errors
+    /// if the header could not be decoded, or `process` failed
+    ///
+    /// [`update`]: Reader::update
+    /// [`set_on_message`]: Reader::set_on_message
+    pub async fn update_with<F, T, E>(
+        &mut self,
+        mut process: F,
+    ) -> Result<Option<T>, error::UpdateWithError<H, E>>
+    where
+        F: FnMut(&[u8]) -> Result<T, E>,
+    {
+        match self.next_frame()? {
+            Some((_header, message_dat)) => process(&message_dat).map(Some).map_err(|e| {
+                self.decode_errors.deserialize_errors += 1;
+                error::UpdateWithError::Process(e)
+            }),
+            None => Ok(None),
+        }
+    }
+
+    /// Checks that a newly decoded header is acceptable, before waiting for its body
+    fn check_header(&mut self, header: &H) -> Result<(), error::UpdateError<H>> {
+        if let Some(limit) = self.max_message_size {
+            if header.size() > limit {
+                return Err(error::UpdateError::MessageTooLarge {
+                    claimed: header.size(),
+                    limit,
+                });
+            }
+        }
+        if usize::try_from(header.size()).is_err() {
+            return Err(error::UpdateError::SizeOverflow { size: header.size() });
+        }
+        if let Some(hook) = &mut self.on_header {
+            if !hook(header) {
+                return Err(error::UpdateError::HeaderRejected { size: header.size() });
+            }
+        }
+        Ok(())
+    }
+
+    /// Stops reading from the stream, after a error that leaves it unreadable
+    fn poison(&mut self) {
+        self.databuffer.clear();
+        self.state = ReaderState::Poisoned;
+    }
+
+    /// Processes buffered data up to the end of the next message, if it has all been received.
+    ///
+    /// # Returns
+    /// the header and body of the message, if there was one
+    fn next_frame(&mut self) -> Result<Option<(H, Bytes)>, error::UpdateError<H>> {
+        if self.is_paused() {
+            // buffered data is held untill the reader is resumed
+            return Ok(None);
+        }
+        loop {
+            match self.state {
+                ReaderState::ProcessHeader => {
+                    if self.databuffer.len() < self.header_size {
+                        // only reached through check_buffered, but splitting would panic otherwise
+                        self.state = ReaderState::ReadingHeader;
+                        return Ok(None);
+                    }
+                    let header_dat = self.databuffer.split_to(self.header_size).freeze();
+                    match H::from_bytes(header_dat) {
+                        Ok(header) => {
+                            if let Err(e) = self.check_header(&header) {
+                                self.poison();
+                                return Err(e);
+                            }
+                            self.state = ReaderState::ReadingMessage { header };
+                            self.check_buffered();
+                        }
+                        Err(e) => {
+                            self.decode_errors.header_parse_errors += 1;
+                            // the length is unknown, so the body can not be skipped.
+                            // reading on would treat it as the next header
+                            self.poison();
+                            return Err(error::UpdateError::HeaderParser(e));
+                        }
+                    }
+                }
+                ReaderState::ProcessMessage { ref header } => {
+                    let header = header.clone();
+                    let Ok(size) = usize::try_from(header.size()) else {
+                        self.poison();
+                        return Err(error::UpdateError::SizeOverflow { size: header.size() });
+                    };
+                    // heartbeats are not numbered, they can be sent at any time.
+                    // checked before the rate limit, so dropped duplicates do not use up a frame
+                    let seq = header.sequence().filter(|_| !header.is_heartbeat());
+                    if let Some(got) = seq {
+                        let expected = self.last_seq.map_or(0, |last| last.wrapping_add(1));
+                        if got > expected {
+                            // the frame is left in place, and delivered normally by the next call
+                            self.last_seq = Some(got.wrapping_sub(1));
+                            return Err(error::UpdateError::SequenceGap { expected, got });
+                        }
+                        if got < expected {
+                            self.databuffer.advance(size);
+                            self.state = ReaderState::Ready;
+                            self.check_buffered();
+                            return Err(error::UpdateError::DuplicateSequence { seq: got, expected });
+                        }
+                    }
+                    if let Some(limiter) = &mut self.frame_limiter {
+                        if !limiter.try_take() {
+                            // frame rate limit hit, leave the message for later
+                            return Ok(None);
+                        }
+                    }
+                    if seq.is_some() {
+                        // only once the frame is definitely being consumed, so it is not seen as a duplicate later
+                        self.last_seq = seq;
+                    }
+                    let message_dat = self.databuffer.split_to(size).freeze();
+                    // the frame has been consumed, so even if it fails to deserialize the next one can be read
+                    self.state = ReaderState::Ready;
+                    self.check_buffered();
+                    header
+                        .validate_body(&message_dat)
+                        .map_err(error::UpdateError::BodyValidation)?;
+                    if self.skip_heartbeats && header.is_heartbeat() {
+                        // heartbeats only show the peer is alive, which reading them already did
+                        continue;
+                    }
+                    self.messages_received.fetch_add(1, Ordering::Relaxed);
+                    return Ok(Some((header, message_dat)));
+                }
+                _ => {
+                    /* ignore other things because they are related to processing messages */
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    /// Removes and returns the ready messages of one kind (see [`IsHeader::kind`]), in the order they were received.
+    ///
+    /// messages of other kinds are left in place, so each kind can be handled by a different part of the application
+    ///
+    /// [`IsHeader::kind`]: crate::header::IsHeader::kind
+    pub fn take_messages_of_kind(&mut self, kind: u16) -> Vec<crate::msg::MessageWrapper<M, H>> {
+        let mut taken = Vec::new();
+        let mut kept = Vec::with_capacity(self.ready_messages.len());
+        let mut kept_sizes = std::collections::VecDeque::with_capacity(self.ready_sizes.len());
+        for message in self.ready_messages.drain(..) {
+            let size = self.ready_sizes.pop_front().unwrap_or(0);
+            if message.kind() == kind {
+                self.ready_bytes -= size;
+                taken.push(message);
+            } else {
+                kept.push(message);
+                kept_sizes.push_back(size);
             }
         }
+        self.ready_messages = kept;
+        self.ready_sizes = kept_sizes;
+        taken
     }
 
-    pub fn ready_messages(&mut self) -> std::vec::Drain<crate::msg::MessageWrapper<M, H>> {
+    pub fn ready_messages(&mut self) -> std::vec::Drain<'_, crate::msg::MessageWrapper<M, H>> {
+        self.ready_sizes.clear();
+        self.ready_bytes = 0;
         self.ready_messages.drain(..)
     }
 
@@ -163,6 +975,7 @@ where
         if self.ready_messages.is_empty() {
             None
         } else {
+            self.ready_bytes -= self.ready_sizes.pop_front().unwrap_or(0);
             Some(self.ready_messages.remove(0))
         }
     }
@@ -171,6 +984,28 @@ where
         self.databuffer.clear();
         self.state = ReaderState::default();
         self.ready_messages.clear();
+        self.ready_sizes.clear();
+        self.ready_bytes = 0;
+    }
+
+    /// Replaces the socket being read from, keeping all buffered data and state.
+    ///
+    /// this is for moving a connection to a new socket (for example after a network change)
+    /// without losing application state. it must be done at a frame boundary (see [`at_frame_boundary`]),
+    /// otherwise the rest of a partially received message is expected from the new socket
+    ///
+    /// # Returns
+    /// the old socket
+    ///
+    /// [`at_frame_boundary`]: Reader::at_frame_boundary
+    pub fn replace_socket(&mut self, socket: OwnedReadHalf) -> OwnedReadHalf {
+        std::mem::replace(&mut self.socket, socket)
+    }
+
+    /// Returns if the reader is between messages, with no partially received data buffered
+    pub fn at_frame_boundary(&self) -> bool {
+        matches!(self.state, ReaderState::Ready | ReaderState::ReadingHeader)
+            && self.databuffer.is_empty()
     }
 
     pub fn as_socket(&self) -> &OwnedReadHalf {
@@ -181,16 +1016,36 @@ where
         &mut self.socket
     }
 
+    /// Gives up the ability to join this back into a `TcpStream`, see [`Detached`] for more info
+    ///
+    /// [`Detached`]: super::Detached
+    pub fn detach(self) -> super::Detached<Self> {
+        super::Detached::new(self)
+    }
+
+    /// Turns the reader into a [`Stream`] of messages, see [`ReaderStream`]
+    ///
+    /// [`Stream`]: futures_core::Stream
+    /// [`ReaderStream`]: super::ReaderStream
+    pub fn into_stream(self) -> super::ReaderStream<H, M, C> {
+        super::ReaderStream::new(self)
+    }
+
     pub fn into_socket(self) -> OwnedReadHalf {
         self.socket
     }
+
+    /// Consumes the reader, producing the socket and any data that was read from it but not processed
+    pub fn into_socket_with_buffer(self) -> (OwnedReadHalf, BytesMut) {
+        (self.socket, self.databuffer)
+    }
 }
 
-impl<H, M, O> Debug for Reader<H, M, O>
+impl<H, M, C> Debug for Reader<H, M, C>
 where
     H: crate::header::IsHeader + Debug,
     M: Serialize + DeserializeOwned + Debug,
-    O: bincode::Options,
+    C: crate::codec::Codec,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Reader")
@@ -198,8 +1053,17 @@ where
             .field("databuffer", &self.databuffer)
             .field("state", &self.state)
             .field("ready_messages", &self.ready_messages)
-            .field("serialization_settings", &"{ ... }")
+            .field("ready_bytes", &self.ready_bytes)
+            .field("peak_buffered", &self.peak_buffered)
+            .field("max_total_buffered", &self.max_total_buffered)
+            .field("max_message_size", &self.max_message_size)
+            .field("frame_limiter", &self.frame_limiter)
+            .field("clock", &self.clock)
+            .field("codec", &"{ ... }")
             .field("header_size", &self.header_size)
+            .field("on_message", &self.on_message.as_ref().map(|_| "{ ... }"))
+            .field("on_header", &self.on_header.as_ref().map(|_| "{ ... }"))
+            .field("sink", &self.sink.is_some())
             .finish()
     }
 }
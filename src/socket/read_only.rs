@@ -0,0 +1,129 @@
+use std::fmt::Debug;
+use std::net::SocketAddr;
+
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::net::tcp::OwnedWriteHalf;
+
+use super::interface::error;
+use super::read::Reader;
+
+/// A connection that is only read from.
+///
+/// this is for pure consumers (for example a subscriber that never sends anything),
+/// unlike [`SocketUtils`] it has no [`Writer`], so updating it never touches one.
+///
+/// the write half of the socket is kept (but never used) for as long as this is,
+/// so the peer does not see the connection half closed. it is dropped by [`into_reader`],
+/// which shuts down the sending direction of the connection
+///
+/// [`into_reader`]: ReadOnlyConnection::into_reader
+///
+/// [`SocketUtils`]: crate::socket::interface::_SocketUtils
+/// [`Writer`]: crate::socket::write::Writer
+pub struct ReadOnlyConnection<H, M, C>
+where
+    H: crate::header::IsHeader,
+    M: Serialize + DeserializeOwned,
+    C: crate::codec::Codec + Clone,
+{
+    reader: Reader<H, M, C>,
+    /// only held so the socket stays fully open, dropping it would send a FIN
+    _write_half: OwnedWriteHalf,
+    addr: SocketAddr,
+}
+
+impl<H, M, C> ReadOnlyConnection<H, M, C>
+where
+    H: crate::header::IsHeader + Debug + Clone,
+    M: Serialize + DeserializeOwned,
+    C: crate::codec::Codec + Clone,
+{
+    pub(crate) fn new(
+        reader: Reader<H, M, C>,
+        write_half: OwnedWriteHalf,
+        addr: SocketAddr,
+    ) -> Self {
+        Self {
+            reader,
+            _write_half: write_half,
+            addr,
+        }
+    }
+
+    /// Attempt to read some data from the socket,
+    /// blocking untill at least a little bit of data has been read
+    ///
+    /// for more info see [`Reader.read()`]
+    ///
+    /// [`Reader.read()`]: crate::socket::read::Reader
+    pub async fn update_read(&mut self) -> std::io::Result<super::read::ReadStatus> {
+        self.reader.read().await
+    }
+
+    /// Updates the reader, deserializing incoming messages if there are any
+    ///
+    /// # Returns
+    /// if sucsesfull, weather or not a new message is ready to be read.
+    ///
+    /// # Errors
+    /// if deserializing a incoming message fails
+    pub async fn update(&mut self) -> Result<bool, error::UpdateError<H>> {
+        self.reader
+            .update()
+            .await
+            .map_err(error::UpdateError::ReadUpdate)
+    }
+
+    /// Repeatedly reads from the socket and updates the reader,
+    /// untill a new message is available, then returns it
+    ///
+    /// # Errors
+    /// if reading or updating the reader fails. if the peer closes the connection
+    /// this returns [`Closed`] (or a io error of kind `UnexpectedEof`, if it was part way through a message)
+    /// instead of waiting forever
+    ///
+    /// [`Closed`]: error::WaitMessageError::Closed
+    pub async fn wait_for_message(
+        &mut self,
+    ) -> Result<crate::msg::MessageWrapper<M, H>, error::WaitMessageError<H>> {
+        loop {
+            // update before reading, in case data for a message is already buffered
+            self.update().await?;
+            if let Some(m) = self.reader.latest_message() {
+                return Ok(m);
+            }
+            if self.update_read().await? == super::read::ReadStatus::Closed {
+                return Err(error::WaitMessageError::Closed);
+            }
+        }
+    }
+
+    /// Gets all incoming messages that have been received
+    pub fn get_messages(&mut self) -> std::vec::Drain<'_, crate::msg::MessageWrapper<M, H>> {
+        self.reader.ready_messages()
+    }
+
+    /// Gets the latest incoming message received
+    pub fn get_latest_message(&mut self) -> Option<crate::msg::MessageWrapper<M, H>> {
+        self.reader.latest_message()
+    }
+
+    /// Gets the address the connection is to
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn as_reader(&self) -> &Reader<H, M, C> {
+        &self.reader
+    }
+
+    pub fn as_reader_mut(&mut self) -> &mut Reader<H, M, C> {
+        &mut self.reader
+    }
+
+    /// Gets the reader, dropping the unused write half,
+    /// which shuts down the sending direction of the connection
+    pub fn into_reader(self) -> Reader<H, M, C> {
+        self.reader
+    }
+}
@@ -0,0 +1,112 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use bytes::Bytes;
+
+/// A FIFO queue of buffers stored in a temporary file.
+///
+/// each buffer is stored as its length (a big endian u64), followed by its contents.
+/// once every buffer has been read back out the file is truncated, so it only grows while backed up
+#[derive(Debug)]
+pub(crate) struct SpillQueue {
+    file: File,
+    read_pos: u64,
+    write_pos: u64,
+    len: usize,
+    /// total size of the buffers stored in the file, without their length prefixes
+    bytes: usize,
+}
+
+impl SpillQueue {
+    pub(crate) fn new() -> std::io::Result<Self> {
+        Ok(Self {
+            file: tempfile::tempfile()?,
+            read_pos: 0,
+            write_pos: 0,
+            len: 0,
+            bytes: 0,
+        })
+    }
+
+    /// Number of buffers in the queue
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Total size of the buffers in the queue
+    pub(crate) fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Adds a buffer to the back of the queue
+    pub(crate) fn push(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        self.file.seek(SeekFrom::Start(self.write_pos))?;
+        self.file.write_all(&(buf.len() as u64).to_be_bytes())?;
+        self.file.write_all(buf)?;
+        self.write_pos += 8 + buf.len() as u64;
+        self.len += 1;
+        self.bytes += buf.len();
+        Ok(())
+    }
+
+    /// Removes the buffer at the front of the queue
+    pub(crate) fn pop(&mut self) -> std::io::Result<Option<Bytes>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let buf = self.read_at(self.read_pos)?;
+        let len = buf.len();
+        self.read_pos += 8 + len as u64;
+        self.len -= 1;
+        self.bytes -= len;
+        if self.is_empty() {
+            self.file.set_len(0)?;
+            self.read_pos = 0;
+            self.write_pos = 0;
+        }
+        Ok(Some(buf))
+    }
+
+    /// Removes every buffer in the queue.
+    ///
+    /// all of them are read before any are removed, so if reading fails the queue is left as it was
+    pub(crate) fn take_all(&mut self) -> std::io::Result<Vec<Bytes>> {
+        let mut bufs = Vec::with_capacity(self.len);
+        let mut pos = self.read_pos;
+        for _ in 0..self.len {
+            let buf = self.read_at(pos)?;
+            pos += 8 + buf.len() as u64;
+            bufs.push(buf);
+        }
+        self.read_pos = 0;
+        self.write_pos = 0;
+        self.len = 0;
+        self.bytes = 0;
+        // everything has been read out already, this only frees the disk space
+        // (the next push overwrites the file from the start either way)
+        let _ = self.file.set_len(0);
+        Ok(bufs)
+    }
+
+    /// Reads the buffer stored at `pos`
+    fn read_at(&mut self, pos: u64) -> std::io::Result<Bytes> {
+        self.file.seek(SeekFrom::Start(pos))?;
+        let mut len = [0; 8];
+        self.file.read_exact(&mut len)?;
+        let len = usize::try_from(u64::from_be_bytes(len))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let mut buf = vec![0; len];
+        self.file.read_exact(&mut buf)?;
+        Ok(Bytes::from(buf))
+    }
+
+    /// Gets the file the queue is stored in, for breaking it in tests
+    #[cfg(test)]
+    pub(crate) fn file_mut(&mut self) -> &mut File {
+        &mut self.file
+    }
+}
@@ -0,0 +1,129 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use futures_core::Stream;
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::read::{error::ReadError, ReadStatus, Reader};
+
+/// A [`Reader`] as a [`Stream`] of received messages.
+///
+/// produced by [`Reader::into_stream`]. each poll reads from the socket as needed and yields one message,
+/// messages already decoded (and waiting in the reader) are yielded first.
+/// messages are yielded directly, so any sink set on the reader is not used.
+///
+/// the stream ends when the peer closes the connection between messages, or after a error reading from the socket.
+/// errors decoding a message body do not end the stream, as the next message can still be read.
+/// errors that poison the reader (see [`Reader::is_poisoned`]), like a header failing to parse,
+/// are yielded and then the stream ends
+///
+/// ## Cancelation Saftey
+/// all buffered data is kept in the reader, so dropping a `next()` future (or the stream, after
+/// getting the reader back with [`into_inner`]) part way through a message does not lose anything
+///
+/// [`into_inner`]: ReaderStream::into_inner
+pub struct ReaderStream<H, M, C>
+where
+    H: crate::header::IsHeader,
+    M: Serialize + DeserializeOwned,
+    C: crate::codec::Codec,
+{
+    reader: Reader<H, M, C>,
+    /// waiting for the frame rate limit
+    limit_sleep: Option<Pin<Box<tokio::time::Sleep>>>,
+    /// waiting for the reader to be resumed
+    resume_wait: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+    done: bool,
+}
+
+impl<H, M, C> ReaderStream<H, M, C>
+where
+    H: crate::header::IsHeader + Clone,
+    M: Serialize + DeserializeOwned,
+    C: crate::codec::Codec + Clone,
+{
+    pub(crate) fn new(reader: Reader<H, M, C>) -> Self {
+        Self {
+            reader,
+            limit_sleep: None,
+            resume_wait: None,
+            done: false,
+        }
+    }
+
+    pub fn as_reader(&self) -> &Reader<H, M, C> {
+        &self.reader
+    }
+
+    pub fn as_reader_mut(&mut self) -> &mut Reader<H, M, C> {
+        &mut self.reader
+    }
+
+    /// Gets the reader back, with any buffered data
+    pub fn into_inner(self) -> Reader<H, M, C> {
+        self.reader
+    }
+}
+
+// the reader is never pinned, only the futures are (and they are boxed)
+impl<H, M, C> Unpin for ReaderStream<H, M, C>
+where
+    H: crate::header::IsHeader,
+    M: Serialize + DeserializeOwned,
+    C: crate::codec::Codec,
+{
+}
+
+impl<H, M, C> Stream for ReaderStream<H, M, C>
+where
+    H: crate::header::IsHeader + Clone,
+    M: Serialize + DeserializeOwned,
+    C: crate::codec::Codec + Clone,
+{
+    type Item = Result<crate::msg::MessageWrapper<M, H>, ReadError<H>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(message) = this.reader.latest_message() {
+                return Poll::Ready(Some(Ok(message)));
+            }
+            if this.done {
+                return Poll::Ready(None);
+            }
+            if this.reader.is_paused() || this.resume_wait.is_some() {
+                let handle = this.reader.pause_handle();
+                let wait = this
+                    .resume_wait
+                    .get_or_insert_with(|| Box::pin(async move { handle.wait_resumed().await }));
+                ready!(wait.as_mut().poll(cx));
+                this.resume_wait = None;
+                continue;
+            }
+            if let Some(sleep) = &mut this.limit_sleep {
+                ready!(sleep.as_mut().poll(cx));
+                this.limit_sleep = None;
+            }
+            match this.reader.decode_one() {
+                Ok(Some((message, _body_len))) => return Poll::Ready(Some(Ok(message))),
+                Ok(None) => {}
+                Err(e) => return Poll::Ready(Some(Err(e.into()))),
+            }
+            if let Some(wait) = this.reader.frame_wait() {
+                this.limit_sleep = Some(Box::pin(tokio::time::sleep(wait)));
+                continue;
+            }
+            match ready!(this.reader.poll_read_socket(cx)) {
+                Ok(ReadStatus::Open) => {}
+                Ok(ReadStatus::Closed) => this.done = true,
+                Err(e) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(e.into())));
+                }
+            }
+        }
+    }
+}
@@ -1,13 +1,52 @@
-use std::{collections::VecDeque, marker::PhantomData};
+use std::{
+    collections::VecDeque,
+    marker::PhantomData,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
-use bytes::{Buf, Bytes};
+use bytes::{Buf, Bytes, BytesMut};
 use serde::{de::DeserializeOwned, Serialize};
 use tokio::{io::AsyncWriteExt, net::tcp::OwnedWriteHalf};
 
 pub mod error {
     #[derive(Debug, thiserror::Error)]
     #[error("Failed to serialize message!\n{0}")]
-    pub struct SeriError(#[from] bincode::Error);
+    pub struct SeriError(#[from] crate::codec::error::CodecError);
+
+    /// Error from queueing a message on a [`Writer`]
+    ///
+    /// [`Writer`]: super::Writer
+    #[derive(Debug, thiserror::Error)]
+    pub enum QueueError {
+        #[error("{0}")]
+        Seri(#[from] SeriError),
+        /// the writer was created with a capacity (see [`Writer::with_capacity`]), and queueing the message would go over it.
+        /// nothing was queued, write some of the queue and try again
+        ///
+        /// [`Writer::with_capacity`]: super::Writer::with_capacity
+        #[error("Send queue is full! ({queued} of {capacity} bytes queued, message is {size} bytes)")]
+        Full {
+            queued: usize,
+            capacity: usize,
+            size: usize,
+        },
+        /// the writer is quiescing (see [`Writer::quiesce`]), and does not accept new messages
+        ///
+        /// [`Writer::quiesce`]: super::Writer::quiesce
+        #[error("Writer is quiescing, and not accepting new messages!")]
+        Quiescing,
+    }
+
+    /// Error from sending messages through [`Writer`]s `Sink` implementation
+    ///
+    /// [`Writer`]: super::Writer
+    #[derive(Debug, thiserror::Error)]
+    pub enum SinkError {
+        #[error("Failed to queue message!\n{0}")]
+        Queue(#[from] QueueError),
+        #[error("Failed to send message!\n{0}")]
+        Write(#[from] WriteError),
+    }
 
     #[derive(Debug, thiserror::Error)]
     pub enum WriteError {
@@ -16,71 +55,891 @@ pub mod error {
         #[error("Socket Closed!")]
         Disconnected,
     }
+
+    impl WriteError {
+        /// Returns if the error is transient, and the write can be retried.
+        ///
+        /// `WouldBlock`, `Interrupted` and `TimedOut` io errors are transient,
+        /// disconnection and all other io errors are not
+        pub fn is_transient(&self) -> bool {
+            match self {
+                Self::IOError(e) => matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock
+                        | std::io::ErrorKind::Interrupted
+                        | std::io::ErrorKind::TimedOut
+                ),
+                Self::Disconnected => false,
+            }
+        }
+    }
+}
+
+/// A point in time summary of what is queued on a [`Writer`], see [`Writer::queue_snapshot`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueueSnapshot {
+    /// number of messages not fully written yet (including spilled ones)
+    pub message_count: usize,
+    /// number of bytes of those messages that have not been written yet
+    pub total_bytes: usize,
+    /// how long ago the oldest of them was queued, `None` if nothing is queued
+    pub oldest_enqueue_age: Option<std::time::Duration>,
+}
+
+impl QueueSnapshot {
+    /// Combines two snapshots (for example of different connections), adding up the counts and keeping the oldest age
+    #[must_use]
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            message_count: self.message_count + other.message_count,
+            total_bytes: self.total_bytes + other.total_bytes,
+            oldest_enqueue_age: self.oldest_enqueue_age.max(other.oldest_enqueue_age),
+        }
+    }
+}
+
+/// A queued message (or group of messages), made of one or more pieces that are sent one after the other.
+///
+/// stamping sequence numbers (see [`Writer::stamp_sequences`]) replaces only the headers,
+/// so the bodies stay slices of the original buffer, which may be shared with other connections (by a broadcast)
+#[derive(Debug, Default)]
+struct Queued {
+    parts: VecDeque<Bytes>,
+    /// total length of `parts`
+    remaining: usize,
+}
+
+impl Queued {
+    fn push(&mut self, part: Bytes) {
+        if !part.is_empty() {
+            self.remaining += part.len();
+            self.parts.push_back(part);
+        }
+    }
+
+    /// Joins the pieces back into one buffer, which is only copied if there is more than one piece
+    fn into_bytes(mut self) -> Bytes {
+        if self.parts.len() <= 1 {
+            return self.parts.pop_front().unwrap_or_default();
+        }
+        let mut buf = BytesMut::with_capacity(self.remaining);
+        for part in self.parts {
+            buf.extend_from_slice(&part);
+        }
+        buf.freeze()
+    }
+}
+
+impl From<Bytes> for Queued {
+    fn from(bytes: Bytes) -> Self {
+        let mut queued = Self::default();
+        queued.push(bytes);
+        queued
+    }
+}
+
+impl Buf for Queued {
+    fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    fn chunk(&self) -> &[u8] {
+        self.parts.front().map_or(&[], |part| &part[..])
+    }
+
+    fn advance(&mut self, mut cnt: usize) {
+        assert!(cnt <= self.remaining, "advanced past the end of the queued data");
+        self.remaining -= cnt;
+        while cnt > 0 {
+            let front = self.parts.front_mut().expect("checked against `remaining`");
+            if cnt < front.len() {
+                front.advance(cnt);
+                return;
+            }
+            cnt -= front.len();
+            self.parts.pop_front();
+        }
+    }
+
+    fn chunks_vectored<'a>(&'a self, dst: &mut [std::io::IoSlice<'a>]) -> usize {
+        let mut filled = 0;
+        for (slot, part) in dst.iter_mut().zip(&self.parts) {
+            *slot = std::io::IoSlice::new(part);
+            filled += 1;
+        }
+        filled
+    }
 }
 
 #[derive(Debug)]
-pub struct Writer<H, M, O>
+pub struct Writer<H, M, C>
 where
-    O: bincode::Options + Clone,
+    C: crate::codec::Codec + Clone,
 {
     socket: OwnedWriteHalf,
-    send_buffers: VecDeque<Bytes>,
-    serialization_options: O,
+    send_buffers: VecDeque<Queued>,
+    /// total size of the unwritten data in `send_buffers`
+    queued_bytes: usize,
+    /// max number of bytes that can be queued (including spilled ones), see [`Writer::with_capacity`]
+    max_queued_bytes: Option<usize>,
+    /// if new messages are rejected, see [`Writer::quiesce`]
+    quiescing: bool,
+    /// total bytes written to the socket
+    bytes_written: AtomicU64,
+    /// total queued buffers that have been fully written
+    messages_sent: AtomicU64,
+    /// if `H` has sequence numbers, see [`IsHeader::sequence`]
+    ///
+    /// [`IsHeader::sequence`]: crate::header::IsHeader::sequence
+    sequenced: bool,
+    /// sequence number given to the next message queued
+    next_seq: u64,
+    /// when each queued buffer (including spilled ones) was queued, in the same order
+    queued_at: VecDeque<tokio::time::Instant>,
+    /// time buffers spent queued before being fully written
+    queue_wait: crate::stats::Histogram,
+    /// if the first buffer in `send_buffers` has been partially written
+    front_partial: bool,
+    #[cfg(feature = "spillover")]
+    spill: Option<super::spill::SpillQueue>,
+    #[cfg(feature = "spillover")]
+    spill_threshold: usize,
+    codec: C,
+    /// total time spent serializing queued messages
+    #[cfg(feature = "serialize-timing")]
+    serialize_nanos: u64,
+    flush_interval: Option<tokio::time::Interval>,
     _compiler_trickery: PhantomData<(H, M)>,
 }
 
-impl<H, M, O> Writer<H, M, O>
+impl<H, M, C> Writer<H, M, C>
 where
     H: crate::header::IsHeader,
     M: Serialize + DeserializeOwned,
-    O: bincode::Options + Clone,
+    C: crate::codec::Codec + Clone,
 {
     /// Creates a new [`Writer`]
-    pub fn new(socket: OwnedWriteHalf, seri_opt: O) -> Self {
+    pub fn new(socket: OwnedWriteHalf, codec: C) -> Self {
         Self {
             socket,
             send_buffers: VecDeque::new(),
-            serialization_options: seri_opt,
+            queued_bytes: 0,
+            max_queued_bytes: None,
+            quiescing: false,
+            bytes_written: AtomicU64::new(0),
+            messages_sent: AtomicU64::new(0),
+            sequenced: H::blank().sequence().is_some(),
+            next_seq: 0,
+            queued_at: VecDeque::new(),
+            queue_wait: crate::stats::Histogram::new(),
+            front_partial: false,
+            #[cfg(feature = "spillover")]
+            spill: None,
+            #[cfg(feature = "spillover")]
+            spill_threshold: 0,
+            codec,
+            #[cfg(feature = "serialize-timing")]
+            serialize_nanos: 0,
+            flush_interval: None,
             _compiler_trickery: PhantomData,
         }
     }
 
+    /// Creates a new [`Writer`] that holds at most `max_queued_bytes` of unwritten data.
+    ///
+    /// once the cap is reached the `queue` methods fail with [`QueueError::Full`] (without queueing anything)
+    /// untill enough has been written, so a slow peer causes backpressure instead of unbounded memory use.
+    /// use [`queue_async`] to wait for space instead.
+    ///
+    /// a message bigger than the cap is still accepted when nothing else is queued,
+    /// as it could never be sent otherwise
+    ///
+    /// [`QueueError::Full`]: error::QueueError::Full
+    /// [`queue_async`]: Writer::queue_async
+    pub fn with_capacity(socket: OwnedWriteHalf, codec: C, max_queued_bytes: usize) -> Self {
+        Self {
+            max_queued_bytes: Some(max_queued_bytes),
+            ..Self::new(socket, codec)
+        }
+    }
+
+    /// Sets the max number of bytes that can be queued, or `None` for no limit.
+    /// see [`with_capacity`] for more info
+    ///
+    /// lowering it does not remove anything that is already queued
+    ///
+    /// [`with_capacity`]: Writer::with_capacity
+    pub fn set_max_queued_bytes(&mut self, max_queued_bytes: Option<usize>) {
+        self.max_queued_bytes = max_queued_bytes;
+    }
+
+    pub fn max_queued_bytes(&self) -> Option<usize> {
+        self.max_queued_bytes
+    }
+
+    /// Stops accepting new messages, while still writing the ones that are already queued.
+    ///
+    /// this is for draining a connection, after this the `queue` methods fail with [`QueueError::Quiescing`],
+    /// but [`write`] and [`flush_all`] keep sending what was queued before. it can not be undone
+    ///
+    /// [`QueueError::Quiescing`]: error::QueueError::Quiescing
+    /// [`write`]: Writer::write
+    /// [`flush_all`]: Writer::flush_all
+    pub fn quiesce(&mut self) {
+        self.quiescing = true;
+    }
+
+    /// Returns if [`quiesce`] has been called
+    ///
+    /// [`quiesce`]: Writer::quiesce
+    pub fn is_quiescing(&self) -> bool {
+        self.quiescing
+    }
+
+    /// Returns if a buffer of `size` bytes can be queued without going over the cap
+    fn has_room(&self, size: usize) -> bool {
+        match self.max_queued_bytes {
+            None => true,
+            Some(capacity) => {
+                let queued = self.total_queued_bytes();
+                queued == 0 || queued + size <= capacity
+            }
+        }
+    }
+
+    /// Returns if there is no room left for anything, so [`Sink::poll_ready`] has to wait.
+    ///
+    /// like [`has_room`] a empty queue always has room, even with a capacity of 0
+    ///
+    /// [`Sink::poll_ready`]: futures_sink::Sink::poll_ready
+    /// [`has_room`]: Writer::has_room
+    fn is_full(&self) -> bool {
+        self.max_queued_bytes.is_some_and(|capacity| {
+            let queued = self.total_queued_bytes();
+            queued != 0 && queued >= capacity
+        })
+    }
+
+    /// Total size of the unwritten data, in memory and spilled over to disk
+    fn total_queued_bytes(&self) -> usize {
+        #[cfg(feature = "spillover")]
+        let spilled = self.spilled_bytes();
+        #[cfg(not(feature = "spillover"))]
+        let spilled = 0;
+        self.queued_bytes + spilled
+    }
+
+    /// Gets the total time spent serializing messages when they were queued, in nanoseconds.
+    ///
+    /// this is to separate time spent serializing from time spent writing when profiling.
+    /// only available with the `serialize-timing` feature, so it has no overhead otherwise
+    #[cfg(feature = "serialize-timing")]
+    pub fn serialize_nanos(&self) -> u64 {
+        self.serialize_nanos
+    }
+
+    #[cfg(feature = "serialize-timing")]
+    fn record_serialize_time(&mut self, start: std::time::Instant) {
+        let elapsed = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        self.serialize_nanos = self.serialize_nanos.saturating_add(elapsed);
+    }
+
     /// Queues a message to be sent
     ///
     /// # Errors
-    /// if the mesage could not be serialized
+    /// if the mesage could not be serialized, or the queue is full (see [`with_capacity`])
+    ///
+    /// [`with_capacity`]: Writer::with_capacity
     pub fn queue(
         &mut self,
         message: &crate::msg::MessageWrapper<M, H>,
-    ) -> Result<(), error::SeriError> {
-        let bytes = message.serialize(self.serialization_options.clone())?;
-        self.send_buffers.push_back(bytes);
+    ) -> Result<(), error::QueueError> {
+        let bytes = self.serialize(message)?;
+        self.push_buffer(bytes)
+    }
+
+    /// Queues a message to be sent, first writing queued data untill there is room for it
+    /// if the queue is full (see [`with_capacity`]). without a capacity this is the same as [`queue`]
+    ///
+    /// ## Cancelation Saftey
+    /// this method IS cancelation safe, if it is canceled the message is not queued,
+    /// and data that was not yet written stays queued
+    ///
+    /// # Errors
+    /// if the mesage could not be serialized, or writing failed
+    ///
+    /// [`with_capacity`]: Writer::with_capacity
+    /// [`queue`]: Writer::queue
+    pub async fn queue_async(
+        &mut self,
+        message: &crate::msg::MessageWrapper<M, H>,
+    ) -> Result<(), error::SinkError> {
+        let bytes = self.serialize(message).map_err(error::QueueError::from)?;
+        while !self.has_room(bytes.len()) {
+            self.write().await?;
+        }
+        Ok(self.push_buffer(bytes)?)
+    }
+
+    fn serialize(
+        &mut self,
+        message: &crate::msg::MessageWrapper<M, H>,
+    ) -> Result<Bytes, error::SeriError> {
+        #[cfg(feature = "serialize-timing")]
+        let start = std::time::Instant::now();
+        let bytes = message.serialize(&self.codec)?;
+        #[cfg(feature = "serialize-timing")]
+        self.record_serialize_time(start);
+        Ok(bytes)
+    }
+
+    /// Queues already framed bytes to be sent, for example the output of [`frame`].
+    ///
+    /// `bytes` is sent as-is, so it must contain complete frames (header and body)
+    /// or the stream will be corrupted
+    ///
+    /// # Errors
+    /// if the queue is full (see [`with_capacity`]),
+    /// or spilling over to disk is enabled and writing to disk failed
+    ///
+    /// [`frame`]: crate::msg::frame
+    /// [`with_capacity`]: Writer::with_capacity
+    pub fn queue_raw(&mut self, bytes: Bytes) -> Result<(), error::QueueError> {
+        self.push_buffer(bytes)
+    }
+
+    /// Queues a message with a fixed serialized size to be sent,
+    /// without computing its size to build the header.
+    ///
+    /// see [`FixedSizeMessage`] for more info
+    ///
+    /// # Errors
+    /// if the mesage could not be serialized, or the queue is full (see [`with_capacity`])
+    ///
+    /// [`FixedSizeMessage`]: crate::msg::FixedSizeMessage
+    /// [`with_capacity`]: Writer::with_capacity
+    pub fn queue_fixed(
+        &mut self,
+        message: &crate::msg::MessageWrapper<M, H>,
+    ) -> Result<(), error::QueueError>
+    where
+        M: crate::msg::FixedSizeMessage,
+    {
+        #[cfg(feature = "serialize-timing")]
+        let start = std::time::Instant::now();
+        let bytes = message
+            .serialize_fixed(&self.codec)
+            .map_err(error::SeriError::from)?;
+        #[cfg(feature = "serialize-timing")]
+        self.record_serialize_time(start);
+        self.push_buffer(bytes)
+    }
+
+    /// Queues a group of messages to be sent as a single unit.
+    ///
+    /// the messages are serialized into one contiguous buffer, so they are sent in order
+    /// and are never reordered or partially dropped relative to each other by the queue
+    /// (the OS may still split the data up when sending it, but framing stays intact)
+    ///
+    /// # Errors
+    /// if any of the messages could not be serialized, or the group does not fit in the queue
+    /// (see [`with_capacity`]), in which case none of them are queued
+    ///
+    /// [`with_capacity`]: Writer::with_capacity
+    pub fn queue_group(
+        &mut self,
+        messages: &[crate::msg::MessageWrapper<M, H>],
+    ) -> Result<(), error::QueueError> {
+        #[cfg(feature = "serialize-timing")]
+        let start = std::time::Instant::now();
+        let mut group = BytesMut::new();
+        for message in messages {
+            crate::msg::frame_into::<M, H>(
+                &mut group,
+                message.message(),
+                message.extension_bytes(),
+                message.kind(),
+                &self.codec,
+            )
+            .map_err(error::SeriError::from)?;
+        }
+        #[cfg(feature = "serialize-timing")]
+        self.record_serialize_time(start);
+        self.push_buffer(group.freeze())
+    }
+
+    /// Adds serialized data to the back of the queue, if there is room for it
+    fn push_buffer(&mut self, bytes: Bytes) -> Result<(), error::QueueError> {
+        if self.quiescing {
+            return Err(error::QueueError::Quiescing);
+        }
+        if !self.has_room(bytes.len()) {
+            return Err(error::QueueError::Full {
+                queued: self.total_queued_bytes(),
+                // has_room is always true without a capacity
+                capacity: self.max_queued_bytes.unwrap_or(usize::MAX),
+                size: bytes.len(),
+            });
+        }
+        self.push_unbounded(bytes)?;
+        Ok(())
+    }
+
+    /// Adds serialized data to the back of the queue, ignoring the capacity
+    fn push_unbounded(&mut self, bytes: Bytes) -> Result<(), error::SeriError> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        let queued = self.stamp_sequences(bytes)?;
+        #[cfg(feature = "spillover")]
+        if let Some(spill) = &mut self.spill {
+            // once something has spilled over everything after it has to as well, to keep the order
+            if !spill.is_empty() || self.queued_bytes + queued.remaining() > self.spill_threshold {
+                spill
+                    .push(&queued.into_bytes())
+                    .map_err(|e| error::SeriError::from(crate::codec::error::CodecError::from(e)))?;
+                self.queued_at.push_back(tokio::time::Instant::now());
+                return Ok(());
+            }
+        }
+        self.queued_at.push_back(tokio::time::Instant::now());
+        self.queued_bytes += queued.remaining();
+        self.send_buffers.push_back(queued);
+        Ok(())
+    }
+
+    /// Gives every frame in `bytes` the next sequence number, if the header type has them.
+    ///
+    /// this is done as late as possible (once the data is definitely going to be queued),
+    /// so messages that fail to queue never use up a number. already framed data (from [`queue_raw`])
+    /// is renumbered too, so a buffer broadcast to many connections gets the right numbers on each.
+    ///
+    /// `bytes` may be shared (for example by a broadcast), so it is not changed: each header is replaced
+    /// by a new one, and the bodies are queued as slices of `bytes` without copying them
+    ///
+    /// [`queue_raw`]: Writer::queue_raw
+    fn stamp_sequences(&mut self, bytes: Bytes) -> Result<Queued, error::SeriError> {
+        if !self.sequenced {
+            return Ok(Queued::from(bytes));
+        }
+        let header_size = H::header_size();
+        let mut queued = Queued::default();
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let header_end = offset + header_size;
+            if header_end > bytes.len() {
+                return Err(crate::codec::error::CodecError::new("queued data ends part way through a header").into());
+            }
+            let raw_header = bytes.slice(offset..header_end);
+            let mut header = H::from_bytes(raw_header.clone())
+                .map_err(|e| crate::codec::error::CodecError::new(e.to_string()))?;
+            let body_end = usize::try_from(header.size())
+                .ok()
+                .and_then(|size| header_end.checked_add(size))
+                .map_or(bytes.len(), |end| end.min(bytes.len()));
+            // heartbeats are not numbered, as they do not go through here
+            if header.is_heartbeat() {
+                queued.push(raw_header);
+            } else {
+                header.set_sequence(self.next_seq);
+                queued.push(header.as_bytes());
+                self.next_seq = self.next_seq.wrapping_add(1);
+            }
+            queued.push(bytes.slice(header_end..body_end));
+            offset = body_end;
+        }
+        Ok(queued)
+    }
+
+    /// Queues a heartbeat (see [`IsHeader::is_heartbeat`]), if nothing else is queued.
+    ///
+    /// a heartbeat queued behind other data would not be sent any sooner than that data,
+    /// which shows the connection is alive just as well. heartbeats are queued even when the writer
+    /// is full or quiescing, as they are tiny
+    ///
+    /// # Returns
+    /// if a heartbeat was queued. nothing is queued if `H` can not mark heartbeats (see [`IsHeader::heartbeat`])
+    ///
+    /// [`IsHeader::is_heartbeat`]: crate::header::IsHeader::is_heartbeat
+    /// [`IsHeader::heartbeat`]: crate::header::IsHeader::heartbeat
+    pub fn queue_heartbeat(&mut self) -> bool {
+        if !self.is_queue_empty() {
+            return false;
+        }
+        let Some(header) = H::heartbeat() else {
+            return false;
+        };
+        // pushed directly, the queue is empty so it can not end up ahead of spilled data
+        let bytes = header.as_bytes();
+        self.queued_at.push_back(tokio::time::Instant::now());
+        self.queued_bytes += bytes.len();
+        self.send_buffers.push_back(Queued::from(bytes));
+        true
+    }
+
+    /// Gets the number of queued messages that have not been fully written yet
+    /// (a group queued with [`queue_group`] counts as one)
+    ///
+    /// [`queue_group`]: Writer::queue_group
+    pub fn queued_messages(&self) -> usize {
+        #[cfg(feature = "spillover")]
+        let spilled = self.spilled_messages();
+        #[cfg(not(feature = "spillover"))]
+        let spilled = 0;
+        self.send_buffers.len() + spilled
+    }
+
+    /// Gets the total number of bytes that have been written to the socket (including headers)
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written.load(Ordering::Relaxed)
+    }
+
+    /// Gets the total number of messages that have been fully written
+    /// (a group queued with [`queue_group`] counts as one, and heartbeats are included)
+    ///
+    /// [`queue_group`]: Writer::queue_group
+    pub fn messages_sent(&self) -> u64 {
+        self.messages_sent.load(Ordering::Relaxed)
+    }
+
+    /// Gets the sequence number the next queued message will be given,
+    /// if the header type has sequence numbers (see [`IsHeader::sequence`])
+    ///
+    /// [`IsHeader::sequence`]: crate::header::IsHeader::sequence
+    pub fn send_sequence(&self) -> Option<u64> {
+        self.sequenced.then_some(self.next_seq)
+    }
+
+    /// Gets a histogram of how long messages were queued for, from being queued untill they were fully written
+    /// (a group queued with [`queue_group`] counts as one message)
+    ///
+    /// this tells apart latency from queueing and latency from the network
+    ///
+    /// [`queue_group`]: Writer::queue_group
+    pub fn queue_wait_histogram(&self) -> &crate::stats::Histogram {
+        &self.queue_wait
+    }
+
+    /// Gets a summary of what is queued, without changing the queue.
+    ///
+    /// this is cheap, so it can be polled for monitoring
+    pub fn queue_snapshot(&self) -> QueueSnapshot {
+        QueueSnapshot {
+            message_count: self.queued_messages(),
+            total_bytes: self.total_queued_bytes(),
+            oldest_enqueue_age: self.queued_at.front().map(tokio::time::Instant::elapsed),
+        }
+    }
+
+    /// Returns if there is nothing queued to be written
+    fn is_queue_empty(&self) -> bool {
+        self.queued_messages() == 0
+    }
+
+    /// Enables spilling queued messages over to a temporary file on disk.
+    ///
+    /// when more than `threshold` bytes are queued in memory, messages queued after that are
+    /// written to the file, and read back once the messages in memory have been sent.
+    /// this bounds memory use when the peer is slow, at the cost of disk io
+    /// (which is blocking, and done in `queue` and `write`).
+    ///
+    /// only available with the `spillover` feature
+    ///
+    /// # Errors
+    /// if the temporary file could not be created
+    #[cfg(feature = "spillover")]
+    pub fn enable_spillover(&mut self, threshold: usize) -> std::io::Result<()> {
+        if self.spill.is_none() {
+            self.spill = Some(super::spill::SpillQueue::new()?);
+        }
+        self.spill_threshold = threshold;
+        Ok(())
+    }
+
+    /// Gets the number of messages (or groups of messages) that have been spilled over to disk
+    ///
+    /// only available with the `spillover` feature
+    #[cfg(feature = "spillover")]
+    pub fn spilled_messages(&self) -> usize {
+        self.spill.as_ref().map_or(0, super::spill::SpillQueue::len)
+    }
+
+    /// Gets the total size of the messages that have been spilled over to disk
+    ///
+    /// only available with the `spillover` feature
+    #[cfg(feature = "spillover")]
+    pub fn spilled_bytes(&self) -> usize {
+        self.spill.as_ref().map_or(0, super::spill::SpillQueue::bytes)
+    }
+
+    /// Moves spilled over messages back into memory, up to the threshold (and at least one message)
+    #[cfg(feature = "spillover")]
+    fn refill_from_spill(&mut self) -> std::io::Result<()> {
+        if let Some(spill) = &mut self.spill {
+            while self.send_buffers.is_empty() || self.queued_bytes < self.spill_threshold {
+                match spill.pop()? {
+                    Some(bytes) => {
+                        self.queued_bytes += bytes.len();
+                        self.send_buffers.push_back(Queued::from(bytes));
+                    }
+                    None => break,
+                }
+            }
+        }
         Ok(())
     }
 
     /// Writes stored data to the socket
     ///
+    /// if nothing is queued this returns imediately, without waiting on the socket
+    ///
     /// # Errors
     /// If the socket has closed (returns Ok(0)) or if there was a error writing to the socket.
     pub async fn write(&mut self) -> Result<(), error::WriteError> {
+        std::future::poll_fn(|cx| self.poll_write(cx)).await
+    }
+
+    /// Polling version of [`write`]
+    ///
+    /// [`write`]: Writer::write
+    fn poll_write(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), error::WriteError>> {
+        #[cfg(feature = "spillover")]
         if self.send_buffers.is_empty() {
-            Ok(())
+            self.refill_from_spill()?;
+        }
+        if self.send_buffers.is_empty() {
+            std::task::Poll::Ready(Ok(()))
         } else {
             // this is not undefined behavior because of the prev check to is_empty()
             let latest_buf = unsafe { self.send_buffers.get_mut(0).unwrap_unchecked() };
-            match self.socket.write_buf(latest_buf).await {
-                Ok(0) => {
-                    if latest_buf.remaining() == 0 {
+            // `poll_write_buf` advances the buffers cursor past what was written,
+            // so a partialy written buffer continues where it left off on the next call.
+            // buffers queued in the mean time are behind it, so nothing is reordered
+            let written = std::task::ready!(tokio_util::io::poll_write_buf(
+                std::pin::Pin::new(&mut self.socket),
+                cx,
+                latest_buf,
+            ));
+            std::task::Poll::Ready(match written {
+                Ok(0) if latest_buf.has_remaining() => Err(error::WriteError::Disconnected),
+                Ok(n) => {
+                    self.queued_bytes -= n;
+                    self.bytes_written.fetch_add(n as u64, Ordering::Relaxed);
+                    // remove the buffer as soon as it has been fully written,
+                    // instead of waiting for a empty write on the next call
+                    self.front_partial = latest_buf.has_remaining();
+                    if !latest_buf.has_remaining() {
                         self.send_buffers.pop_front();
-                        Ok(())
-                    } else {
-                        Err(error::WriteError::Disconnected)
+                        self.messages_sent.fetch_add(1, Ordering::Relaxed);
+                        if let Some(queued_at) = self.queued_at.pop_front() {
+                            self.queue_wait.record(queued_at.elapsed());
+                        }
                     }
+                    Ok(())
                 }
-                Ok(_n) => Ok(()),
                 Err(e) => Err(e.into()),
+            })
+        }
+    }
+
+    /// Writes everything that is queued, waiting for the socket to accept more data as needed.
+    ///
+    /// unlike [`write`], which does a single write of (part of) the first queued message,
+    /// this keeps writing untill the queue is empty
+    ///
+    /// ## Cancelation Saftey
+    /// this method IS cancelation safe, if it is canceled the data that was not yet written stays queued,
+    /// and a partially written message continues where it left off on the next write
+    ///
+    /// # Returns
+    /// how many messages (a group queued with [`queue_group`] counts as one) were fully written
+    ///
+    /// # Errors
+    /// see [`write`]
+    ///
+    /// [`write`]: Writer::write
+    /// [`queue_group`]: Writer::queue_group
+    pub async fn flush_all(&mut self) -> Result<usize, error::WriteError> {
+        let mut written = 0;
+        while !self.is_queue_empty() {
+            let before = self.queued_messages();
+            self.write().await?;
+            written += before - self.queued_messages();
+        }
+        Ok(written)
+    }
+
+    /// Writes everything that is queued, then shuts down the write half of the socket,
+    /// so the peer sees the end of the stream after the last message.
+    ///
+    /// # Errors
+    /// if writing or shutting down the socket failed
+    pub async fn close_after_flush(&mut self) -> Result<(), error::WriteError> {
+        self.flush_all().await?;
+        self.socket.shutdown().await?;
+        Ok(())
+    }
+
+    /// Writes stored data to the socket, retrying transient failures.
+    ///
+    /// if [`write`] fails with a transient error (see [`WriteError::is_transient`]),
+    /// it is retried up to `max_retries` times, waiting `backoff` before the first retry
+    /// and doubling the wait after each one.
+    ///
+    /// # Errors
+    /// if a non-transient error occurs, or the write still fails after `max_retries` retries
+    ///
+    /// [`write`]: Writer::write
+    /// [`WriteError::is_transient`]: error::WriteError::is_transient
+    pub async fn write_with_retry(
+        &mut self,
+        max_retries: usize,
+        backoff: std::time::Duration,
+    ) -> Result<(), error::WriteError> {
+        retry_transient(self, max_retries, backoff).await
+    }
+
+    /// Sets the interval queued messages are automatically flushed on, or `None` to disable it.
+    ///
+    /// this only has an effect when [`flush_tick`] is being awaited, for example in a `select!` in
+    /// a event loop, or when the writer is moved into a task with [`spawn_flusher`]
+    ///
+    /// # Panics
+    /// if `interval` is zero, or this is called outside of a tokio runtime
+    ///
+    /// [`flush_tick`]: Writer::flush_tick
+    /// [`spawn_flusher`]: Writer::spawn_flusher
+    pub fn set_flush_interval(&mut self, interval: Option<std::time::Duration>) {
+        self.flush_interval = interval.map(|interval| {
+            let mut interval = tokio::time::interval(interval);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            interval
+        });
+    }
+
+    /// Copies the settings of `old` over to this writer, for when it replaces a lost connection.
+    /// queued data and counters are not carried over
+    ///
+    /// # Errors
+    /// if `old` spills over to disk, and the file for this writer could not be created
+    pub(crate) fn take_settings(&mut self, old: &Self) -> std::io::Result<()> {
+        self.max_queued_bytes = old.max_queued_bytes;
+        self.set_flush_interval(old.flush_interval());
+        #[cfg(feature = "spillover")]
+        if old.spill.is_some() {
+            self.enable_spillover(old.spill_threshold)?;
+        }
+        Ok(())
+    }
+
+    /// Gets the interval queued messages are automatically flushed on
+    pub fn flush_interval(&self) -> Option<std::time::Duration> {
+        self.flush_interval.as_ref().map(tokio::time::Interval::period)
+    }
+
+    /// Waits for the next tick of the flush interval, then writes everything that is queued.
+    ///
+    /// if no flush interval is set this never completes.
+    ///
+    /// ## Cancelation Saftey
+    /// this method IS cancelation safe, if it is canceled while writing
+    /// the data that was not yet written stays queued
+    ///
+    /// # Errors
+    /// see [`write`]
+    ///
+    /// [`write`]: Writer::write
+    pub async fn flush_tick(&mut self) -> Result<(), error::WriteError> {
+        match &mut self.flush_interval {
+            Some(interval) => {
+                interval.tick().await;
             }
+            None => std::future::pending().await,
         }
+        self.flush_all().await?;
+        Ok(())
+    }
+
+    /// Moves the writer into a new task, that writes queued messages every flush interval.
+    ///
+    /// the flush interval set with [`set_flush_interval`] is moved into the task,
+    /// see [`BackgroundWriter`] for more info
+    ///
+    /// # Panics
+    /// if no flush interval is set, or this is called outside of a tokio runtime
+    ///
+    /// [`set_flush_interval`]: Writer::set_flush_interval
+    pub fn spawn_flusher(mut self) -> BackgroundWriter<H, M, C>
+    where
+        H: Send + 'static,
+        M: Send + 'static,
+        C: Send + 'static,
+    {
+        let mut interval = self
+            .flush_interval
+            .take()
+            .expect("A flush interval must be set to spawn a flusher");
+        let writer = std::sync::Arc::new(tokio::sync::Mutex::new(self));
+        let task_writer = writer.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                // only lock once it is time to flush, so queueing does not have to wait for the tick
+                interval.tick().await;
+                if let Err(e) = task_writer.lock().await.flush_all().await {
+                    return e;
+                }
+            }
+        });
+        BackgroundWriter { writer, task }
+    }
+
+    /// Removes everything that is queued, returning the messages that have not been written at all.
+    ///
+    /// this is for moving unsent messages to a new connection. a message that was partially written
+    /// can not be resent without corrupting the new stream, so it is dropped
+    ///
+    /// # Returns
+    /// the unsent messages (as framed bytes, ready for [`queue_raw`]), and if a partially written message was dropped.
+    /// messages that had their sequence numbers stamped are joined back into one buffer each, which copies them
+    ///
+    /// # Errors
+    /// only if spilling over to disk is enabled, and reading spilled messages failed.
+    /// nothing is removed from the queue in that case
+    ///
+    /// [`queue_raw`]: Writer::queue_raw
+    pub fn take_unsent(&mut self) -> std::io::Result<(Vec<Bytes>, bool)> {
+        #[cfg(feature = "spillover")]
+        let spilled = match &mut self.spill {
+            Some(spill) => spill.take_all()?,
+            None => Vec::new(),
+        };
+        let dropped_partial = self.front_partial;
+        if dropped_partial {
+            self.send_buffers.pop_front();
+        }
+        self.front_partial = false;
+        #[allow(unused_mut)]
+        let mut unsent: Vec<Bytes> = self.send_buffers.drain(..).map(Queued::into_bytes).collect();
+        #[cfg(feature = "spillover")]
+        unsent.extend(spilled);
+        self.queued_bytes = 0;
+        self.queued_at.clear();
+        Ok((unsent, dropped_partial))
+    }
+
+    /// Replaces the socket being written to, keeping all queued data.
+    ///
+    /// this is for moving a connection to a new socket (for example after a network change)
+    /// without losing queued messages. it should be done at a frame boundary, if a message was
+    /// partially written to the old socket the rest of it is written to the new one
+    ///
+    /// # Returns
+    /// the old socket
+    pub fn replace_socket(&mut self, socket: OwnedWriteHalf) -> OwnedWriteHalf {
+        std::mem::replace(&mut self.socket, socket)
     }
 
     pub fn as_socket(&self) -> &OwnedWriteHalf {
@@ -91,7 +950,300 @@ where
         &mut self.socket
     }
 
+    /// Gives up the ability to join this back into a `TcpStream`, see [`Detached`] for more info
+    ///
+    /// [`Detached`]: super::Detached
+    pub fn detach(self) -> super::Detached<Self> {
+        super::Detached::new(self)
+    }
+
     pub fn into_socket(self) -> OwnedWriteHalf {
         self.socket
     }
 }
+
+/// Something that can be written to, so retrying can be tested without a socket
+trait TryWrite {
+    async fn try_write(&mut self) -> Result<(), error::WriteError>;
+}
+
+impl<H, M, C> TryWrite for Writer<H, M, C>
+where
+    H: crate::header::IsHeader,
+    M: Serialize + DeserializeOwned,
+    C: crate::codec::Codec + Clone,
+{
+    async fn try_write(&mut self) -> Result<(), error::WriteError> {
+        self.write().await
+    }
+}
+
+/// see [`Writer::write_with_retry`]
+async fn retry_transient<W: TryWrite>(
+    target: &mut W,
+    max_retries: usize,
+    backoff: std::time::Duration,
+) -> Result<(), error::WriteError> {
+    let mut delay = backoff;
+    let mut retries = 0;
+    loop {
+        match target.try_write().await {
+            Err(e) if e.is_transient() && retries < max_retries => {
+                retries += 1;
+                tokio::time::sleep(delay).await;
+                delay = delay.saturating_mul(2);
+            }
+            res => return res,
+        }
+    }
+}
+
+// nothing in the writer is pinned, `H` and `M` are only markers
+impl<H, M, C> Unpin for Writer<H, M, C> where C: crate::codec::Codec + Clone {}
+
+/// Sends messages by queueing them, flushing writes everything that is queued.
+/// closing flushes, then shuts down the write half of the socket
+impl<H, M, C> futures_sink::Sink<crate::msg::MessageWrapper<M, H>> for Writer<H, M, C>
+where
+    H: crate::header::IsHeader,
+    M: Serialize + DeserializeOwned,
+    C: crate::codec::Codec + Clone,
+{
+    type Error = error::SinkError;
+
+    fn poll_ready(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        // ready as soon as there is any room, the next message may go over the cap a bit
+        let this = self.get_mut();
+        if this.quiescing {
+            return std::task::Poll::Ready(Err(error::QueueError::Quiescing.into()));
+        }
+        while this.is_full() {
+            std::task::ready!(this.poll_write(cx))?;
+        }
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn start_send(
+        self: std::pin::Pin<&mut Self>,
+        item: crate::msg::MessageWrapper<M, H>,
+    ) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        if this.quiescing {
+            return Err(error::QueueError::Quiescing.into());
+        }
+        let bytes = this.serialize(&item).map_err(error::QueueError::from)?;
+        // `poll_ready` already waited for room
+        this.push_unbounded(bytes).map_err(error::QueueError::from)?;
+        Ok(())
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        while !this.is_queue_empty() {
+            std::task::ready!(this.poll_write(cx))?;
+        }
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        std::task::ready!(self.as_mut().poll_flush(cx))?;
+        let socket = std::pin::Pin::new(&mut self.get_mut().socket);
+        tokio::io::AsyncWrite::poll_shutdown(socket, cx)
+            .map_err(|e| error::SinkError::Write(e.into()))
+    }
+}
+
+/// A [`Writer`] running in its own task, that writes queued messages every flush interval.
+///
+/// produced by [`Writer::spawn_flusher`].
+/// the task has the writer locked while it is writing, so queueing may wait for
+/// a flush to finish. the task stops when writing fails, or this is dropped.
+#[derive(Debug)]
+pub struct BackgroundWriter<H, M, C>
+where
+    C: crate::codec::Codec + Clone,
+{
+    writer: std::sync::Arc<tokio::sync::Mutex<Writer<H, M, C>>>,
+    task: tokio::task::JoinHandle<error::WriteError>,
+}
+
+impl<H, M, C> BackgroundWriter<H, M, C>
+where
+    H: crate::header::IsHeader,
+    M: Serialize + DeserializeOwned,
+    C: crate::codec::Codec + Clone,
+{
+    /// Queues a message to be sent on the next flush
+    ///
+    /// # Errors
+    /// if the mesage could not be serialized, or the queue is full
+    pub async fn queue(
+        &self,
+        message: &crate::msg::MessageWrapper<M, H>,
+    ) -> Result<(), error::QueueError> {
+        self.writer.lock().await.queue(message)
+    }
+
+    /// Locks the writer, giving full access to it
+    pub async fn lock(&self) -> tokio::sync::MutexGuard<'_, Writer<H, M, C>> {
+        self.writer.lock().await
+    }
+
+    /// Returns if the flushing task has stopped because writing failed
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+}
+
+impl<H, M, C> Drop for BackgroundWriter<H, M, C>
+where
+    C: crate::codec::Codec + Clone,
+{
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, io, time::Duration};
+
+    use bytes::{Buf, Bytes};
+
+    use super::{error::WriteError, retry_transient, Queued, TryWrite};
+
+    /// Returns the scripted results in order, like writing to a socket would
+    struct MockWriter {
+        results: VecDeque<Result<(), WriteError>>,
+        attempts: usize,
+    }
+
+    impl MockWriter {
+        fn new(results: impl IntoIterator<Item = Result<(), WriteError>>) -> Self {
+            Self {
+                results: results.into_iter().collect(),
+                attempts: 0,
+            }
+        }
+    }
+
+    impl TryWrite for MockWriter {
+        async fn try_write(&mut self) -> Result<(), WriteError> {
+            self.attempts += 1;
+            self.results.pop_front().expect("written too many times")
+        }
+    }
+
+    fn transient() -> Result<(), WriteError> {
+        Err(WriteError::IOError(io::ErrorKind::WouldBlock.into()))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn transient_write_errors_are_retried_with_backoff() {
+        let mut mock = MockWriter::new([transient(), transient(), Ok(())]);
+        let start = tokio::time::Instant::now();
+        retry_transient(&mut mock, 3, Duration::from_millis(10)).await.unwrap();
+        assert_eq!(mock.attempts, 3);
+        // waited 10ms, then 20ms
+        assert_eq!(start.elapsed(), Duration::from_millis(30));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_give_up() {
+        let mut mock = MockWriter::new([transient(), transient(), Ok(())]);
+        let err = retry_transient(&mut mock, 1, Duration::from_millis(10)).await.unwrap_err();
+        assert!(err.is_transient());
+        assert_eq!(mock.attempts, 2);
+
+        // fatal errors are not retried at all
+        let mut mock = MockWriter::new([Err(WriteError::Disconnected), Ok(())]);
+        let err = retry_transient(&mut mock, 5, Duration::from_millis(10)).await.unwrap_err();
+        assert!(matches!(err, WriteError::Disconnected));
+        assert_eq!(mock.attempts, 1);
+    }
+
+    #[test]
+    fn queued_pieces_are_read_in_order() {
+        let shared = Bytes::from_static(b"headerbody");
+        let mut queued = Queued::default();
+        queued.push(Bytes::from_static(b"HEADER"));
+        queued.push(Bytes::new());
+        queued.push(shared.slice(6..));
+        assert_eq!(queued.remaining(), 10);
+
+        let mut slices = [std::io::IoSlice::new(&[]); 4];
+        assert_eq!(queued.chunks_vectored(&mut slices), 2);
+
+        // a partial write that ends part way through the second piece
+        queued.advance(8);
+        assert_eq!(queued.remaining(), 2);
+        assert_eq!(queued.chunk(), b"dy");
+        // the body was never copied
+        assert_eq!(queued.chunk().as_ptr(), shared[8..].as_ptr());
+        assert_eq!(queued.into_bytes(), Bytes::from_static(b"dy"));
+    }
+
+    #[test]
+    fn into_bytes_joins_pieces() {
+        let mut queued = Queued::from(Bytes::from_static(b"ab"));
+        queued.push(Bytes::from_static(b"cd"));
+        assert_eq!(queued.into_bytes(), Bytes::from_static(b"abcd"));
+        assert_eq!(Queued::default().into_bytes(), Bytes::new());
+    }
+
+    #[cfg(feature = "spillover")]
+    #[tokio::test]
+    async fn failing_to_read_spilled_messages_keeps_the_queue() {
+        use std::io::{Read, Seek, SeekFrom, Write};
+
+        use crate::codec::{Codec, DefaultCodec};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let stream = tokio::net::TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (_read_half, write_half) = stream.into_split();
+        let mut writer =
+            super::Writer::<crate::header::U64Header, u32, _>::new(write_half, DefaultCodec::default());
+        writer.enable_spillover(0).unwrap();
+        for n in 0..4 {
+            writer.queue(&crate::msg::MessageWrapper::new(n)).unwrap();
+        }
+        let spilled = writer.spilled_messages();
+        assert!(spilled > 1);
+
+        // cut the last spilled message short, so reading it back fails
+        let file = writer.spill.as_mut().unwrap().file_mut();
+        let mut contents = Vec::new();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.read_to_end(&mut contents).unwrap();
+        file.set_len(contents.len() as u64 - 1).unwrap();
+
+        assert!(writer.take_unsent().is_err());
+        assert_eq!(writer.queued_messages(), 4);
+        assert_eq!(writer.spilled_messages(), spilled);
+
+        // once it can be read again, nothing is missing
+        let file = writer.spill.as_mut().unwrap().file_mut();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.write_all(&contents).unwrap();
+        let (unsent, dropped_partial) = writer.take_unsent().unwrap();
+        assert!(!dropped_partial);
+        let unsent = unsent
+            .iter()
+            .map(|bytes| DefaultCodec::default().deserialize::<u32>(&bytes[8..]).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(unsent, [0, 1, 2, 3]);
+        assert_eq!(writer.queued_messages(), 0);
+        assert_eq!(writer.spilled_messages(), 0);
+    }
+}
@@ -0,0 +1,119 @@
+use std::time::Duration;
+
+/// Number of buckets in a [`Histogram`], the last one holds everything longer than `2^(BUCKETS - 2)` microseconds
+const BUCKETS: usize = 32;
+
+/// A histogram of durations, with power-of-two sized buckets.
+///
+/// bucket `i` holds durations of less than `2^i` microseconds (and at least `2^(i-1)`),
+/// which is precise enough to tell apart microseconds, milliseconds and seconds
+/// while using a fixed amount of memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    buckets: [u64; BUCKETS],
+    count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+impl Histogram {
+    /// Creates a new, empty, histogram
+    pub fn new() -> Self {
+        Self {
+            buckets: [0; BUCKETS],
+            count: 0,
+            total: Duration::ZERO,
+            max: Duration::ZERO,
+        }
+    }
+
+    /// Adds a duration to the histogram
+    pub fn record(&mut self, duration: Duration) {
+        let micros = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        let bucket = ((u64::BITS - micros.leading_zeros()) as usize).min(BUCKETS - 1);
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.total = self.total.saturating_add(duration);
+        self.max = self.max.max(duration);
+    }
+
+    /// Gets the number of durations recorded
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Gets the longest duration recorded
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// Gets the average of the recorded durations, or `None` if nothing was recorded
+    pub fn mean(&self) -> Option<Duration> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(Duration::from_secs_f64(self.total.as_secs_f64() / self.count as f64))
+        }
+    }
+
+    /// Gets the buckets of the histogram, as the upper bound of each bucket and the number of durations in it.
+    ///
+    /// the last bucket has no upper bound, and is returned with `Duration::MAX`
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.buckets.iter().enumerate().map(|(i, &count)| {
+            let bound = if i == BUCKETS - 1 {
+                Duration::MAX
+            } else {
+                Duration::from_micros(1 << i)
+            };
+            (bound, count)
+        })
+    }
+
+    /// Removes all recorded durations
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Counts of the errors a [`Reader`] hit while decoding messages, see [`Reader::decode_errors`]
+///
+/// a rising number of these usually means the peer is using a different protocol version, or is malicious
+///
+/// [`Reader`]: crate::socket::read::Reader
+/// [`Reader::decode_errors`]: crate::socket::read::Reader::decode_errors
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeErrors {
+    /// headers that could not be parsed
+    pub header_parse_errors: u64,
+    /// message bodies that could not be deserialized
+    pub deserialize_errors: u64,
+}
+
+impl DecodeErrors {
+    /// Gets the total number of errors
+    pub fn total(&self) -> u64 {
+        self.header_parse_errors + self.deserialize_errors
+    }
+}
+
+/// Totals of what has been sent and received on a connection, see [`SocketUtils::stats`]
+///
+/// [`SocketUtils::stats`]: crate::socket::interface::_SocketUtils::stats
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectionStats {
+    /// bytes read from the socket, including headers
+    pub bytes_read: u64,
+    /// complete messages received
+    pub messages_received: u64,
+    /// bytes written to the socket, including headers
+    pub bytes_written: u64,
+    /// messages fully written (a group of messages counts as one)
+    pub messages_sent: u64,
+}
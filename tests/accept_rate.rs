@@ -0,0 +1,51 @@
+//! the accept rate limit paces new connections, leaving bursts in the OS backlog
+mod common;
+
+use std::time::Duration;
+
+use common::*;
+use smalltalk::U64Header;
+use tokio::{net::TcpStream, time::Instant};
+
+const RATE: u32 = 10;
+
+#[tokio::test(start_paused = true)]
+async fn connections_are_accepted_at_the_configured_rate() {
+    let mut server = server().await;
+    server.set_accept_rate(Some(RATE));
+    assert_eq!(server.accept_rate(), Some(RATE));
+    let addr = server.as_listener().local_addr().unwrap();
+
+    // a burst of connections, which wait in the backlog
+    let mut clients = Vec::new();
+    for _ in 0..30 {
+        clients.push(TcpStream::connect(addr).await.unwrap());
+    }
+
+    let start = Instant::now();
+    let mut accepted_at = Vec::new();
+    for _ in 0..30 {
+        server.accept::<U64Header, u32>().await.unwrap();
+        accepted_at.push(start.elapsed());
+    }
+    // the first RATE straight away, then RATE a second
+    assert!(accepted_at[..RATE as usize].iter().all(|t| t.is_zero()));
+    let last = accepted_at[29];
+    assert!(
+        last >= Duration::from_millis(1950) && last <= Duration::from_millis(2050),
+        "{last:?}"
+    );
+    for pair in accepted_at[RATE as usize..].windows(2) {
+        let gap = pair[1] - pair[0];
+        assert!(gap >= Duration::from_millis(95), "{gap:?}");
+    }
+}
+
+#[tokio::test]
+async fn a_rate_of_zero_is_raised_to_one() {
+    let mut server = server().await;
+    server.set_accept_rate(Some(0));
+    assert_eq!(server.accept_rate(), Some(1));
+    server.set_accept_rate(None);
+    assert_eq!(server.accept_rate(), None);
+}
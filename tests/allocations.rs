@@ -0,0 +1,107 @@
+//! serializing a message writes the body straight into the frame, once, without an intermediate buffer
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+use bytes::BytesMut;
+use serde::{de::DeserializeOwned, Serialize};
+use smalltalk::{
+    codec::error::CodecError, header::U32Header, msg::MessageWrapper, Codec, DefaultCodec,
+};
+
+/// Counts the allocations made on the current thread while counting is turned on
+struct Counting;
+
+thread_local! {
+    static COUNTING: Cell<bool> = const { Cell::new(false) };
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+fn count() {
+    if COUNTING.with(Cell::get) {
+        ALLOCATIONS.with(|a| a.set(a.get() + 1));
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Runs `f`, returning its output and how many allocations it made
+fn allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    ALLOCATIONS.with(|a| a.set(0));
+    COUNTING.with(|c| c.set(true));
+    let out = f();
+    COUNTING.with(|c| c.set(false));
+    (out, ALLOCATIONS.with(Cell::get))
+}
+
+#[test]
+fn serialize_matches_the_old_path_with_fewer_allocations() {
+    let codec = DefaultCodec::default();
+    for len in [0, 1, 100, 10_000] {
+        let msg = MessageWrapper::<Vec<u32>, U32Header>::new((0..len).collect());
+
+        let (new, new_allocs) = allocations(|| msg.serialize(&codec).unwrap());
+        // what serialize used to do, the body into its own buffer, then copied after the header
+        let (old, old_allocs) = allocations(|| {
+            let header = msg.header_bytes(&codec).unwrap();
+            let body = msg.serialize_self(&codec).unwrap();
+            let mut buf = BytesMut::with_capacity(header.len());
+            buf.extend_from_slice(&header);
+            buf.extend_from_slice(&body);
+            buf.freeze()
+        });
+
+        assert_eq!(new, old, "{len} items");
+        assert!(
+            new_allocs < old_allocs,
+            "{len} items: {new_allocs} vs {old_allocs}"
+        );
+    }
+}
+
+/// bincode, but only implementing what a codec has to, and counting how many times it serializes
+#[derive(Debug, Default)]
+struct CountingCodec {
+    serializes: Cell<usize>,
+}
+
+impl Codec for CountingCodec {
+    fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        self.serializes.set(self.serializes.get() + 1);
+        DefaultCodec::default().serialize(value)
+    }
+
+    fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        DefaultCodec::default().deserialize(bytes)
+    }
+}
+
+#[test]
+fn codecs_without_a_size_hint_serialize_once() {
+    let codec = CountingCodec::default();
+    let msg = MessageWrapper::<Vec<u32>, U32Header>::new((0..1000).collect());
+    for sends in 1..=3 {
+        let bytes = msg.serialize(&codec).unwrap();
+        assert_eq!(codec.serializes.get(), sends);
+        assert_eq!(bytes, msg.serialize(&DefaultCodec::default()).unwrap());
+    }
+}
@@ -0,0 +1,28 @@
+//! binding an address that is already taken is reported as such
+mod common;
+
+use common::*;
+use smalltalk::{server::error::BindServerError, DefaultCodec, Server};
+
+#[tokio::test]
+async fn binding_twice_is_address_in_use() {
+    let first = server().await;
+    let addr = first.as_listener().local_addr().unwrap();
+    let second = Server::bind(addr, DefaultCodec::default()).await;
+    assert!(matches!(
+        second,
+        Err(BindServerError::AddressInUse { addr: taken }) if taken == addr
+    ));
+
+    // once the first is gone the address can be used again
+    drop(first);
+    let third = Server::bind(addr, DefaultCodec::default()).await.unwrap();
+    assert_eq!(third.as_listener().local_addr().unwrap(), addr);
+}
+
+#[tokio::test]
+async fn other_bind_errors_are_not_address_in_use() {
+    // not a local address
+    let res = Server::bind("192.0.2.1:0", DefaultCodec::default()).await;
+    assert!(matches!(res, Err(BindServerError::ListenerBindError(_))));
+}
@@ -0,0 +1,58 @@
+//! the reader reports how much it has buffered at most, and stops reading at a cap
+mod common;
+
+use common::*;
+use smalltalk::U64Header;
+
+const CAP: usize = 1000;
+
+#[tokio::test]
+async fn reading_pauses_at_the_cap() {
+    let (mut reader, mut peer) = reader::<U64Header, Vec<u8>>().await;
+    reader.set_max_total_buffered(Some(CAP));
+    let body = vec![7u8; 100];
+    let mut bytes = Vec::new();
+    for _ in 0..30 {
+        bytes.extend_from_slice(&frame::<U64Header, _>(&body));
+    }
+    write_all(&mut peer, &bytes).await;
+
+    let mut seen = 0;
+    tokio::time::timeout(TIMEOUT, async {
+        while !reader.is_read_paused() {
+            reader.read().await.unwrap();
+            seen = seen.max(reader.buffered_bytes());
+            reader.update().await.unwrap();
+            seen = seen.max(reader.buffered_bytes());
+        }
+    })
+    .await
+    .unwrap();
+    assert!(reader.buffered_bytes() >= CAP);
+    assert_eq!(reader.peak_buffered_bytes(), seen);
+
+    // nothing more is read untill messages are retrieved
+    let buffered = reader.buffered_bytes();
+    reader.read().await.unwrap();
+    reader.update().await.unwrap();
+    assert_eq!(reader.buffered_bytes(), buffered);
+    assert!(reader.is_read_paused());
+
+    let mut received = reader.ready_messages().count();
+    assert!(received < 30);
+    assert!(!reader.is_read_paused());
+    tokio::time::timeout(TIMEOUT, async {
+        while received < 30 {
+            reader.read().await.unwrap();
+            seen = seen.max(reader.buffered_bytes());
+            reader.update().await.unwrap();
+            seen = seen.max(reader.buffered_bytes());
+            received += reader.ready_messages().count();
+        }
+    })
+    .await
+    .unwrap();
+    // the peak from before is still reported
+    assert_eq!(reader.peak_buffered_bytes(), seen);
+    assert!(reader.peak_buffered_bytes() >= CAP);
+}
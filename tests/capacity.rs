@@ -0,0 +1,60 @@
+mod common;
+
+use common::*;
+use futures::SinkExt;
+use smalltalk::{socket::write::error::QueueError, MessageWrapper, U64Header};
+
+#[tokio::test]
+async fn a_full_queue_rejects_messages_untill_it_is_written() {
+    let (mut writer, mut peer) = writer::<U64Header, Vec<u8>>().await;
+    let message = MessageWrapper::new(vec![1u8; 100]);
+    let size = message.serialize(&smalltalk::DefaultCodec::default()).unwrap().len();
+    writer.set_max_queued_bytes(Some(size * 2));
+    writer.queue(&message).unwrap();
+    writer.queue(&message).unwrap();
+    match writer.queue(&message) {
+        Err(QueueError::Full { queued, capacity, size: rejected }) => {
+            assert_eq!((queued, capacity, rejected), (size * 2, size * 2, size));
+        }
+        other => panic!("expected the queue to be full, got {other:?}"),
+    }
+    assert_eq!(writer.queued_messages(), 2);
+
+    // waits for room instead of failing
+    writer.queue_async(&message).await.unwrap();
+    writer.flush_all().await.unwrap();
+    let mut received = 0;
+    while received < 3 {
+        let frame = read_exactly(&mut peer, size).await;
+        assert_eq!(frame[..], message.serialize(&smalltalk::DefaultCodec::default()).unwrap()[..]);
+        received += 1;
+    }
+}
+
+#[tokio::test]
+async fn a_message_larger_than_the_capacity_is_accepted_when_the_queue_is_empty() {
+    let (mut writer, mut peer) = writer::<U64Header, Vec<u8>>().await;
+    writer.set_max_queued_bytes(Some(10));
+    let message = MessageWrapper::new(vec![2u8; 64]);
+    writer.queue(&message).unwrap();
+    assert!(matches!(writer.queue(&message), Err(QueueError::Full { .. })));
+    writer.flush_all().await.unwrap();
+    let framed = message.serialize(&smalltalk::DefaultCodec::default()).unwrap();
+    assert_eq!(read_exactly(&mut peer, framed.len()).await, framed[..]);
+}
+
+#[tokio::test]
+async fn the_sink_does_not_spin_with_a_capacity_of_zero() {
+    let (mut writer, mut peer) = writer::<U64Header, u32>().await;
+    writer.set_max_queued_bytes(Some(0));
+    tokio::time::timeout(TIMEOUT, async {
+        writer.send(MessageWrapper::new(1)).await.unwrap();
+        writer.send(MessageWrapper::new(2)).await.unwrap();
+    })
+    .await
+    .expect("sending with a capacity of zero never finished");
+    for expected in [1u32, 2] {
+        let framed = frame::<U64Header, _>(&expected);
+        assert_eq!(read_exactly(&mut peer, framed.len()).await, framed[..]);
+    }
+}
@@ -0,0 +1,113 @@
+//! time based features can be driven by a paused tokio clock or a `ManualClock`, without waiting
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use common::*;
+use smalltalk::{
+    clock::ManualClock,
+    socket::{interface::error::UpdateError, HeartbeatConfig},
+    IsHeader, TypedHeader,
+};
+
+const HEARTBEAT: HeartbeatConfig = HeartbeatConfig {
+    interval: Duration::from_secs(5),
+    timeout: Duration::from_secs(60),
+};
+
+/// Checks that the next bytes on `stream` are a heartbeat
+async fn expect_heartbeat(stream: &mut tokio::net::TcpStream) {
+    let heartbeat = TypedHeader::heartbeat().unwrap().as_bytes();
+    assert_eq!(read_exactly(stream, heartbeat.len()).await, heartbeat);
+}
+
+#[tokio::test(start_paused = true)]
+async fn paused_clock_triggers_a_heartbeat() {
+    let (mut client, mut peer) = client_and_raw::<TypedHeader, u32>().await;
+    client.set_heartbeat(Some(HEARTBEAT)).unwrap();
+
+    client.update().await.unwrap();
+    assert_eq!(client.as_writer().messages_sent(), 0);
+
+    tokio::time::advance(HEARTBEAT.interval).await;
+    client.update().await.unwrap();
+    assert_eq!(client.as_writer().messages_sent(), 1);
+    expect_heartbeat(&mut peer).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn heartbeats_are_sent_while_waiting() {
+    let (mut client, mut peer) = client_and_raw::<TypedHeader, u32>().await;
+    client.set_heartbeat(Some(HEARTBEAT)).unwrap();
+
+    // the paused clock jumps ahead whenever the runtime is idle, so this returns right away
+    let waited = tokio::time::Instant::now();
+    let received = client
+        .wait_for_message_timeout(HEARTBEAT.interval * 3 + Duration::from_secs(1))
+        .await
+        .unwrap();
+    assert!(received.is_none());
+    assert!(waited.elapsed() >= HEARTBEAT.interval * 3);
+    for _ in 0..3 {
+        expect_heartbeat(&mut peer).await;
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn paused_clock_detects_a_dead_peer() {
+    let (mut client, _peer) = client_and_raw::<TypedHeader, u32>().await;
+    client.set_heartbeat(Some(HEARTBEAT)).unwrap();
+    tokio::time::advance(HEARTBEAT.timeout).await;
+    assert!(matches!(
+        client.update().await,
+        Err(UpdateError::Dead { idle }) if idle >= HEARTBEAT.timeout
+    ));
+}
+
+#[tokio::test]
+async fn manual_clock() {
+    let (mut client, mut peer) = client_and_raw::<TypedHeader, u32>().await;
+    let clock = ManualClock::new();
+    client.set_clock(Arc::new(clock.clone()));
+    client.set_heartbeat(Some(HEARTBEAT)).unwrap();
+    client.set_max_lifetime(Some(Duration::from_secs(30)));
+
+    clock.advance(HEARTBEAT.interval);
+    assert_eq!(client.age(), HEARTBEAT.interval);
+    assert_eq!(client.as_reader().idle_time(), HEARTBEAT.interval);
+    let status = client.update().await.unwrap();
+    assert!(!status.lifetime_exceeded());
+    expect_heartbeat(&mut peer).await;
+
+    clock.advance(Duration::from_secs(30));
+    assert!(client.lifetime_exceeded());
+    clock.advance(HEARTBEAT.timeout);
+    assert!(matches!(
+        client.update().await,
+        Err(UpdateError::Dead { .. })
+    ));
+}
+
+#[tokio::test]
+async fn manual_clock_drives_the_frame_rate_limit() {
+    let (mut reader, _peer) = reader::<TypedHeader, u32>().await;
+    let clock = ManualClock::new();
+    reader.set_clock(Arc::new(clock.clone()));
+    reader.set_max_frames_per_sec(Some(2));
+    for n in 0..5u32 {
+        reader.feed(&frame::<TypedHeader, _>(&n));
+    }
+
+    // a full bucket allows a burst of 2
+    reader.update().await.unwrap();
+    assert_eq!(reader.messages_received(), 2);
+    reader.update().await.unwrap();
+    assert_eq!(reader.messages_received(), 2);
+
+    clock.advance(Duration::from_millis(500));
+    reader.update().await.unwrap();
+    assert_eq!(reader.messages_received(), 3);
+    clock.advance(Duration::from_secs(10));
+    reader.update().await.unwrap();
+    assert_eq!(reader.messages_received(), 5);
+}
@@ -0,0 +1,54 @@
+//! a peer closing right after connecting is a clean closure, not a spin or a truncated message
+mod common;
+
+use std::io::ErrorKind;
+
+use common::*;
+use smalltalk::{
+    socket::{interface::error::WaitMessageError, read::ReadStatus},
+    U64Header,
+};
+use tokio::io::AsyncWriteExt;
+
+#[tokio::test]
+async fn server_closing_immediately_is_a_clean_closure() {
+    let (mut client, conn) = pair::<U64Header, u32>().await;
+    drop(conn);
+
+    let status = tokio::time::timeout(TIMEOUT, client.as_reader_mut().read())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(status, ReadStatus::Closed);
+    // and keeps being reported, instead of moving on to reading a header
+    let status = tokio::time::timeout(TIMEOUT, client.as_reader_mut().read())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(status, ReadStatus::Closed);
+    assert!(client.as_reader().at_frame_boundary());
+
+    let err = tokio::time::timeout(TIMEOUT, client.wait_for_message())
+        .await
+        .unwrap()
+        .unwrap_err();
+    assert!(matches!(err, WaitMessageError::Closed), "{err:?}");
+}
+
+#[tokio::test]
+async fn closing_part_way_through_a_header_is_an_error() {
+    let (mut client, mut raw) = client_and_raw::<U64Header, u32>().await;
+    raw.write_all(&[0, 0, 0]).await.unwrap();
+    drop(raw);
+
+    let err = tokio::time::timeout(TIMEOUT, async {
+        loop {
+            if let Err(e) = client.as_reader_mut().read().await {
+                break e;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+}
@@ -0,0 +1,175 @@
+//! helpers shared by the integration tests, everything runs over loopback sockets
+#![allow(dead_code)]
+
+use std::{fmt::Debug, time::Duration};
+
+use serde::{de::DeserializeOwned, Serialize};
+use smalltalk::{server::ClientConnection, Client, DefaultCodec, IsHeader, Server};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// long enough that it is only hit if something is actually broken
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub type Conn<H, M> = ClientConnection<H, M, DefaultCodec>;
+
+pub async fn server() -> Server<DefaultCodec> {
+    Server::bind("127.0.0.1:0", DefaultCodec::default())
+        .await
+        .unwrap()
+}
+
+/// A client and the servers side of its connection
+pub async fn pair<H, M>() -> (Client<H, M, DefaultCodec>, Conn<H, M>)
+where
+    H: IsHeader + Clone + Debug + Send,
+    M: Serialize + DeserializeOwned + Send,
+{
+    let mut server = server().await;
+    let addr = server.as_listener().local_addr().unwrap();
+    let (client, conn) = tokio::join!(
+        Client::<H, M, DefaultCodec>::connect(addr, DefaultCodec::default()),
+        server.accept::<H, M>()
+    );
+    (client.unwrap(), conn.unwrap())
+}
+
+/// A plain stream, and the servers side of its connection, for sending hand made bytes
+pub async fn raw_pair<H, M>() -> (TcpStream, Conn<H, M>)
+where
+    H: IsHeader + Clone + Debug + Send,
+    M: Serialize + DeserializeOwned + Send,
+{
+    let mut server = server().await;
+    let addr = server.as_listener().local_addr().unwrap();
+    let (stream, conn) = tokio::join!(TcpStream::connect(addr), server.accept::<H, M>());
+    (stream.unwrap(), conn.unwrap())
+}
+
+/// A client, and the plain stream it is connected to, for checking the exact bytes it sends
+pub async fn client_and_raw<H, M>() -> (Client<H, M, DefaultCodec>, TcpStream)
+where
+    H: IsHeader + Clone + Debug,
+    M: Serialize + DeserializeOwned,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (client, accepted) = tokio::join!(
+        Client::<H, M, DefaultCodec>::connect(addr, DefaultCodec::default()),
+        listener.accept()
+    );
+    (client.unwrap(), accepted.unwrap().0)
+}
+
+/// Reads exactly `len` bytes from `stream`
+pub async fn read_exactly(stream: &mut TcpStream, len: usize) -> Vec<u8> {
+    let mut buf = vec![0; len];
+    tokio::time::timeout(TIMEOUT, stream.read_exact(&mut buf))
+        .await
+        .expect("timed out reading from the stream")
+        .unwrap();
+    buf
+}
+
+pub async fn write_all(stream: &mut TcpStream, bytes: &[u8]) {
+    stream.write_all(bytes).await.unwrap();
+    stream.flush().await.unwrap();
+}
+
+pub type Sock<H, M> = smalltalk::socket::interface::_SocketUtils<H, M, DefaultCodec>;
+
+/// Waits for the next message on `conn` (a client or a servers connection), failing the test if it takes too long
+pub async fn recv<H, M>(conn: &mut Sock<H, M>) -> smalltalk::MessageWrapper<M, H>
+where
+    H: IsHeader + Clone + Debug,
+    M: Serialize + DeserializeOwned,
+{
+    tokio::time::timeout(TIMEOUT, conn.wait_for_message())
+        .await
+        .expect("timed out waiting for a message")
+        .unwrap()
+}
+
+/// Frames `msg` with the header `H` and the default codec, like the writer does
+pub fn frame<H: IsHeader, M: Serialize>(msg: &M) -> bytes::Bytes {
+    smalltalk::msg::frame::<M, H>(msg, &DefaultCodec::default()).unwrap()
+}
+
+/// A reader on one end of a loopback connection, and the plain stream on the other end writing to it
+pub async fn reader<H, M>() -> (smalltalk::Reader<H, M, DefaultCodec>, TcpStream)
+where
+    H: IsHeader + Clone,
+    M: Serialize + DeserializeOwned,
+{
+    let (ours, theirs) = stream_pair().await;
+    let (read_half, _write_half) = ours.into_split();
+    (
+        smalltalk::Reader::new(read_half, DefaultCodec::default()),
+        theirs,
+    )
+}
+
+/// A writer on one end of a loopback connection, and the plain stream on the other end reading from it
+pub async fn writer<H, M>() -> (smalltalk::Writer<H, M, DefaultCodec>, TcpStream)
+where
+    H: IsHeader,
+    M: Serialize + DeserializeOwned,
+{
+    let (ours, theirs) = stream_pair().await;
+    let (_read_half, write_half) = ours.into_split();
+    (
+        smalltalk::Writer::new(write_half, DefaultCodec::default()),
+        theirs,
+    )
+}
+
+/// Both ends of a loopback connection
+pub async fn stream_pair() -> (TcpStream, TcpStream) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (ours, theirs) = tokio::join!(TcpStream::connect(addr), listener.accept());
+    (ours.unwrap(), theirs.unwrap().0)
+}
+
+/// Decodes what `reader` has buffered, reading more untill `done` returns true
+pub async fn read_until<H, M>(
+    reader: &mut smalltalk::Reader<H, M, DefaultCodec>,
+    mut done: impl FnMut(&mut smalltalk::Reader<H, M, DefaultCodec>) -> bool,
+) where
+    H: IsHeader + Clone + Debug,
+    M: Serialize + DeserializeOwned,
+{
+    tokio::time::timeout(TIMEOUT, async {
+        loop {
+            reader.update().await.unwrap();
+            if done(reader) {
+                break;
+            }
+            reader.read().await.unwrap();
+        }
+    })
+    .await
+    .expect("timed out reading");
+}
+
+/// Queues more than the socket buffers (on both ends) can hold, and writes untill they are full,
+/// so the next write blocks untill the peer reads
+pub async fn stall_writer<H, M>(peer: &Client<H, M, DefaultCodec>, conn: &mut Conn<H, M>)
+where
+    H: IsHeader + Clone + Debug,
+    M: Serialize + DeserializeOwned,
+{
+    peer.set_recv_buffer_size(4096).unwrap();
+    conn.set_send_buffer_size(4096).unwrap();
+    let big = frame::<H, _>(&vec![0u8; 1 << 20]);
+    for _ in 0..16 {
+        conn.as_writer_mut().queue_raw(big.clone()).unwrap();
+    }
+    // writing is cancelation safe, so giving up on a blocked write loses nothing
+    while tokio::time::timeout(Duration::from_millis(50), conn.as_writer_mut().write())
+        .await
+        .is_ok()
+    {}
+}
@@ -0,0 +1,73 @@
+//! `SocketUtils::config` reflects the options set on a connection
+mod common;
+
+use std::time::Duration;
+
+use smalltalk::{
+    socket::{HeartbeatConfig, SocketConfig},
+    Client, DefaultCodec, TypedHeader,
+};
+use tokio::net::TcpListener;
+
+#[tokio::test]
+async fn defaults() {
+    let (client, _conn) = common::pair::<TypedHeader, u32>().await;
+    let config = client.config().unwrap();
+    assert!(!config.nodelay);
+    assert!(!config.keepalive);
+    assert_eq!(config.max_message_size, None);
+    assert_eq!(config.max_frames_per_sec, None);
+    assert!(!config.retain_raw_body);
+    assert!(!config.skip_heartbeats);
+    assert_eq!(config.heartbeat, None);
+    assert_eq!(config.max_lifetime, None);
+    assert_eq!(config.flush_interval, None);
+}
+
+#[tokio::test]
+async fn reflects_non_default_options() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let socket_config = SocketConfig {
+        nodelay: true,
+        keepalive: Some(Duration::from_secs(60)),
+    };
+    let (client, _accepted) = tokio::join!(
+        Client::<TypedHeader, u32, DefaultCodec>::connect_with_config(
+            addr,
+            DefaultCodec::default(),
+            &socket_config
+        ),
+        listener.accept()
+    );
+    let mut client = client.unwrap();
+
+    let heartbeat = HeartbeatConfig {
+        interval: Duration::from_secs(1),
+        timeout: Duration::from_secs(3),
+    };
+    client.set_heartbeat(Some(heartbeat)).unwrap();
+    client.set_max_lifetime(Some(Duration::from_secs(600)));
+    client.set_flush_interval(Some(Duration::from_millis(5)));
+    client.set_send_buffer_size(64 * 1024).unwrap();
+    client.as_reader_mut().set_max_message_size(Some(1024));
+    client.as_reader_mut().set_max_total_buffered(Some(1 << 20));
+    client.as_reader_mut().set_max_frames_per_sec(Some(100));
+    client.as_reader_mut().set_retain_raw_body(true);
+    client.as_writer_mut().set_max_queued_bytes(Some(4096));
+
+    let config = client.config().unwrap();
+    assert!(config.nodelay);
+    assert!(config.keepalive);
+    assert_eq!(config.send_buffer_size, client.send_buffer_size().unwrap());
+    assert_eq!(config.recv_buffer_size, client.recv_buffer_size().unwrap());
+    assert_eq!(config.max_message_size, Some(1024));
+    assert_eq!(config.max_total_buffered, Some(1 << 20));
+    assert_eq!(config.max_frames_per_sec, Some(100));
+    assert!(config.retain_raw_body);
+    assert!(config.skip_heartbeats);
+    assert_eq!(config.max_queued_bytes, Some(4096));
+    assert_eq!(config.flush_interval, Some(Duration::from_millis(5)));
+    assert_eq!(config.heartbeat, Some(heartbeat));
+    assert_eq!(config.max_lifetime, Some(Duration::from_secs(600)));
+}
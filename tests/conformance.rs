@@ -0,0 +1,81 @@
+//! every built-in header passes the conformance battery, and a broken codec is reported instead of passing
+use serde::{de::DeserializeOwned, Serialize};
+use smalltalk::{
+    codec::error::CodecError,
+    conformance::{self, CaseResult},
+    Codec, DefaultCodec, U64Header,
+};
+
+/// bodies over this size get corrupted by [`Corrupting`]
+const CORRUPT_OVER: usize = 1000;
+
+/// bincode, but flipping the last byte of large bodies, like a codec with a bug in it
+#[derive(Debug, Clone, Copy, Default)]
+struct Corrupting;
+
+impl Codec for Corrupting {
+    fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        let mut bytes = DefaultCodec::default().serialize(value)?;
+        if bytes.len() > CORRUPT_OVER {
+            *bytes.last_mut().unwrap() ^= 0xff;
+        }
+        Ok(bytes)
+    }
+
+    fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        DefaultCodec::default().deserialize(bytes)
+    }
+}
+
+#[tokio::test]
+async fn builtin_headers_conform() {
+    let reports = conformance::run_builtin().await.unwrap();
+    assert_eq!(reports.len(), 6);
+    for report in reports {
+        assert!(report.passed(), "{report}");
+        // nothing stopped early
+        let sizes = report.cases.iter().map(|c| c.size).collect::<Vec<_>>();
+        assert!(
+            sizes.contains(&0) && sizes.contains(&(4 * 1024 * 1024)),
+            "{report}"
+        );
+        assert!(report.to_string().ends_with(&format!(
+            "PASS: {0} of {0} cases passed",
+            report.cases.len()
+        )));
+    }
+}
+
+#[test]
+fn sizes_cover_the_edges_around_the_header() {
+    let sizes = conformance::sizes::<U64Header>();
+    for size in [0, 1, 7, 8, 9, u16::MAX as usize + 1] {
+        assert!(sizes.contains(&size), "{size} missing from {sizes:?}");
+    }
+    assert!(sizes.windows(2).all(|w| w[0] < w[1]));
+}
+
+#[tokio::test]
+async fn a_broken_codec_fails_at_the_first_bad_size() {
+    let report = conformance::run::<U64Header, _>(Corrupting).await.unwrap();
+    assert!(!report.passed());
+
+    let failures = report.failures().collect::<Vec<&CaseResult>>();
+    assert_eq!(failures.len(), 1, "{report}");
+    let failed = failures[0];
+    assert!(failed.size > CORRUPT_OVER);
+    assert!(
+        failed
+            .error
+            .as_ref()
+            .unwrap()
+            .contains("server received different data"),
+        "{report}"
+    );
+    // smaller sizes passed, and the run stopped after the failure
+    assert_eq!(report.cases.last(), Some(failed));
+    assert!(report.cases[..report.cases.len() - 1]
+        .iter()
+        .all(|case| case.passed() && case.size <= CORRUPT_OVER));
+    assert!(report.to_string().contains("FAIL"));
+}
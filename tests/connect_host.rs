@@ -0,0 +1,41 @@
+//! connecting by host name tries each address it resolves to
+mod common;
+
+use common::*;
+use smalltalk::{Client, DefaultCodec, MessageWrapper, U64Header};
+
+#[tokio::test]
+async fn localhost_resolves_and_connects() {
+    // only bound on ipv4, so if localhost resolves to ::1 first that attempt fails and the next is used
+    let mut server = server().await;
+    let port = server.as_listener().local_addr().unwrap().port();
+    let (client, conn) = tokio::join!(
+        Client::<U64Header, String, DefaultCodec>::connect_host(
+            "localhost",
+            port,
+            DefaultCodec::default()
+        ),
+        server.accept::<U64Header, String>()
+    );
+    let (mut client, mut conn) = (client.unwrap(), conn.unwrap());
+    assert_eq!(client.addr(), server.as_listener().local_addr().unwrap());
+
+    client
+        .queue_message(&MessageWrapper::new("by name".into()))
+        .unwrap();
+    client.flush_all().await.unwrap();
+    assert_eq!(recv(&mut conn).await.message(), "by name");
+}
+
+#[tokio::test]
+async fn nothing_listening_is_an_error() {
+    let port = server().await.as_listener().local_addr().unwrap().port();
+    // the server was dropped, so every address is refused
+    let res = Client::<U64Header, String, DefaultCodec>::connect_host(
+        "localhost",
+        port,
+        DefaultCodec::default(),
+    )
+    .await;
+    assert!(res.is_err());
+}
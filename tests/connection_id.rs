@@ -0,0 +1,95 @@
+//! each accepted connection gets its own id, which stays the same over its lifetime
+mod common;
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use common::*;
+use smalltalk::{
+    server::{ConnectionId, CounterIdGenerator, IdGenerator},
+    Client, DefaultCodec, MessageWrapper, U64Header,
+};
+
+#[tokio::test]
+async fn connections_get_distinct_stable_ids() {
+    let mut server = server().await;
+    let addr = server.as_listener().local_addr().unwrap();
+    let mut conns = Vec::new();
+    let mut clients = Vec::new();
+    for _ in 0..2 {
+        let (client, conn) = tokio::join!(
+            Client::<U64Header, u32, DefaultCodec>::connect(addr, DefaultCodec::default()),
+            server.accept::<U64Header, u32>()
+        );
+        clients.push(client.unwrap());
+        conns.push(conn.unwrap());
+    }
+    let ids = conns.iter().map(|c| c.id()).collect::<Vec<_>>();
+    assert_ne!(ids[0], ids[1]);
+    // assigned in increasing order
+    assert!(ids[0] < ids[1]);
+
+    // using the connection does not change it
+    for (client, conn) in clients.iter_mut().zip(&mut conns) {
+        client.queue_message(&MessageWrapper::new(1)).unwrap();
+        client.flush_all().await.unwrap();
+        recv(conn).await;
+    }
+    assert_eq!(conns.iter().map(|c| c.id()).collect::<Vec<_>>(), ids);
+}
+
+#[tokio::test]
+async fn servers_can_use_their_own_ids() {
+    let mut server = server().await;
+    server.set_id_generator(Arc::new(CounterIdGenerator::new()));
+    let addr = server.as_listener().local_addr().unwrap();
+    for expected in 0..3 {
+        let (_client, conn) = tokio::join!(
+            Client::<U64Header, u32, DefaultCodec>::connect(addr, DefaultCodec::default()),
+            server.accept::<U64Header, u32>()
+        );
+        assert_eq!(conn.unwrap().id(), ConnectionId::new(expected));
+    }
+}
+
+/// snowflake style ids, the machine in the high bits and a counter in the low bits
+struct Snowflake {
+    machine: u64,
+    counter: AtomicU64,
+}
+
+impl IdGenerator for Snowflake {
+    fn next_id(&self) -> u128 {
+        (u128::from(self.machine) << 64) | u128::from(self.counter.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+#[tokio::test]
+async fn a_custom_generator_is_shared_between_servers() {
+    let generator = Arc::new(Snowflake {
+        machine: 42,
+        counter: AtomicU64::new(0),
+    });
+    let mut servers = [server().await, server().await];
+    for server in &mut servers {
+        server.set_id_generator(generator.clone());
+    }
+
+    let mut ids = Vec::new();
+    for _ in 0..2 {
+        for server in &mut servers {
+            let addr = server.as_listener().local_addr().unwrap();
+            let (_client, conn) = tokio::join!(
+                Client::<U64Header, u32, DefaultCodec>::connect(addr, DefaultCodec::default()),
+                server.accept::<U64Header, u32>()
+            );
+            ids.push(conn.unwrap().id().as_u128());
+        }
+    }
+    // every id follows the scheme, and is unique across both servers
+    assert!(ids.iter().all(|id| id >> 64 == 42));
+    let counters = ids.iter().map(|id| *id as u64).collect::<Vec<_>>();
+    assert_eq!(counters, [0, 1, 2, 3]);
+}
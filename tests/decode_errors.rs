@@ -0,0 +1,174 @@
+mod common;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use common::*;
+use futures::StreamExt;
+use smalltalk::{
+    socket::read::error::{ReadError, UpdateError},
+    IsHeader, TypedHeader, U64Header,
+};
+
+/// a length prefixed by a magic byte, so a corrupted stream produces a header that fails to parse
+#[derive(Debug, Clone, Copy)]
+struct MagicHeader {
+    len: u32,
+}
+
+const MAGIC: u8 = 0xAB;
+
+impl IsHeader for MagicHeader {
+    type Error = String;
+
+    fn new(msg_len: u64) -> Self {
+        Self {
+            len: msg_len as u32,
+        }
+    }
+
+    fn size(&self) -> u64 {
+        u64::from(self.len)
+    }
+
+    fn as_bytes(&self) -> Bytes {
+        self.as_bytes_mut().freeze()
+    }
+
+    fn as_bytes_mut(&self) -> BytesMut {
+        let mut bytes = BytesMut::new();
+        bytes.put_u8(MAGIC);
+        bytes.put_u32(self.len);
+        bytes
+    }
+
+    fn from_bytes(bytes: Bytes) -> Result<Self, Self::Error> {
+        if bytes.len() != 5 || bytes[0] != MAGIC {
+            return Err(format!("bad header {bytes:?}"));
+        }
+        Ok(Self {
+            len: u32::from_be_bytes(bytes[1..].try_into().unwrap()),
+        })
+    }
+
+    fn header_size() -> usize {
+        5
+    }
+}
+
+#[tokio::test]
+async fn bodies_that_fail_to_deserialize_are_counted_and_skipped() {
+    let (mut reader, mut peer) = reader::<U64Header, u32>().await;
+    // a u32 body that is too short, then a good message
+    let mut bytes = U64Header::new(1).as_bytes_mut();
+    bytes.put_u8(0xFF);
+    bytes.extend_from_slice(&frame::<U64Header, _>(&7u32));
+    write_all(&mut peer, &bytes).await;
+
+    let err = tokio::time::timeout(TIMEOUT, async {
+        loop {
+            reader.read().await.unwrap();
+            match reader.update().await {
+                Ok(_) => continue,
+                Err(e) => break e,
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert!(matches!(err, UpdateError::MessageDeseri { body_len: 1, .. }));
+    read_until(&mut reader, |r| r.messages_received() == 2).await;
+    assert_eq!(reader.latest_message().unwrap().into_message(), 7);
+    let errors = reader.decode_errors();
+    assert_eq!((errors.deserialize_errors, errors.header_parse_errors), (1, 0));
+    assert_eq!(errors.total(), 1);
+    reader.reset_decode_errors();
+    assert_eq!(reader.decode_errors().total(), 0);
+}
+
+#[tokio::test]
+async fn deserialize_errors_describe_the_frame() {
+    let (mut reader, _peer) = reader::<TypedHeader, String>().await;
+    // a string claiming 10 bytes, cut off after 3 of them
+    let mut bytes = TypedHeader::new_with_kind(4, 6).unwrap().as_bytes_mut();
+    bytes.extend_from_slice(&[10, b'a', b'b', b'c']);
+    reader.feed(&bytes);
+    let err = reader.update().await.unwrap_err();
+    let UpdateError::MessageDeseri {
+        kind,
+        body_len,
+        header_size,
+        ..
+    } = &err
+    else {
+        panic!("expected a deserialize error, got {err}");
+    };
+    assert_eq!((*kind, *body_len, *header_size), (6, 4, TypedHeader::header_size()));
+    let message = err.to_string();
+    assert!(message.contains("body of 4 bytes"), "{message}");
+    assert!(message.contains("header of 10 bytes"), "{message}");
+}
+
+#[tokio::test]
+async fn a_header_that_fails_to_parse_poisons_the_reader() {
+    let (mut reader, mut peer) = reader::<MagicHeader, u32>().await;
+    let mut bytes = BytesMut::from(&frame::<MagicHeader, _>(&1u32)[..]);
+    // garbage where the next header should be, with a valid looking frame after it
+    bytes.put_slice(&[0, 0, 0, 0, 4]);
+    bytes.put_slice(&frame::<MagicHeader, _>(&2u32));
+    write_all(&mut peer, &bytes).await;
+
+    let err = tokio::time::timeout(TIMEOUT, async {
+        loop {
+            reader.read().await.unwrap();
+            if let Err(e) = reader.update().await {
+                break e;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert!(matches!(err, UpdateError::HeaderParser(_)));
+    assert!(reader.is_poisoned());
+    assert_eq!(reader.decode_errors().header_parse_errors, 1);
+    // only the message before the bad header was delivered, nothing after it is misread
+    assert_eq!(reader.latest_message().unwrap().into_message(), 1);
+    assert!(!reader.update().await.unwrap());
+    assert!(reader.latest_message().is_none());
+    assert_eq!(
+        reader.read().await.unwrap_err().kind(),
+        std::io::ErrorKind::InvalidData
+    );
+}
+
+#[tokio::test]
+async fn a_partial_header_does_not_panic() {
+    let (mut reader, mut peer) = reader::<MagicHeader, u32>().await;
+    // fewer bytes than a header, fed and read in pieces
+    reader.feed(&[MAGIC, 0]);
+    assert!(!reader.update().await.unwrap());
+    write_all(&mut peer, &[0, 0]).await;
+    reader.read().await.unwrap();
+    assert!(!reader.update().await.unwrap());
+    let body = frame::<MagicHeader, _>(&9u32);
+    write_all(&mut peer, &body[4..]).await;
+    read_until(&mut reader, |r| r.messages_received() == 1).await;
+    assert_eq!(reader.latest_message().unwrap().into_message(), 9);
+}
+
+#[tokio::test]
+async fn the_stream_ends_after_a_header_fails_to_parse() {
+    let (reader, mut peer) = reader::<MagicHeader, u32>().await;
+    let mut bytes = BytesMut::from(&frame::<MagicHeader, _>(&1u32)[..]);
+    bytes.put_slice(&[0; 5]);
+    bytes.put_slice(&frame::<MagicHeader, _>(&2u32));
+    write_all(&mut peer, &bytes).await;
+
+    let mut stream = reader.into_stream();
+    assert_eq!(tokio::time::timeout(TIMEOUT, stream.next()).await.unwrap().unwrap().unwrap().into_message(), 1);
+    assert!(matches!(
+        tokio::time::timeout(TIMEOUT, stream.next()).await.unwrap(),
+        Some(Err(ReadError::Update(UpdateError::HeaderParser(_))))
+    ));
+    // the poisoned reader fails to read, which ends the stream
+    assert!(matches!(tokio::time::timeout(TIMEOUT, stream.next()).await.unwrap(), Some(Err(ReadError::Io(_)))));
+    assert!(tokio::time::timeout(TIMEOUT, stream.next()).await.unwrap().is_none());
+}
@@ -0,0 +1,85 @@
+//! values nested past the configured depth are a error, instead of overflowing the stack
+mod common;
+
+use common::*;
+use serde::{Deserialize, Serialize};
+use smalltalk::{
+    codec::error::DepthLimitExceeded, socket::write::error::QueueError, Codec, DefaultCodec,
+    DepthLimited, MessageWrapper, U64Header, Writer,
+};
+
+/// A linked list, about as deeply nested as a message can get
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Node {
+    next: Option<Box<Node>>,
+}
+
+impl Node {
+    fn list(len: usize) -> Self {
+        let mut node = Node::default();
+        for _ in 1..len {
+            node = Node {
+                next: Some(Box::new(node)),
+            };
+        }
+        node
+    }
+}
+
+impl Drop for Node {
+    // dropping a long list recursively would overflow the stack too
+    fn drop(&mut self) {
+        let mut next = self.next.take();
+        while let Some(mut node) = next {
+            next = node.next.take();
+        }
+    }
+}
+
+fn depth_error(err: smalltalk::codec::error::CodecError) -> usize {
+    err.into_inner()
+        .downcast::<DepthLimitExceeded>()
+        .expect("not a depth limit error")
+        .limit
+}
+
+#[test]
+fn too_deep_values_are_an_error() {
+    let codec = DepthLimited::new(DefaultCodec::default(), 64);
+    // far deeper than the stack could handle if it was serialized
+    let deep = Node::list(1_000_000);
+    assert_eq!(depth_error(codec.serialize(&deep).unwrap_err()), 64);
+    assert_eq!(depth_error(codec.serialized_size(&deep).unwrap_err()), 64);
+    assert_eq!(
+        depth_error(codec.serialize_into(Vec::new(), &deep).unwrap_err()),
+        64
+    );
+}
+
+#[test]
+fn the_limit_is_inclusive() {
+    // each node is a struct and a option, so two levels
+    let codec = DepthLimited::new(DefaultCodec::default(), 64);
+    let fits = Node::list(32);
+    let bytes = codec.serialize(&fits).unwrap();
+    assert_eq!(bytes, DefaultCodec::default().serialize(&fits).unwrap());
+    assert!(codec.serialize(&Node::list(33)).is_err());
+}
+
+#[tokio::test]
+async fn queueing_a_too_deep_message_queues_nothing() {
+    let (ours, _theirs) = stream_pair().await;
+    let (_, write_half) = ours.into_split();
+    let mut writer = Writer::<U64Header, Node, _>::new(
+        write_half,
+        DepthLimited::new(DefaultCodec::default(), 64),
+    );
+    let err = writer
+        .queue(&MessageWrapper::new(Node::list(10_000)))
+        .unwrap_err();
+    assert!(matches!(err, QueueError::Seri(_)), "{err:?}");
+    assert_eq!(writer.queued_messages(), 0);
+
+    writer.queue(&MessageWrapper::new(Node::list(8))).unwrap();
+    assert_eq!(writer.queued_messages(), 1);
+}
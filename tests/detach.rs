@@ -0,0 +1,55 @@
+//! detached halves can be moved to their own tasks and still carry messages both ways
+mod common;
+
+use common::*;
+use smalltalk::{
+    socket::{read::ReadStatus, split_stream, Detached},
+    DefaultCodec, MessageWrapper, Reader, U32Header, Writer,
+};
+
+type DetachedWriter = Detached<Writer<U32Header, u32, DefaultCodec>>;
+type DetachedReader = Detached<Reader<U32Header, u32, DefaultCodec>>;
+
+/// Sends `0..count` then drops the writer, closing its side
+async fn send_all(mut writer: DetachedWriter, count: u32) {
+    for n in 0..count {
+        writer.queue(&MessageWrapper::new(n)).unwrap();
+    }
+    writer.flush_all().await.unwrap();
+}
+
+/// Reads messages untill the peer closes its side
+async fn recv_all(mut reader: DetachedReader) -> Vec<u32> {
+    let mut received = vec![];
+    loop {
+        let status = reader.read().await.unwrap();
+        reader.update().await.unwrap();
+        received.extend(reader.ready_messages().map(|m| m.into_message()));
+        if status == ReadStatus::Closed {
+            return received;
+        }
+    }
+}
+
+#[tokio::test]
+async fn detached_halves_in_separate_tasks() {
+    let (ours, theirs) = stream_pair().await;
+    let (our_reader, our_writer) = split_stream::<U32Header, u32, _>(ours, DefaultCodec::default());
+    let (their_reader, their_writer) =
+        split_stream::<U32Header, u32, _>(theirs, DefaultCodec::default());
+
+    let tasks = async {
+        tokio::join!(
+            tokio::spawn(send_all(our_writer.detach(), 100)),
+            tokio::spawn(send_all(their_writer.detach(), 50)),
+            tokio::spawn(recv_all(their_reader.detach())),
+            tokio::spawn(recv_all(our_reader.detach())),
+        )
+    };
+    let (sent_ours, sent_theirs, received_theirs, received_ours) =
+        tokio::time::timeout(TIMEOUT, tasks).await.unwrap();
+    sent_ours.unwrap();
+    sent_theirs.unwrap();
+    assert_eq!(received_theirs.unwrap(), (0..100).collect::<Vec<_>>());
+    assert_eq!(received_ours.unwrap(), (0..50).collect::<Vec<_>>());
+}
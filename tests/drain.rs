@@ -0,0 +1,49 @@
+//! one update decodes every complete message that is buffered
+mod common;
+
+use common::*;
+use smalltalk::U64Header;
+
+#[tokio::test]
+async fn two_frames_in_one_read_need_one_update() {
+    let (mut reader, mut stream) = reader::<U64Header, String>().await;
+    let mut bytes = frame::<U64Header, _>(&"first".to_string()).to_vec();
+    bytes.extend_from_slice(&frame::<U64Header, _>(&"second".to_string()));
+    write_all(&mut stream, &bytes).await;
+    // one read gets both, as they were written together
+    while reader.buffered_bytes() < bytes.len() {
+        tokio::time::timeout(TIMEOUT, reader.read())
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    assert!(reader.update().await.unwrap());
+    let received = reader
+        .ready_messages()
+        .map(|m| m.into_message())
+        .collect::<Vec<_>>();
+    assert_eq!(received, ["first", "second"]);
+    // nothing left to do
+    assert!(!reader.update().await.unwrap());
+}
+
+#[tokio::test]
+async fn a_partial_frame_after_complete_ones_is_left_buffered() {
+    let (mut reader, _stream) = reader::<U64Header, u32>().await;
+    let mut bytes = Vec::new();
+    for n in 0..10u32 {
+        bytes.extend_from_slice(&frame::<U64Header, _>(&n));
+    }
+    let last = frame::<U64Header, _>(&10u32);
+    bytes.extend_from_slice(&last[..5]);
+    reader.feed(&bytes);
+
+    assert!(reader.update().await.unwrap());
+    assert_eq!(reader.messages_received(), 10);
+    assert_eq!(reader.partial_frame_bytes(), 5);
+    reader.feed(&last[5..]);
+    assert!(reader.update().await.unwrap());
+    assert_eq!(reader.messages_received(), 11);
+    assert!(reader.at_frame_boundary());
+}
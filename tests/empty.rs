@@ -0,0 +1,29 @@
+//! messages with no contents are sent as just a header
+mod common;
+
+use common::*;
+use smalltalk::{IsHeader, MessageWrapper, U32Header};
+
+#[tokio::test]
+async fn unit_messages_are_header_only_frames() {
+    let (mut client, mut stream) = client_and_raw::<U32Header, ()>().await;
+    client.queue_message(&MessageWrapper::new(())).unwrap();
+    client.queue_message(&MessageWrapper::empty()).unwrap();
+    client.flush_all().await.unwrap();
+    let sent = read_exactly(&mut stream, 2 * U32Header::header_size()).await;
+    assert_eq!(sent, [0; 8]);
+    assert_eq!(&frame::<U32Header, ()>(&())[..], [0; 4]);
+}
+
+#[tokio::test]
+async fn unit_messages_round_trip() {
+    let (mut client, mut conn) = pair::<U32Header, ()>().await;
+    for _ in 0..3 {
+        client.queue_message(&MessageWrapper::empty()).unwrap();
+    }
+    client.flush_all().await.unwrap();
+    for _ in 0..3 {
+        let () = recv(&mut conn).await.into_message();
+    }
+    assert_eq!(conn.as_reader().messages_received(), 3);
+}
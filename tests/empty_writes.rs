@@ -0,0 +1,40 @@
+//! writing with nothing queued never waits on the socket
+mod common;
+
+use common::*;
+use futures::FutureExt;
+use smalltalk::{MessageWrapper, U64Header};
+
+#[tokio::test]
+async fn empty_writes_complete_without_waiting() {
+    let (mut client, _peer) = client_and_raw::<U64Header, u32>().await;
+    assert!(client
+        .as_writer_mut()
+        .write()
+        .now_or_never()
+        .unwrap()
+        .is_ok());
+    assert!(client.update().now_or_never().unwrap().is_ok());
+
+    // fill the socket up, so waiting for it to be writable would never finish
+    let chunk = vec![0; 1 << 16];
+    while client.as_writer().as_socket().try_write(&chunk).is_ok() {}
+    assert!(matches!(
+        client.as_writer().as_socket().try_write(&chunk),
+        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock
+    ));
+    // the first poll finishes, instead of waiting for the socket
+    assert!(client
+        .as_writer_mut()
+        .write()
+        .now_or_never()
+        .unwrap()
+        .is_ok());
+    let status = client.update().now_or_never().unwrap().unwrap();
+    assert!(!status.new_msg());
+
+    // with something queued it does wait
+    client.queue_message(&MessageWrapper::new(1)).unwrap();
+    assert!(client.update().now_or_never().is_none());
+    assert_eq!(client.as_writer().queued_messages(), 1);
+}
@@ -0,0 +1,73 @@
+//! the fixed size fast path produces the same frames as working out the size
+mod common;
+
+use bincode::Options;
+use common::*;
+use serde::{Deserialize, Serialize};
+use smalltalk::{
+    msg::FixedSizeMessage, BincodeCodec, Codec, IsHeader, MessageWrapper, TypedHeader, Writer,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Position {
+    id: u32,
+    x: f64,
+    y: f64,
+    flags: [u8; 4],
+}
+
+// with fixint encoding every field is always the same size
+impl FixedSizeMessage for Position {
+    const SERIALIZED_SIZE: u64 = 4 + 8 + 8 + 4;
+}
+
+const POSITIONS: [Position; 2] = [
+    Position {
+        id: 0,
+        x: 0.0,
+        y: 0.0,
+        flags: [0; 4],
+    },
+    Position {
+        id: u32::MAX,
+        x: -1.5,
+        y: 1e300,
+        flags: [1, 2, 3, 255],
+    },
+];
+
+#[test]
+fn fixed_and_general_serialization_match() {
+    let codec = BincodeCodec::new(bincode::DefaultOptions::new().with_fixint_encoding());
+    for position in POSITIONS {
+        assert_eq!(
+            codec.serialized_size(&position).unwrap(),
+            Position::SERIALIZED_SIZE
+        );
+        let msg = MessageWrapper::<_, TypedHeader>::new(position).with_kind(4);
+        assert_eq!(
+            msg.serialize_fixed(&codec).unwrap(),
+            msg.serialize(&codec).unwrap()
+        );
+    }
+}
+
+#[tokio::test]
+async fn queue_fixed_sends_the_same_bytes_as_queue() {
+    let codec = BincodeCodec::new(bincode::DefaultOptions::new().with_fixint_encoding());
+    let (ours, mut theirs) = stream_pair().await;
+    let mut writer = Writer::<TypedHeader, Position, _>::new(ours.into_split().1, codec);
+    for position in POSITIONS {
+        let msg = MessageWrapper::new(position).with_kind(9);
+        writer.queue(&msg).unwrap();
+        writer.queue_fixed(&msg).unwrap();
+    }
+    writer.flush_all().await.unwrap();
+
+    let frame_len = TypedHeader::header_size() + Position::SERIALIZED_SIZE as usize;
+    for _ in POSITIONS {
+        let general = read_exactly(&mut theirs, frame_len).await;
+        let fixed = read_exactly(&mut theirs, frame_len).await;
+        assert_eq!(general, fixed);
+    }
+}
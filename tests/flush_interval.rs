@@ -0,0 +1,54 @@
+//! queued messages are flushed on an interval, without writing them explicitly
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::*;
+use smalltalk::{MessageWrapper, U64Header};
+
+const INTERVAL: Duration = Duration::from_millis(50);
+
+#[tokio::test]
+async fn the_background_flusher_sends_queued_messages() {
+    let (mut writer, mut peer) = writer::<U64Header, String>().await;
+    writer.set_flush_interval(Some(INTERVAL));
+    // let the first tick (which is immediate) pass with nothing queued
+    let flusher = writer.spawn_flusher();
+    tokio::time::sleep(INTERVAL / 2).await;
+
+    for text in ["sporadic", "messages"] {
+        let start = Instant::now();
+        let msg = MessageWrapper::new(text.to_string());
+        flusher.queue(&msg).await.unwrap();
+        let expected = frame::<U64Header, _>(&text.to_string());
+        assert_eq!(read_exactly(&mut peer, expected.len()).await, expected);
+        // generous, as the test runner may be busy
+        assert!(
+            start.elapsed() < INTERVAL * 10,
+            "took {:?}",
+            start.elapsed()
+        );
+    }
+    assert!(!flusher.is_finished());
+    assert_eq!(flusher.lock().await.messages_sent(), 2);
+}
+
+#[tokio::test]
+async fn flush_tick_in_an_event_loop() {
+    let (mut client, mut conn) = pair::<U64Header, String>().await;
+    client.set_flush_interval(Some(INTERVAL));
+    client
+        .queue_message(&MessageWrapper::new("ticked".into()))
+        .unwrap();
+    tokio::select! {
+        res = client.flush_tick() => res.unwrap(),
+        () = tokio::time::sleep(TIMEOUT) => panic!("the flush interval never ticked"),
+    }
+    assert_eq!(recv(&mut conn).await.message(), "ticked");
+
+    // without an interval it never ticks
+    client.set_flush_interval(None);
+    assert!(tokio::time::timeout(INTERVAL * 2, client.flush_tick())
+        .await
+        .is_err());
+}
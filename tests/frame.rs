@@ -0,0 +1,49 @@
+//! precomputed frames match what the writer produces, and can be sent repeatedly
+mod common;
+
+use std::collections::HashMap;
+
+use common::*;
+use smalltalk::{
+    msg, ChecksummedHeader, DefaultCodec, IsHeader, MessageWrapper, U32Header, U64Header,
+};
+
+fn matches_serialize<H: IsHeader>() {
+    let codec = DefaultCodec::default();
+    let messages = [
+        HashMap::new(),
+        HashMap::from([(1u8, "one".to_string())]),
+        (0..200).map(|n| (n, "x".repeat(n.into()))).collect(),
+    ];
+    for message in messages {
+        let framed = msg::frame::<_, H>(&message, &codec).unwrap();
+        let wrapped = MessageWrapper::<_, H>::new(message)
+            .serialize(&codec)
+            .unwrap();
+        assert_eq!(framed, wrapped);
+    }
+}
+
+#[test]
+fn frame_matches_serialize() {
+    matches_serialize::<U32Header>();
+    matches_serialize::<U64Header>();
+    matches_serialize::<ChecksummedHeader>();
+}
+
+#[tokio::test]
+async fn a_cached_frame_is_sent_repeatedly() {
+    let (mut client, mut conn) = pair::<U64Header, String>().await;
+    let cached =
+        msg::frame::<_, U64Header>(&"cached".to_string(), &DefaultCodec::default()).unwrap();
+    for _ in 0..3 {
+        client.as_writer_mut().queue_raw(cached.clone()).unwrap();
+    }
+    client
+        .queue_message(&MessageWrapper::new("normal".into()))
+        .unwrap();
+    client.flush_all().await.unwrap();
+    for expected in ["cached", "cached", "cached", "normal"] {
+        assert_eq!(recv(&mut conn).await.message(), expected);
+    }
+}
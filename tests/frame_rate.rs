@@ -0,0 +1,56 @@
+//! the frames per second limit caps how fast a flood of tiny frames is decoded
+mod common;
+
+use std::time::Duration;
+
+use common::*;
+use smalltalk::U32Header;
+use tokio::time::Instant;
+
+const LIMIT: u32 = 20;
+
+#[tokio::test(start_paused = true)]
+async fn a_flood_of_tiny_frames_is_decoded_at_the_limit() {
+    let (mut reader, mut peer) = reader::<U32Header, u8>().await;
+    reader.set_max_frames_per_sec(Some(LIMIT));
+    let mut flood = Vec::new();
+    for n in 0..60u8 {
+        flood.extend_from_slice(&frame::<U32Header, _>(&n));
+    }
+    write_all(&mut peer, &flood).await;
+
+    let start = Instant::now();
+    let mut after_one_sec = None;
+    while reader.messages_received() < 60 {
+        reader.read().await.unwrap();
+        reader.update().await.unwrap();
+        if after_one_sec.is_none() && start.elapsed() >= Duration::from_secs(1) {
+            after_one_sec = Some(reader.messages_received());
+        }
+    }
+    // a burst of LIMIT, then LIMIT more each second
+    let decoded = after_one_sec.unwrap();
+    assert!(
+        (2 * LIMIT - 1..=2 * LIMIT + 1).contains(&(decoded as u32)),
+        "{decoded}"
+    );
+    let elapsed = start.elapsed();
+    assert!(
+        elapsed >= Duration::from_millis(1950) && elapsed <= Duration::from_millis(2050),
+        "{elapsed:?}"
+    );
+    let received = reader
+        .ready_messages()
+        .map(|m| m.into_message())
+        .collect::<Vec<_>>();
+    assert_eq!(received, (0..60).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn a_limit_of_zero_is_raised_to_one() {
+    let (mut reader, _peer) = reader::<U32Header, u8>().await;
+    reader.set_max_frames_per_sec(Some(0));
+    assert_eq!(reader.max_frames_per_sec(), Some(1));
+    reader.set_max_frames_per_sec(None);
+    assert_eq!(reader.max_frames_per_sec(), None);
+}
@@ -0,0 +1,57 @@
+//! groups of messages are queued and sent as one unit
+mod common;
+
+use common::*;
+use smalltalk::{MessageWrapper, U32Header};
+
+#[tokio::test]
+async fn groups_are_sent_contiguously_in_order() {
+    let (mut writer, mut stream) = writer::<U32Header, String>().await;
+    let group = ["a", "bc", "def"].map(|s| MessageWrapper::new(s.to_string()));
+    writer.queue(&MessageWrapper::new("before".into())).unwrap();
+    writer.queue_group(&group).unwrap();
+    writer.queue(&MessageWrapper::new("after".into())).unwrap();
+    // the group is one buffer
+    assert_eq!(writer.queued_messages(), 3);
+    writer.flush_all().await.unwrap();
+
+    let expected = ["before", "a", "bc", "def", "after"]
+        .iter()
+        .flat_map(|s| frame::<U32Header, _>(&s.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(read_exactly(&mut stream, expected.len()).await, expected);
+}
+
+#[tokio::test]
+async fn groups_are_queued_entirely_or_not_at_all() {
+    let (mut writer, _stream) = writer::<U32Header, String>().await;
+    // the second can not be serialized, U32Header has no room for a kind
+    let group = [
+        MessageWrapper::new("fine".to_string()),
+        MessageWrapper::new("not fine".to_string()).with_kind(3),
+    ];
+    assert!(writer.queue_group(&group).is_err());
+    assert!(writer.queued_messages() == 0);
+
+    // an empty queue takes anything, so start with something queued
+    let one = frame::<U32Header, _>(&"one".to_string()).len();
+    writer.queue(&MessageWrapper::new("one".into())).unwrap();
+    let group = ["one", "two"].map(|s| MessageWrapper::new(s.to_string()));
+    writer.set_max_queued_bytes(Some(one * 3 - 1));
+    assert!(writer.queue_group(&group).is_err());
+    assert_eq!(writer.queued_messages(), 1);
+    writer.set_max_queued_bytes(Some(one * 3));
+    writer.queue_group(&group).unwrap();
+    assert_eq!(writer.queued_messages(), 2);
+}
+
+#[tokio::test]
+async fn group_round_trip() {
+    let (mut client, mut conn) = pair::<U32Header, u32>().await;
+    let group = (0..10).map(MessageWrapper::new).collect::<Vec<_>>();
+    client.as_writer_mut().queue_group(&group).unwrap();
+    client.flush_all().await.unwrap();
+    for n in 0..10 {
+        assert_eq!(recv(&mut conn).await.into_message(), n);
+    }
+}
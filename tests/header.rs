@@ -0,0 +1,108 @@
+mod common;
+
+use common::*;
+use smalltalk::{
+    codec::Codec,
+    socket::write::error::QueueError, FixedSizeMessage, IsHeader, MessageWrapper, U32Header,
+    U64Header,
+};
+
+#[test]
+fn u32_header_round_trips_through_bytes() {
+    for len in [0, 1, 255, 65_536, u64::from(u32::MAX)] {
+        let header = U32Header::new(len);
+        let bytes = header.as_bytes();
+        assert_eq!(bytes.len(), U32Header::header_size());
+        assert_eq!(bytes[..], (len as u32).to_be_bytes());
+        assert_eq!(U32Header::from_bytes(bytes).unwrap().size(), len);
+    }
+}
+
+#[test]
+fn u32_header_rejects_lengths_that_do_not_fit() {
+    let too_large = u64::from(u32::MAX) + 1;
+    assert!(U32Header::try_new(u64::from(u32::MAX)).is_ok());
+    assert!(U32Header::try_new(too_large).is_err());
+    // through the trait, which is what framing uses
+    assert!(<U32Header as IsHeader>::try_new(too_large).is_err());
+    assert!(U32Header::from_bytes(vec![0; 3].into()).is_err());
+}
+
+/// claims to be larger than a `U32Header` can describe, without actually allocating that much
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Huge(u8);
+
+impl FixedSizeMessage for Huge {
+    const SERIALIZED_SIZE: u64 = u32::MAX as u64 + 1;
+}
+
+#[tokio::test]
+async fn messages_too_large_for_the_header_fail_to_queue_instead_of_panicking() {
+    let message = MessageWrapper::<Huge, U32Header>::new(Huge(0));
+    assert!(message.serialize_fixed(&smalltalk::DefaultCodec::default()).is_err());
+
+    let (mut client, _conn) = pair::<U32Header, Huge>().await;
+    assert!(matches!(
+        client.as_writer_mut().queue_fixed(&message),
+        Err(QueueError::Seri(_))
+    ));
+    assert_eq!(client.as_writer().queued_messages(), 0);
+}
+
+#[tokio::test]
+async fn u32_header_frames_messages_on_the_wire() {
+    let (mut client, mut raw) = client_and_raw::<U32Header, Vec<u8>>().await;
+    let payload = vec![7u8; 300];
+    client.queue_message(&MessageWrapper::new(payload.clone())).unwrap();
+    client.flush_all().await.unwrap();
+
+    let body = smalltalk::DefaultCodec::default().serialize(&payload).unwrap();
+    let header = read_exactly(&mut raw, 4).await;
+    assert_eq!(header, (body.len() as u32).to_be_bytes());
+    assert_eq!(read_exactly(&mut raw, body.len()).await, body);
+
+    let (mut client, mut conn) = pair::<U32Header, Vec<u8>>().await;
+    client.queue_message(&MessageWrapper::new(payload.clone())).unwrap();
+    client.flush_all().await.unwrap();
+    assert_eq!(recv(&mut conn).await.into_message(), payload);
+}
+
+#[test]
+fn u64_header_round_trips_the_full_range() {
+    for len in [0, 1, 0x0123_4567_89AB_CDEF, u64::from(u32::MAX) + 1, u64::MAX] {
+        let header = U64Header::new(len);
+        assert_eq!(header.size(), len);
+        let bytes = header.as_bytes();
+        assert_eq!(bytes.len(), U64Header::header_size());
+        assert_eq!(bytes[..], len.to_be_bytes());
+        assert_eq!(header.as_bytes_mut()[..], bytes[..]);
+        assert_eq!(U64Header::from_bytes(bytes).unwrap().size(), len);
+    }
+    assert_eq!(U64Header::header_size(), 8);
+    assert!(<U64Header as IsHeader>::try_new(u64::MAX).is_ok());
+}
+
+#[test]
+fn u64_header_rejects_the_wrong_number_of_bytes() {
+    for len in [0, 4, 7, 9, 16] {
+        assert!(U64Header::from_bytes(vec![0; len].into()).is_err());
+    }
+}
+
+#[tokio::test]
+async fn u64_header_frames_messages_on_the_wire() {
+    let (mut client, mut raw) = client_and_raw::<U64Header, Vec<u8>>().await;
+    let payload = vec![9u8; 70_000];
+    client.queue_message(&MessageWrapper::new(payload.clone())).unwrap();
+    client.flush_all().await.unwrap();
+
+    let body = smalltalk::DefaultCodec::default().serialize(&payload).unwrap();
+    let header = read_exactly(&mut raw, 8).await;
+    assert_eq!(header, (body.len() as u64).to_be_bytes());
+    assert_eq!(read_exactly(&mut raw, body.len()).await, body);
+
+    let (mut client, mut conn) = pair::<U64Header, Vec<u8>>().await;
+    client.queue_message(&MessageWrapper::new(payload.clone())).unwrap();
+    client.flush_all().await.unwrap();
+    assert_eq!(recv(&mut conn).await.into_message(), payload);
+}
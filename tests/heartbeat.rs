@@ -0,0 +1,77 @@
+//! heartbeats are only enabled for headers that can mark them, and are never mixed up with empty messages
+mod common;
+
+use std::time::Duration;
+
+use common::*;
+use smalltalk::{
+    socket::{interface::error::HeartbeatUnsupported, HeartbeatConfig},
+    IsHeader, MessageWrapper, TypedHeader, U32Header,
+};
+
+const HEARTBEAT: HeartbeatConfig = HeartbeatConfig {
+    interval: Duration::from_secs(5),
+    timeout: Duration::from_secs(15),
+};
+
+#[tokio::test]
+async fn headers_without_a_heartbeat_marker_are_rejected() {
+    let (mut client, _conn) = pair::<U32Header, ()>().await;
+    assert_eq!(
+        client.set_heartbeat(Some(HEARTBEAT)),
+        Err(HeartbeatUnsupported)
+    );
+    assert_eq!(client.heartbeat(), None);
+    assert!(!client.as_reader().skip_heartbeats());
+    // disabling them is always fine
+    client.set_heartbeat(None).unwrap();
+    assert!(U32Header::heartbeat().is_none());
+    assert!(!U32Header::new(0).is_heartbeat());
+    // and the writer does not send anything in their place
+    assert!(!client.as_writer_mut().queue_heartbeat());
+}
+
+#[test]
+fn typed_header_reserves_a_kind_for_heartbeats() {
+    let heartbeat = TypedHeader::heartbeat().unwrap();
+    assert!(heartbeat.is_heartbeat());
+    assert_eq!(heartbeat.size(), 0);
+    assert_eq!(heartbeat.kind(), TypedHeader::HEARTBEAT_KIND);
+    assert!(!TypedHeader::new(0).is_heartbeat());
+    let err = TypedHeader::new_with_kind(0, TypedHeader::HEARTBEAT_KIND).unwrap_err();
+    assert_eq!(err.kind, TypedHeader::HEARTBEAT_KIND);
+    // it survives the trip over the wire
+    let parsed = TypedHeader::from_bytes(heartbeat.as_bytes()).unwrap();
+    assert!(parsed.is_heartbeat());
+}
+
+#[tokio::test]
+async fn empty_messages_are_not_swallowed_as_heartbeats() {
+    let (mut client, mut conn) = pair::<TypedHeader, ()>().await;
+    client.set_heartbeat(Some(HEARTBEAT)).unwrap();
+    conn.set_heartbeat(Some(HEARTBEAT)).unwrap();
+
+    // `()` serializes to zero bytes, so this has the same length as a heartbeat
+    assert!(client.as_writer_mut().queue_heartbeat());
+    client.flush_all().await.unwrap();
+    client.queue_message(&MessageWrapper::new(())).unwrap();
+    client.flush_all().await.unwrap();
+
+    let msg = recv(&mut conn).await;
+    let () = msg.into_message();
+    // the heartbeat was dropped (and not counted), only the real message was delivered
+    assert_eq!(conn.as_reader().messages_received(), 1);
+    assert_eq!(conn.get_messages().count(), 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn enabling_heartbeats_restarts_the_idle_timer() {
+    let (mut client, _conn) = pair::<TypedHeader, ()>().await;
+    tokio::time::advance(HEARTBEAT.timeout * 2).await;
+    assert!(client.as_reader().idle_time() >= HEARTBEAT.timeout * 2);
+
+    client.set_heartbeat(Some(HEARTBEAT)).unwrap();
+    assert!(client.as_reader().idle_time() < HEARTBEAT.timeout);
+    // so the quiet time before heartbeats were enabled does not count against the peer
+    client.update().await.unwrap();
+}
@@ -0,0 +1,96 @@
+//! the reader's callbacks run once for everything it decodes
+mod common;
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use common::*;
+use smalltalk::{
+    socket::read::error::UpdateError, DefaultCodec, IsHeader, MessageWrapper, TypedHeader,
+    U64Header,
+};
+
+#[tokio::test]
+async fn on_message_runs_once_per_frame() {
+    let (mut reader, mut stream) = reader::<U64Header, u32>().await;
+    let seen = Arc::new(AtomicUsize::new(0));
+    let count = seen.clone();
+    reader.set_on_message(move |msg| {
+        // runs before the message can be retrieved, in the order they were sent
+        assert_eq!(
+            *msg.message() as usize,
+            count.fetch_add(1, Ordering::SeqCst)
+        );
+    });
+
+    // several frames in one write, and one split over two
+    let mut bytes = Vec::new();
+    for n in 0..5u32 {
+        bytes.extend_from_slice(&frame::<U64Header, _>(&n));
+    }
+    write_all(&mut stream, &bytes).await;
+    let last = frame::<U64Header, _>(&5u32);
+    write_all(&mut stream, &last[..3]).await;
+    read_until(&mut reader, |r| r.messages_received() == 5).await;
+    assert_eq!(seen.load(Ordering::SeqCst), 5);
+    write_all(&mut stream, &last[3..]).await;
+    read_until(&mut reader, |r| r.messages_received() == 6).await;
+    assert_eq!(seen.load(Ordering::SeqCst), 6);
+
+    // no extra calls when the messages are taken
+    assert_eq!(reader.ready_messages().count(), 6);
+    reader.clear_on_message();
+    write_all(&mut stream, &frame::<U64Header, _>(&6u32)).await;
+    read_until(&mut reader, |r| r.messages_received() == 7).await;
+    assert_eq!(seen.load(Ordering::SeqCst), 6);
+}
+
+#[tokio::test]
+async fn on_header_runs_before_the_body_arrives() {
+    let (mut reader, mut stream) = reader::<TypedHeader, Vec<u8>>().await;
+    let headers = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let seen = headers.clone();
+    reader.set_on_header(move |header| {
+        seen.lock().unwrap().push((header.size(), header.kind()));
+        true
+    });
+
+    let bytes = frame_with_kind(&[1u8; 5000], 3);
+    let header_size = TypedHeader::header_size();
+    // the header and only part of the body
+    write_all(&mut stream, &bytes[..header_size + 10]).await;
+    read_until(&mut reader, |_| !headers.lock().unwrap().is_empty()).await;
+    assert_eq!(
+        *headers.lock().unwrap(),
+        [(bytes.len() as u64 - header_size as u64, 3)]
+    );
+    assert_eq!(reader.messages_received(), 0);
+
+    write_all(&mut stream, &bytes[header_size + 10..]).await;
+    read_until(&mut reader, |r| r.messages_received() == 1).await;
+    // once per message
+    assert_eq!(headers.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn on_header_can_reject_messages() {
+    let (mut reader, _stream) = reader::<TypedHeader, Vec<u8>>().await;
+    reader.set_on_header(|header| header.kind() != 4);
+    reader.feed(&frame_with_kind(&[0u8; 3], 1));
+    reader.feed(&frame_with_kind(&[0u8; 3], 4));
+    let err = reader.update().await.unwrap_err();
+    assert!(matches!(err, UpdateError::HeaderRejected { size: 4 }));
+    // the first was decoded before the rejected one
+    assert_eq!(reader.messages_received(), 1);
+    assert!(reader.is_poisoned());
+}
+
+/// Frames `body` with a `TypedHeader` of `kind`
+fn frame_with_kind(body: &[u8], kind: u16) -> bytes::Bytes {
+    MessageWrapper::<_, TypedHeader>::new(body.to_vec())
+        .with_kind(kind)
+        .serialize(&DefaultCodec::default())
+        .unwrap()
+}
@@ -0,0 +1,82 @@
+//! the body codec never changes how headers are framed
+mod common;
+
+use common::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use smalltalk::{
+    codec::error::CodecError, Client, Codec, IsHeader, MessageWrapper, Server, TypedHeader,
+    U32Header,
+};
+use tokio::net::TcpListener;
+
+/// a text body format, with nothing in common with the binary headers
+#[derive(Debug, Clone, Copy, Default)]
+struct JsonCodec;
+
+impl Codec for JsonCodec {
+    fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        serde_json::to_vec(value).map_err(CodecError::new)
+    }
+
+    fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        serde_json::from_slice(bytes).map_err(CodecError::new)
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Reading {
+    sensor: String,
+    value: f64,
+}
+
+fn reading() -> Reading {
+    Reading {
+        sensor: "temp".into(),
+        value: 21.5,
+    }
+}
+
+#[tokio::test]
+async fn json_bodies_keep_the_binary_header() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (client, accepted) = tokio::join!(
+        Client::<TypedHeader, Reading, _>::connect(addr, JsonCodec),
+        listener.accept()
+    );
+    let (mut client, mut peer) = (client.unwrap(), accepted.unwrap().0);
+    client
+        .queue_message(&MessageWrapper::new(reading()).with_kind(2))
+        .unwrap();
+    client.flush_all().await.unwrap();
+
+    let body = br#"{"sensor":"temp","value":21.5}"#;
+    let header = TypedHeader::new_with_kind(body.len() as u64, 2).unwrap();
+    let sent = read_exactly(&mut peer, TypedHeader::header_size() + body.len()).await;
+    assert_eq!(sent[..TypedHeader::header_size()], header.as_bytes()[..]);
+    assert_eq!(sent[TypedHeader::header_size()..], body[..]);
+}
+
+#[tokio::test]
+async fn json_bodies_round_trip() {
+    let mut server = Server::bind("127.0.0.1:0", JsonCodec).await.unwrap();
+    let addr = server.as_listener().local_addr().unwrap();
+    let (client, conn) = tokio::join!(
+        Client::<U32Header, Reading, _>::connect(addr, JsonCodec),
+        server.accept::<U32Header, Reading>()
+    );
+    let (mut client, mut conn) = (client.unwrap(), conn.unwrap());
+    for _ in 0..3 {
+        client
+            .queue_message(&MessageWrapper::new(reading()))
+            .unwrap();
+    }
+    client.flush_all().await.unwrap();
+    for _ in 0..3 {
+        let msg = tokio::time::timeout(TIMEOUT, conn.wait_for_message())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(msg.into_message(), reading());
+    }
+}
@@ -0,0 +1,77 @@
+//! the json codec round trips messages, and puts plain json on the wire
+mod common;
+
+use common::*;
+use serde::{Deserialize, Serialize};
+use smalltalk::{IsHeader, JsonCodec, MessageWrapper, Reader, U32Header, Writer};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Reading {
+    sensor: String,
+    value: f64,
+    tags: Vec<String>,
+}
+
+fn reading() -> Reading {
+    Reading {
+        sensor: "thermometer".to_string(),
+        value: 21.5,
+        tags: vec!["indoor".to_string(), "kitchen".to_string()],
+    }
+}
+
+#[tokio::test]
+async fn a_struct_round_trips_through_writer_and_reader() {
+    let (ours, theirs) = stream_pair().await;
+    let (_, write_half) = ours.into_split();
+    let (read_half, _) = theirs.into_split();
+    let mut writer = Writer::<U32Header, Reading, _>::new(write_half, JsonCodec);
+    let mut reader = Reader::<U32Header, Reading, _>::new(read_half, JsonCodec);
+
+    let mut sent = reading();
+    for n in 0..3 {
+        sent.value = f64::from(n);
+        writer.queue(&MessageWrapper::new(sent.clone())).unwrap();
+    }
+    tokio::time::timeout(TIMEOUT, writer.flush_all())
+        .await
+        .unwrap()
+        .unwrap();
+
+    tokio::time::timeout(TIMEOUT, async {
+        while reader.messages_received() < 3 {
+            reader.read().await.unwrap();
+            reader.update().await.unwrap();
+        }
+    })
+    .await
+    .unwrap();
+    let received = reader
+        .ready_messages()
+        .map(MessageWrapper::into_message)
+        .collect::<Vec<_>>();
+    assert_eq!(received.len(), 3);
+    for (n, msg) in received.into_iter().enumerate() {
+        assert_eq!(msg.value, n as f64);
+        assert_eq!(msg.sensor, "thermometer");
+    }
+}
+
+#[tokio::test]
+async fn the_body_is_plain_json() {
+    let (ours, mut peer) = stream_pair().await;
+    let (_, write_half) = ours.into_split();
+    let mut writer = Writer::<U32Header, Reading, _>::new(write_half, JsonCodec);
+    writer.queue(&MessageWrapper::new(reading())).unwrap();
+    writer.flush_all().await.unwrap();
+
+    let header = read_exactly(&mut peer, U32Header::header_size()).await;
+    let len = u32::from_be_bytes(header.try_into().unwrap()) as usize;
+    let body = read_exactly(&mut peer, len).await;
+    let expected = serde_json::to_vec(&reading()).unwrap();
+    assert_eq!(body, expected);
+    assert!(!body.ends_with(b"\n"));
+    // readable by anything that speaks json
+    let value: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(value["sensor"], "thermometer");
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use bytes::BytesMut;
+use common::*;
+use smalltalk::{
+    codec::Codec, DefaultCodec, ExtendedHeader, IsHeader, MessageWrapper, TypedHeader, U64Header,
+};
+
+#[test]
+fn header_bytes_match_the_header_sent_by_serialize() {
+    let codec = DefaultCodec::default();
+    let typed = MessageWrapper::<String, TypedHeader>::new("hi".into()).with_kind(7);
+    assert_eq!(typed.header(&codec).unwrap().kind(), 7);
+    let mut manual = BytesMut::from(&typed.header_bytes(&codec).unwrap()[..]);
+    manual.extend_from_slice(&typed.serialize_self(&codec).unwrap());
+    assert_eq!(manual.freeze(), typed.serialize(&codec).unwrap());
+
+    let extended =
+        MessageWrapper::<String, ExtendedHeader<4>>::new("hi".into()).with_extension(&b"ab"[..]);
+    assert_eq!(extended.header(&codec).unwrap().extension(), b"ab\0\0");
+    let mut manual = BytesMut::from(&extended.header_bytes(&codec).unwrap()[..]);
+    manual.extend_from_slice(&extended.serialize_self(&codec).unwrap());
+    assert_eq!(manual.freeze(), extended.serialize(&codec).unwrap());
+
+    // the same errors as serialize, instead of a header for a different message
+    let untyped = MessageWrapper::<String, U64Header>::new("hi".into()).with_kind(7);
+    assert!(untyped.header(&codec).is_err());
+    assert!(untyped.serialize(&codec).is_err());
+}
+
+#[test]
+fn typed_header_layout() {
+    let header = TypedHeader::new_with_kind(0x0102, 0x0304).unwrap();
+    assert_eq!(
+        header.as_bytes()[..],
+        [0, 0, 0, 0, 0, 0, 0x01, 0x02, 0x03, 0x04]
+    );
+    let parsed = TypedHeader::from_bytes(header.as_bytes()).unwrap();
+    assert_eq!((parsed.size(), parsed.kind()), (0x0102, 0x0304));
+    assert!(TypedHeader::from_bytes(vec![0; 9].into()).is_err());
+    // headers without room for a kind only accept 0
+    assert!(U64Header::new_with_kind(1, 0).is_ok());
+    assert!(U64Header::new_with_kind(1, 2).is_err());
+}
+
+#[tokio::test]
+async fn messages_are_routed_by_kind() {
+    let (mut client, mut conn) = pair::<TypedHeader, u32>().await;
+    for (message, kind) in [(1, 1), (2, 2), (3, 1), (4, 3)] {
+        client
+            .queue_message(&MessageWrapper::new(message).with_kind(kind))
+            .unwrap();
+    }
+    client.flush_all().await.unwrap();
+    // read all four before sorting them
+    let mut received = vec![recv(&mut conn).await];
+    while received.len() < 4 {
+        received.push(recv(&mut conn).await);
+    }
+    assert_eq!(
+        received.iter().map(|m| (*m.message(), m.kind())).collect::<Vec<_>>(),
+        [(1, 1), (2, 2), (3, 1), (4, 3)]
+    );
+
+    for (message, kind) in [(1, 1), (2, 2), (3, 1), (4, 3)] {
+        client
+            .queue_message(&MessageWrapper::new(message).with_kind(kind))
+            .unwrap();
+    }
+    client.flush_all().await.unwrap();
+    // the counter includes the first four
+    while conn.as_reader().messages_received() < 8 {
+        tokio::time::timeout(TIMEOUT, conn.update_read()).await.unwrap().unwrap();
+        conn.update().await.unwrap();
+    }
+    let ones = conn.get_messages_of_kind(1);
+    assert_eq!(ones.iter().map(|m| *m.message()).collect::<Vec<_>>(), [1, 3]);
+    let rest = conn.get_messages().map(|m| (*m.message(), m.kind())).collect::<Vec<_>>();
+    assert_eq!(rest, [(2, 2), (4, 3)]);
+}
+
+#[tokio::test]
+async fn sending_a_kind_the_header_can_not_hold_fails() {
+    let (mut client, _conn) = pair::<U64Header, u32>().await;
+    assert!(client
+        .queue_message(&MessageWrapper::new(1).with_kind(2))
+        .is_err());
+    assert_eq!(client.as_writer().queued_messages(), 0);
+    // the body is encoded by the codec, only the header changes
+    let framed = MessageWrapper::<u32, TypedHeader>::new(5)
+        .with_kind(9)
+        .serialize(&DefaultCodec::default())
+        .unwrap();
+    assert_eq!(framed[10..], DefaultCodec::default().serialize(&5u32).unwrap()[..]);
+}
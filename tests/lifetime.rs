@@ -0,0 +1,37 @@
+//! connections report when they are older than their max lifetime, so they can be closed
+mod common;
+
+use std::time::Duration;
+
+use common::*;
+use smalltalk::{MessageWrapper, U64Header};
+
+const LIFETIME: Duration = Duration::from_millis(200);
+
+#[tokio::test]
+async fn update_reports_an_exceeded_lifetime() {
+    let (mut client, mut conn) = pair::<U64Header, String>().await;
+    conn.set_max_lifetime(Some(LIFETIME));
+    // activity does not extend it
+    client
+        .queue_message(&MessageWrapper::new("hi".into()))
+        .unwrap();
+    client.flush_all().await.unwrap();
+    recv(&mut conn).await;
+    assert!(!conn.update().await.unwrap().lifetime_exceeded());
+
+    tokio::time::sleep(LIFETIME).await;
+    assert!(conn.age() >= LIFETIME);
+    assert!(conn.update().await.unwrap().lifetime_exceeded());
+    assert!(conn.lifetime_exceeded());
+
+    // closing gracefully still sends what was queued
+    conn.queue_message(&MessageWrapper::new("bye".into()))
+        .unwrap();
+    conn.close_after_flush().await.unwrap();
+    assert_eq!(recv(&mut client).await.message(), "bye");
+    assert!(client.wait_for_message().await.is_err());
+
+    // without a limit it never fires
+    assert!(!client.update().await.unwrap().lifetime_exceeded());
+}
@@ -0,0 +1,48 @@
+//! a connection can be consumed as a stream of plain messages
+mod common;
+
+use common::*;
+use futures::StreamExt;
+use smalltalk::{MessageWrapper, U64Header};
+
+#[tokio::test]
+async fn messages_are_yielded_until_the_peer_closes() {
+    let (mut client, conn) = pair::<U64Header, String>().await;
+    for word in ["one", "two", "three"] {
+        client
+            .queue_message(&MessageWrapper::new(word.to_string()))
+            .unwrap();
+    }
+    client.flush_all().await.unwrap();
+    drop(client);
+
+    let received = tokio::time::timeout(TIMEOUT, conn.into_message_stream().collect::<Vec<_>>())
+        .await
+        .unwrap();
+    let received = received.into_iter().map(Result::unwrap).collect::<Vec<_>>();
+    assert_eq!(received, ["one", "two", "three"]);
+}
+
+#[tokio::test]
+async fn the_connection_can_be_taken_back_between_messages() {
+    let (mut client, conn) = pair::<U64Header, u32>().await;
+    client.queue_message(&MessageWrapper::new(1)).unwrap();
+    client.queue_message(&MessageWrapper::new(2)).unwrap();
+    client.flush_all().await.unwrap();
+
+    let mut stream = conn.into_message_stream();
+    let first = tokio::time::timeout(TIMEOUT, stream.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(first, 1);
+
+    // nothing already received is lost by going back to the connection
+    let mut conn = stream.into_inner().unwrap();
+    let second = tokio::time::timeout(TIMEOUT, conn.wait_for_message())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(second.into_message(), 2);
+}
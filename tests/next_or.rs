@@ -0,0 +1,121 @@
+mod common;
+
+use std::time::Duration;
+
+use common::*;
+use smalltalk::{socket::interface::res::NextOutcome, MessageWrapper, U64Header};
+use tokio_util::sync::CancellationToken;
+
+#[tokio::test]
+async fn returns_whichever_happens_first() {
+    let (mut client, mut conn) = pair::<U64Header, u32>().await;
+    let shutdown = CancellationToken::new();
+
+    assert!(matches!(
+        conn.next_or(Duration::from_millis(20), &shutdown)
+            .await
+            .unwrap(),
+        NextOutcome::Timeout
+    ));
+
+    client.queue_message(&MessageWrapper::new(5)).unwrap();
+    client.flush_all().await.unwrap();
+    match conn.next_or(TIMEOUT, &shutdown).await.unwrap() {
+        NextOutcome::Message(m) => assert_eq!(m.into_message(), 5),
+        other => panic!("expected a message, got {other:?}"),
+    }
+
+    let trigger = shutdown.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        trigger.cancel();
+    });
+    assert!(matches!(
+        conn.next_or(TIMEOUT, &shutdown).await.unwrap(),
+        NextOutcome::Shutdown
+    ));
+}
+
+#[tokio::test]
+async fn a_partial_message_survives_a_timeout() {
+    let (mut raw, mut conn) = raw_pair::<U64Header, u32>().await;
+    let framed = frame::<U64Header, _>(&300u32);
+    write_all(&mut raw, &framed[..framed.len() - 1]).await;
+    let shutdown = CancellationToken::new();
+    assert!(matches!(
+        conn.next_or(Duration::from_millis(50), &shutdown)
+            .await
+            .unwrap(),
+        NextOutcome::Timeout
+    ));
+    write_all(&mut raw, &framed[framed.len() - 1..]).await;
+    match conn.next_or(TIMEOUT, &shutdown).await.unwrap() {
+        NextOutcome::Message(m) => assert_eq!(m.into_message(), 300),
+        other => panic!("expected a message, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn the_peer_closing_is_a_disconnect() {
+    let (raw, mut conn) = raw_pair::<U64Header, u32>().await;
+    drop(raw);
+    assert!(matches!(
+        conn.next_or(TIMEOUT, &CancellationToken::new())
+            .await
+            .unwrap(),
+        NextOutcome::Disconnected
+    ));
+}
+
+#[tokio::test]
+async fn a_write_failing_because_the_peer_reset_is_a_disconnect() {
+    let (raw, mut conn) = raw_pair::<U64Header, Vec<u8>>().await;
+    // closing with a zero linger resets the connection, so writes fail instead of reads ending cleanly
+    raw.set_linger(Some(Duration::ZERO)).unwrap();
+    drop(raw);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    // the write happens in `update` before anything is read
+    conn.queue_message(&MessageWrapper::new(vec![0; 1024]))
+        .unwrap();
+    assert!(matches!(
+        conn.next_or(TIMEOUT, &CancellationToken::new())
+            .await
+            .unwrap(),
+        NextOutcome::Disconnected
+    ));
+}
+
+#[tokio::test]
+async fn shutdown_interrupts_a_stalled_write() {
+    // the client never reads
+    let (client, mut conn) = pair::<U64Header, Vec<u8>>().await;
+    stall_writer(&client, &mut conn).await;
+    let shutdown = CancellationToken::new();
+    let trigger = shutdown.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        trigger.cancel();
+    });
+
+    let outcome = tokio::time::timeout(TIMEOUT, conn.next_or(TIMEOUT * 2, &shutdown))
+        .await
+        .expect("next_or blocked on writing")
+        .unwrap();
+    assert!(matches!(outcome, NextOutcome::Shutdown), "{outcome:?}");
+    // nothing queued was dropped
+    assert!(conn.as_writer().queued_messages() > 0);
+}
+
+#[tokio::test]
+async fn the_timeout_interrupts_a_stalled_write() {
+    let (client, mut conn) = pair::<U64Header, Vec<u8>>().await;
+    stall_writer(&client, &mut conn).await;
+    let outcome = tokio::time::timeout(
+        TIMEOUT,
+        conn.next_or(Duration::from_millis(50), &CancellationToken::new()),
+    )
+    .await
+    .expect("next_or blocked on writing")
+    .unwrap();
+    assert!(matches!(outcome, NextOutcome::Timeout), "{outcome:?}");
+}
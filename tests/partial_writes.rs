@@ -0,0 +1,62 @@
+//! queueing while the writer is part way through a message does not lose or reorder anything
+mod common;
+
+use common::*;
+use smalltalk::{DefaultCodec, MessageWrapper, U32Header, Writer};
+use tokio::io::AsyncReadExt;
+
+#[tokio::test]
+async fn queueing_during_a_partial_write() {
+    let (ours, mut theirs) = stream_pair().await;
+    // a small send buffer, so the first write only gets part of the message out
+    socket2::SockRef::from(&ours)
+        .set_send_buffer_size(4096)
+        .unwrap();
+    let mut writer =
+        Writer::<U32Header, Vec<u8>, _>::new(ours.into_split().1, DefaultCodec::default());
+    let (start_reading, start) = tokio::sync::oneshot::channel();
+    let peer = tokio::spawn(async move {
+        start.await.unwrap();
+        let mut received = Vec::new();
+        theirs.read_to_end(&mut received).await.unwrap();
+        received
+    });
+
+    let big = vec![0xAB; 1 << 20];
+    let mut expected = frame::<U32Header, _>(&big).to_vec();
+    writer.queue(&MessageWrapper::new(big)).unwrap();
+    tokio::time::timeout(TIMEOUT, writer.write())
+        .await
+        .unwrap()
+        .unwrap();
+    let written = writer.bytes_written() as usize;
+    assert!(written > 0 && written < expected.len());
+    assert_eq!(writer.messages_sent(), 0);
+
+    // queue behind the partial message, writing more between each one.
+    // the buffer is made bigger again so the rest does not take forever
+    socket2::SockRef::from(writer.as_socket().as_ref())
+        .set_send_buffer_size(1 << 20)
+        .unwrap();
+    start_reading.send(()).unwrap();
+    for n in 0..20u8 {
+        let small = vec![n; n.into()];
+        expected.extend_from_slice(&frame::<U32Header, _>(&small));
+        writer.queue(&MessageWrapper::new(small)).unwrap();
+        tokio::time::timeout(TIMEOUT, writer.write())
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    writer.flush_all().await.unwrap();
+    assert_eq!(writer.messages_sent(), 21);
+    assert_eq!(writer.bytes_written() as usize, expected.len());
+    assert_eq!(writer.queued_messages(), 0);
+    writer.close_after_flush().await.unwrap();
+    let received = tokio::time::timeout(TIMEOUT, peer).await.unwrap().unwrap();
+    assert!(
+        received == expected,
+        "the bytes received do not match what was queued"
+    );
+}
@@ -0,0 +1,78 @@
+//! a paused reader leaves data in the socket, and holds what it already read, untill it is resumed
+mod common;
+
+use std::time::Duration;
+
+use common::*;
+use smalltalk::{socket::read::ReadStatus, U64Header};
+
+/// long enough that a read would have finished if it was going to
+const SETTLE: Duration = Duration::from_millis(20);
+
+#[tokio::test]
+async fn nothing_is_read_from_the_socket_while_paused() {
+    let (mut reader, mut raw) = reader::<U64Header, u32>().await;
+    reader.pause();
+    for n in [1u32, 2] {
+        write_all(&mut raw, &frame::<U64Header, _>(&n)).await;
+    }
+
+    for _ in 0..5 {
+        assert!(tokio::time::timeout(SETTLE, reader.read()).await.is_err());
+        assert_eq!(reader.bytes_read(), 0);
+        assert_eq!(reader.buffered_bytes(), 0);
+    }
+    assert!(!reader.update().await.unwrap());
+
+    reader.resume();
+    read_until(&mut reader, |r| r.messages_received() == 2).await;
+    let received = reader
+        .ready_messages()
+        .map(|m| m.into_message())
+        .collect::<Vec<_>>();
+    assert_eq!(received, [1, 2]);
+}
+
+#[tokio::test]
+async fn already_buffered_data_is_held_untill_resumed() {
+    let (mut reader, mut raw) = reader::<U64Header, u32>().await;
+    let first = frame::<U64Header, _>(&1u32);
+    write_all(&mut raw, &first).await;
+    // read but not processed yet
+    let status = tokio::time::timeout(TIMEOUT, reader.read())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(status, ReadStatus::Open);
+    assert_eq!(reader.bytes_read(), first.len() as u64);
+
+    reader.pause();
+    assert!(!reader.update().await.unwrap());
+    assert_eq!(reader.messages_received(), 0);
+    assert_eq!(reader.buffered_bytes(), first.len());
+
+    write_all(&mut raw, &frame::<U64Header, _>(&2u32)).await;
+    assert!(tokio::time::timeout(SETTLE, reader.read()).await.is_err());
+    assert_eq!(reader.bytes_read(), first.len() as u64);
+
+    // resuming from elsewhere wakes a read waiting on the pause
+    let handle = reader.pause_handle();
+    let resumer = tokio::spawn(async move {
+        tokio::time::sleep(SETTLE).await;
+        handle.resume();
+    });
+    tokio::time::timeout(TIMEOUT, reader.read())
+        .await
+        .unwrap()
+        .unwrap();
+    resumer.await.unwrap();
+    assert!(!reader.is_paused());
+
+    // the held data is processed first, then the new data
+    read_until(&mut reader, |r| r.messages_received() == 2).await;
+    let received = reader
+        .ready_messages()
+        .map(|m| m.into_message())
+        .collect::<Vec<_>>();
+    assert_eq!(received, [1, 2]);
+}
@@ -0,0 +1,55 @@
+//! messages in a `PersistentSendQueue` outlive the client they were meant for
+mod common;
+
+use common::*;
+use smalltalk::{client::PersistentSendQueue, Client, DefaultCodec, MessageWrapper, U64Header};
+
+#[tokio::test]
+async fn a_recreated_client_sends_the_queued_messages() {
+    let mut server = server().await;
+    let addr = server.as_listener().local_addr().unwrap();
+    let mut queue = PersistentSendQueue::new();
+    for n in 0..3u32 {
+        queue.push(MessageWrapper::new(n));
+    }
+
+    // the first client fails before it sends anything
+    let (client, conn) = tokio::join!(
+        Client::<U64Header, u32, DefaultCodec>::connect(addr, DefaultCodec::default()),
+        server.accept::<U64Header, u32>()
+    );
+    drop((client.unwrap(), conn.unwrap()));
+    queue.push(MessageWrapper::new(3));
+    assert_eq!(queue.len(), 4);
+
+    let (client, conn) = tokio::join!(
+        Client::<U64Header, u32, DefaultCodec>::connect(addr, DefaultCodec::default()),
+        server.accept::<U64Header, u32>()
+    );
+    let (mut client, mut conn) = (client.unwrap(), conn.unwrap());
+    client.attach_queue(&mut queue).unwrap();
+    assert!(queue.is_empty());
+    client.flush_all().await.unwrap();
+    for n in 0..4 {
+        assert_eq!(recv(&mut conn).await.into_message(), n);
+    }
+}
+
+#[tokio::test]
+async fn messages_that_do_not_fit_stay_in_the_queue() {
+    let (mut client, _conn) = pair::<U64Header, u32>().await;
+    let mut queue = PersistentSendQueue::new();
+    for n in 0..3u32 {
+        queue.push(MessageWrapper::new(n));
+    }
+    // room for two messages
+    let size = frame::<U64Header, _>(&0u32).len();
+    client.as_writer_mut().set_max_queued_bytes(Some(size * 2));
+    assert!(client.attach_queue(&mut queue).is_err());
+    assert_eq!(client.as_writer().queued_messages(), 2);
+    assert_eq!(queue.len(), 1);
+
+    client.flush_all().await.unwrap();
+    client.attach_queue(&mut queue).unwrap();
+    assert!(queue.is_empty());
+}
@@ -0,0 +1,62 @@
+//! a framed prelude can be read before switching the connection over to raw bytes
+mod common;
+
+use common::*;
+use smalltalk::U64Header;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+#[tokio::test]
+async fn framed_handshake_then_raw_stream() {
+    let (mut client, mut peer) = client_and_raw::<U64Header, String>().await;
+    let raw: Vec<u8> = (0..=255).cycle().take(10_000).collect();
+    // the handshake and the start of the raw data arrive together
+    let mut bytes = frame::<U64Header, _>(&"auth".to_string()).to_vec();
+    bytes.extend_from_slice(&frame::<U64Header, _>(&"mode: raw".to_string()));
+    bytes.extend_from_slice(&raw[..100]);
+    write_all(&mut peer, &bytes).await;
+
+    let prelude = tokio::time::timeout(TIMEOUT, client.take_first(2))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        prelude
+            .iter()
+            .map(|m| m.message().as_str())
+            .collect::<Vec<_>>(),
+        ["auth", "mode: raw"]
+    );
+    let (mut stream, leftover) = client.into_stream().unwrap();
+    // whatever was read past the prelude comes back, and the rest is read from the stream
+    let mut received = leftover.to_vec();
+    assert!(raw.starts_with(&received));
+    write_all(&mut peer, &raw[100..]).await;
+    let mut rest = vec![0; raw.len() - received.len()];
+    tokio::time::timeout(TIMEOUT, stream.read_exact(&mut rest))
+        .await
+        .unwrap()
+        .unwrap();
+    received.extend_from_slice(&rest);
+    assert_eq!(received, raw);
+
+    // and the recovered stream can be written to
+    stream.write_all(b"raw reply").await.unwrap();
+    assert_eq!(read_exactly(&mut peer, 9).await, b"raw reply");
+}
+
+#[tokio::test]
+async fn server_side_connections_can_switch_too() {
+    let (mut stream, mut conn) = raw_pair::<U64Header, u32>().await;
+    write_all(&mut stream, &frame::<U64Header, _>(&1u32)).await;
+    let prelude = tokio::time::timeout(TIMEOUT, conn.take_first(1))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(*prelude[0].message(), 1);
+    let (mut raw, leftover) = conn.into_stream().unwrap();
+    assert!(leftover.is_empty());
+    write_all(&mut stream, b"raw").await;
+    let mut buf = [0; 3];
+    raw.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"raw");
+}
@@ -0,0 +1,64 @@
+//! snapshots summarise what is queued without sending it, for one connection or all of them
+mod common;
+
+use std::time::Duration;
+
+use common::*;
+use smalltalk::{server::ConnectionRegistry, DefaultCodec, MessageWrapper, U64Header};
+
+#[tokio::test(start_paused = true)]
+async fn snapshot_reports_count_bytes_and_oldest_age() {
+    let (mut writer, _peer) = writer::<U64Header, String>().await;
+    let empty = writer.queue_snapshot();
+    assert_eq!(empty.message_count, 0);
+    assert_eq!(empty.total_bytes, 0);
+    assert_eq!(empty.oldest_enqueue_age, None);
+
+    let first = "first".to_string();
+    let second = "the second one".repeat(10);
+    writer.queue(&MessageWrapper::new(first.clone())).unwrap();
+    tokio::time::advance(Duration::from_millis(300)).await;
+    writer.queue(&MessageWrapper::new(second.clone())).unwrap();
+    tokio::time::advance(Duration::from_millis(200)).await;
+
+    let snapshot = writer.queue_snapshot();
+    assert_eq!(snapshot.message_count, 2);
+    assert_eq!(
+        snapshot.total_bytes,
+        frame::<U64Header, _>(&first).len() + frame::<U64Header, _>(&second).len()
+    );
+    assert_eq!(
+        snapshot.oldest_enqueue_age,
+        Some(Duration::from_millis(500))
+    );
+    // taking a snapshot changes nothing
+    assert_eq!(writer.queued_messages(), 2);
+
+    writer.flush_all().await.unwrap();
+    let flushed = writer.queue_snapshot();
+    assert_eq!(flushed.message_count, 0);
+    assert_eq!(flushed.total_bytes, 0);
+    assert_eq!(flushed.oldest_enqueue_age, None);
+}
+
+#[tokio::test(start_paused = true)]
+async fn registry_snapshot_adds_up_every_connection() {
+    let mut registry = ConnectionRegistry::new(DefaultCodec::default());
+    let (_a, mut a) = pair::<U64Header, u32>().await;
+    let (_b, mut b) = pair::<U64Header, u32>().await;
+    let (_c, c) = pair::<U64Header, u32>().await;
+
+    a.queue_message(&MessageWrapper::new(1)).unwrap();
+    tokio::time::advance(Duration::from_secs(2)).await;
+    b.queue_message(&MessageWrapper::new(2)).unwrap();
+    b.queue_message(&MessageWrapper::new(3)).unwrap();
+    tokio::time::advance(Duration::from_secs(1)).await;
+    for conn in [a, b, c] {
+        registry.insert(conn);
+    }
+
+    let snapshot = registry.queue_snapshot();
+    assert_eq!(snapshot.message_count, 3);
+    assert_eq!(snapshot.total_bytes, 3 * frame::<U64Header, _>(&1u32).len());
+    assert_eq!(snapshot.oldest_enqueue_age, Some(Duration::from_secs(3)));
+}
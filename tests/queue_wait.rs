@@ -0,0 +1,38 @@
+//! the writer records how long each message waited in its queue
+mod common;
+
+use std::time::Duration;
+
+use common::*;
+use smalltalk::{MessageWrapper, U64Header};
+
+#[tokio::test(start_paused = true)]
+async fn queue_wait_reflects_the_delay_before_flushing() {
+    let (mut writer, _peer) = writer::<U64Header, u32>().await;
+    for n in 0..3 {
+        writer.queue(&MessageWrapper::new(n)).unwrap();
+    }
+    tokio::time::advance(Duration::from_millis(250)).await;
+    writer.queue(&MessageWrapper::new(3)).unwrap();
+    tokio::time::advance(Duration::from_millis(50)).await;
+    assert_eq!(writer.queue_wait_histogram().count(), 0);
+    writer.flush_all().await.unwrap();
+
+    let waits = writer.queue_wait_histogram();
+    assert_eq!(waits.count(), 4);
+    assert_eq!(waits.max(), Duration::from_millis(300));
+    // three waited 300ms and one 50ms
+    assert_eq!(waits.mean(), Some(Duration::from_micros(237_500)));
+    let bucket = |d: Duration| waits.buckets().find(|(bound, _)| d < *bound).unwrap().1;
+    assert_eq!(bucket(Duration::from_millis(300)), 3);
+    assert_eq!(bucket(Duration::from_millis(50)), 1);
+
+    // messages sent straight away barely wait
+    writer.queue(&MessageWrapper::new(4)).unwrap();
+    writer.flush_all().await.unwrap();
+    assert_eq!(writer.queue_wait_histogram().count(), 5);
+    assert_eq!(
+        writer.queue_wait_histogram().max(),
+        Duration::from_millis(300)
+    );
+}
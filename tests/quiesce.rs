@@ -0,0 +1,55 @@
+//! a quiescing connection refuses new messages, but still sends what was queued and keeps receiving
+mod common;
+
+use common::*;
+use futures::SinkExt;
+use smalltalk::{
+    socket::write::error::{QueueError, SinkError},
+    MessageWrapper, U64Header,
+};
+
+#[tokio::test]
+async fn queued_messages_still_flush_and_inbound_still_arrives() {
+    let (mut client, mut conn) = pair::<U64Header, u32>().await;
+    client.queue_message(&MessageWrapper::new(1)).unwrap();
+    client.queue_message(&MessageWrapper::new(2)).unwrap();
+
+    assert!(!client.is_quiescing());
+    client.quiesce();
+    assert!(client.is_quiescing());
+    let err = client.queue_message(&MessageWrapper::new(3)).unwrap_err();
+    assert!(matches!(err, QueueError::Quiescing), "{err:?}");
+    let err = client
+        .as_writer_mut()
+        .queue_raw(frame::<U64Header, _>(&3u32))
+        .unwrap_err();
+    assert!(matches!(err, QueueError::Quiescing), "{err:?}");
+    assert_eq!(client.as_writer().queued_messages(), 2);
+
+    // updating keeps writing what was queued before
+    tokio::time::timeout(TIMEOUT, async {
+        while client.as_writer().queued_messages() > 0 {
+            client.update().await.unwrap();
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(recv(&mut conn).await.into_message(), 1);
+    assert_eq!(recv(&mut conn).await.into_message(), 2);
+
+    conn.queue_message(&MessageWrapper::new(9)).unwrap();
+    conn.flush_all().await.unwrap();
+    assert_eq!(recv(&mut client).await.into_message(), 9);
+}
+
+#[tokio::test]
+async fn the_sink_refuses_new_messages() {
+    let (mut writer, _peer) = writer::<U64Header, u32>().await;
+    writer.quiesce();
+    let err = writer.send(MessageWrapper::new(1)).await.unwrap_err();
+    assert!(
+        matches!(err, SinkError::Queue(QueueError::Quiescing)),
+        "{err:?}"
+    );
+    assert_eq!(writer.messages_sent(), 0);
+}
@@ -0,0 +1,41 @@
+//! received messages can keep the exact bytes of their body
+mod common;
+
+use common::*;
+use smalltalk::{IsHeader, MessageWrapper, U64Header};
+
+#[tokio::test]
+async fn retained_bodies_match_what_was_sent() {
+    let (mut client, mut conn) = pair::<U64Header, Vec<String>>().await;
+    conn.as_reader_mut().set_retain_raw_body(true);
+    let message = vec!["audit".to_string(), "me".to_string()];
+    client
+        .queue_message(&MessageWrapper::new(message.clone()))
+        .unwrap();
+    client.flush_all().await.unwrap();
+
+    let received = recv(&mut conn).await;
+    let sent = frame::<U64Header, _>(&message);
+    assert_eq!(
+        received.raw_body().unwrap()[..],
+        sent[U64Header::header_size()..]
+    );
+    assert_eq!(*received.message(), message);
+
+    // the raw body can be forwarded as is
+    let mut forwarded = U64Header::for_body(received.raw_body().unwrap()).as_bytes_mut();
+    forwarded.extend_from_slice(received.raw_body().unwrap());
+    assert_eq!(forwarded.freeze(), sent);
+}
+
+#[tokio::test]
+async fn bodies_are_not_retained_by_default() {
+    let (mut client, mut conn) = pair::<U64Header, u32>().await;
+    client.queue_message(&MessageWrapper::new(1)).unwrap();
+    client.flush_all().await.unwrap();
+    assert!(recv(&mut conn).await.raw_body().is_none());
+    // and messages that were sent never have one
+    assert!(MessageWrapper::<u32, U64Header>::new(1)
+        .raw_body()
+        .is_none());
+}
@@ -0,0 +1,99 @@
+//! read only connections consume messages without a writer
+mod common;
+
+use std::time::Duration;
+
+use common::*;
+use smalltalk::{
+    socket::interface::error::WaitMessageError, Client, DefaultCodec, MessageWrapper, U64Header,
+};
+use tokio::{io::AsyncReadExt, net::TcpListener};
+
+#[tokio::test]
+async fn server_side_read_only_connections() {
+    let mut server = server().await;
+    let addr = server.as_listener().local_addr().unwrap();
+    let (client, conn) = tokio::join!(
+        Client::<U64Header, String, DefaultCodec>::connect(addr, DefaultCodec::default()),
+        server.accept_read_only::<U64Header, String>()
+    );
+    let (mut client, mut conn) = (client.unwrap(), conn.unwrap());
+    assert_eq!(
+        conn.addr(),
+        client.as_writer().as_socket().local_addr().unwrap()
+    );
+
+    for text in ["one", "two", "three"] {
+        client
+            .queue_message(&MessageWrapper::new(text.into()))
+            .unwrap();
+    }
+    client.flush_all().await.unwrap();
+    let first = tokio::time::timeout(TIMEOUT, conn.wait_for_message())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(first.message(), "one");
+    while conn.as_reader().messages_received() < 3 {
+        conn.update_read().await.unwrap();
+        conn.update().await.unwrap();
+    }
+    let rest = conn
+        .get_messages()
+        .map(|m| m.into_message())
+        .collect::<Vec<_>>();
+    assert_eq!(rest, ["two", "three"]);
+
+    // the connection stays fully open, so the client does not see the end of the stream
+    assert!(
+        tokio::time::timeout(Duration::from_millis(100), client.update_read())
+            .await
+            .is_err()
+    );
+
+    // the peer closing ends the wait instead of hanging
+    drop(client);
+    assert!(matches!(
+        tokio::time::timeout(TIMEOUT, conn.wait_for_message())
+            .await
+            .unwrap(),
+        Err(WaitMessageError::Closed)
+    ));
+}
+
+#[tokio::test]
+async fn client_side_read_only_connections() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (conn, accepted) = tokio::join!(
+        Client::<U64Header, u32, DefaultCodec>::connect_read_only(addr, DefaultCodec::default()),
+        listener.accept()
+    );
+    let (mut conn, mut peer) = (conn.unwrap(), accepted.unwrap().0);
+    assert_eq!(conn.addr(), addr);
+    write_all(&mut peer, &frame::<U64Header, _>(&42u32)).await;
+    let msg = tokio::time::timeout(TIMEOUT, conn.wait_for_message())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(msg.into_message(), 42);
+
+    // the connection stays fully open, so the peer does not see the end of the stream
+    let mut buf = [0; 1];
+    assert!(
+        tokio::time::timeout(Duration::from_millis(100), peer.read(&mut buf))
+            .await
+            .is_err()
+    );
+
+    // untill `into_reader` drops the write half
+    let reader = conn.into_reader();
+    assert_eq!(
+        tokio::time::timeout(TIMEOUT, peer.read(&mut buf))
+            .await
+            .unwrap()
+            .unwrap(),
+        0
+    );
+    drop(reader);
+}
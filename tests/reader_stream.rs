@@ -0,0 +1,95 @@
+//! a reader can be used as a stream of messages, with the usual stream combinators
+mod common;
+
+use std::time::Duration;
+
+use common::*;
+use futures::StreamExt;
+use smalltalk::{socket::read::error::ReadError, U64Header};
+use tokio::io::AsyncWriteExt;
+
+#[tokio::test]
+async fn messages_work_with_stream_combinators() {
+    let (reader, mut raw) = reader::<U64Header, u32>().await;
+    let mut bytes = Vec::new();
+    for n in 0..10u32 {
+        bytes.extend_from_slice(&frame::<U64Header, _>(&n));
+    }
+    raw.write_all(&bytes).await.unwrap();
+
+    let mut stream = reader.into_stream();
+    let evens = tokio::time::timeout(
+        TIMEOUT,
+        stream
+            .by_ref()
+            .map(|msg| msg.unwrap().into_message())
+            .filter(|n| std::future::ready(n % 2 == 0))
+            .take(3)
+            .collect::<Vec<_>>(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(evens, [0, 2, 4]);
+
+    // the rest are still there, and the stream ends when the peer closes
+    drop(raw);
+    let rest = tokio::time::timeout(TIMEOUT, stream.collect::<Vec<_>>())
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|msg| msg.unwrap().into_message())
+        .collect::<Vec<_>>();
+    assert_eq!(rest, [5, 6, 7, 8, 9]);
+}
+
+#[tokio::test]
+async fn dropping_next_part_way_through_a_message_loses_nothing() {
+    let (reader, mut raw) = reader::<U64Header, String>().await;
+    let bytes = frame::<U64Header, _>(&"a message".to_string());
+    let (first, second) = bytes.split_at(5);
+    raw.write_all(first).await.unwrap();
+
+    let mut stream = reader.into_stream();
+    let pending = tokio::time::timeout(Duration::from_millis(50), stream.next()).await;
+    assert!(pending.is_err(), "only part of the message was sent");
+
+    // the stream can be taken apart and put back together, still holding the partial message
+    let reader = stream.into_inner();
+    assert_eq!(reader.partial_frame_bytes(), first.len());
+    let mut stream = reader.into_stream();
+
+    raw.write_all(second).await.unwrap();
+    let msg = tokio::time::timeout(TIMEOUT, stream.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(msg.into_message(), "a message");
+}
+
+#[tokio::test]
+async fn a_body_that_fails_to_decode_does_not_end_the_stream() {
+    let (reader, mut raw) = reader::<U64Header, u32>().await;
+    // a empty body is not a valid u32
+    raw.write_all(&0u64.to_be_bytes()).await.unwrap();
+    raw.write_all(&frame::<U64Header, _>(&7u32)).await.unwrap();
+    drop(raw);
+
+    let mut stream = reader.into_stream();
+    let err = tokio::time::timeout(TIMEOUT, stream.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap_err();
+    assert!(matches!(err, ReadError::Update(_)), "{err:?}");
+    let msg = tokio::time::timeout(TIMEOUT, stream.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(msg.into_message(), 7);
+    assert!(tokio::time::timeout(TIMEOUT, stream.next())
+        .await
+        .unwrap()
+        .is_none());
+}
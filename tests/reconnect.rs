@@ -0,0 +1,148 @@
+//! a reconnecting client keeps its settings and hooks when it re-dials
+mod common;
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use common::*;
+use smalltalk::{
+    client::{ReconnectConfig, ReconnectEvent, ReconnectingClient},
+    socket::{HeartbeatConfig, SocketConfig},
+    DefaultCodec, MessageWrapper, TypedHeader, U64Header,
+};
+
+fn config() -> ReconnectConfig {
+    ReconnectConfig {
+        base_delay: Duration::from_millis(1),
+        socket: SocketConfig {
+            nodelay: true,
+            keepalive: None,
+        },
+        ..ReconnectConfig::default()
+    }
+}
+
+#[tokio::test]
+async fn settings_and_hooks_survive_a_reconnect() {
+    let mut server = server().await;
+    let addr = server.as_listener().local_addr().unwrap();
+    let (client, first) = tokio::join!(
+        ReconnectingClient::<TypedHeader, u32, DefaultCodec>::connect(
+            addr,
+            DefaultCodec::default(),
+            config()
+        ),
+        server.accept::<TypedHeader, u32>()
+    );
+    let (mut client, first) = (client.unwrap(), first.unwrap());
+
+    let hooked = Arc::new(AtomicUsize::new(0));
+    let events = Arc::new(std::sync::Mutex::new(vec![]));
+    {
+        let hooked = hooked.clone();
+        let events = events.clone();
+        client.set_on_event(move |e| events.lock().unwrap().push(e.clone()));
+        let conn = client.client_mut();
+        conn.set_heartbeat(Some(HeartbeatConfig {
+            interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(90),
+        }))
+        .unwrap();
+        conn.set_max_lifetime(Some(Duration::from_secs(600)));
+        conn.set_flush_interval(Some(Duration::from_millis(10)));
+        let reader = conn.as_reader_mut();
+        reader.set_max_message_size(Some(64));
+        reader.set_max_total_buffered(Some(1 << 16));
+        reader.set_max_frames_per_sec(Some(1000));
+        reader.set_retain_raw_body(true);
+        reader.set_on_message(move |_| {
+            hooked.fetch_add(1, Ordering::Relaxed);
+        });
+        conn.as_writer_mut().set_max_queued_bytes(Some(1 << 12));
+    }
+    let before = client.client().config().unwrap();
+    assert!(before.nodelay);
+
+    // the server drops the first connection, and sends a message on the second one
+    drop(first);
+    let (received, second) = tokio::join!(
+        tokio::time::timeout(TIMEOUT, client.wait_for_message()),
+        async {
+            let mut second = server.accept::<TypedHeader, u32>().await.unwrap();
+            second.queue_message(&MessageWrapper::new(7)).unwrap();
+            second.flush_all().await.unwrap();
+            second
+        }
+    );
+    let received = received.unwrap().unwrap();
+    assert_eq!(*received.message(), 7);
+    assert!(received.raw_body().is_some());
+    assert_eq!(hooked.load(Ordering::Relaxed), 1);
+    assert!(events
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|e| matches!(e, ReconnectEvent::Reconnected { .. })));
+
+    let after = client.client().config().unwrap();
+    assert_eq!(
+        smalltalk::socket::EffectiveConfig {
+            recv_buffer_size: before.recv_buffer_size,
+            send_buffer_size: before.send_buffer_size,
+            ..after
+        },
+        before
+    );
+    drop(second);
+}
+
+#[tokio::test]
+async fn a_partially_received_frame_is_reported_and_not_mixed_into_the_new_connection() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (client, first) = tokio::join!(
+        ReconnectingClient::<U64Header, String, DefaultCodec>::connect(
+            addr,
+            DefaultCodec::default(),
+            config()
+        ),
+        listener.accept()
+    );
+    let (mut client, (mut first, _)) = (client.unwrap(), first.unwrap());
+    let events = Arc::new(std::sync::Mutex::new(vec![]));
+    {
+        let events = events.clone();
+        client.set_on_event(move |e| events.lock().unwrap().push(e.clone()));
+    }
+
+    // the first connection dies part way through a message
+    let lost = frame::<U64Header, _>(&"never finished".to_string());
+    write_all(&mut first, &lost[..12]).await;
+    let (received, _second) = tokio::join!(
+        tokio::time::timeout(TIMEOUT, client.wait_for_message()),
+        async {
+            // the partial frame is still received before the end of the stream
+            drop(first);
+            let (mut second, _) = listener.accept().await.unwrap();
+            write_all(&mut second, &frame::<U64Header, _>(&"fresh".to_string())).await;
+            second
+        }
+    );
+    assert_eq!(received.unwrap().unwrap().into_message(), "fresh");
+
+    let events = events.lock().unwrap();
+    let lost_at = events
+        .iter()
+        .position(|e| matches!(e, ReconnectEvent::FrameLostOnReconnect { bytes: 12 }))
+        .unwrap_or_else(|| panic!("lost frame not reported: {events:?}"));
+    let reconnected_at = events
+        .iter()
+        .position(|e| matches!(e, ReconnectEvent::Reconnected { .. }))
+        .unwrap();
+    assert!(lost_at < reconnected_at);
+}
@@ -0,0 +1,87 @@
+//! `recv_timeout` gives up after the timeout without losing anything it already read
+mod common;
+
+use std::time::Duration;
+
+use common::*;
+use smalltalk::{socket::interface::error::WaitMessageError, MessageWrapper, U64Header};
+
+#[tokio::test]
+async fn a_late_reply_times_out_then_arrives() {
+    let (mut client, mut conn) = pair::<U64Header, String>().await;
+    let peer = tokio::spawn(async move {
+        let request = recv(&mut conn).await;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        conn.queue_message(&MessageWrapper::new(format!("re: {}", request.message())))
+            .unwrap();
+        conn.flush_all().await.unwrap();
+        conn
+    });
+    client
+        .queue_message(&MessageWrapper::new("ping".into()))
+        .unwrap();
+    client.flush_all().await.unwrap();
+
+    let early = client
+        .recv_timeout(Duration::from_millis(50))
+        .await
+        .unwrap();
+    assert!(early.is_none());
+    let reply = client.recv_timeout(TIMEOUT).await.unwrap().unwrap();
+    assert_eq!(reply.message(), "re: ping");
+    let _conn = peer.await.unwrap();
+}
+
+#[tokio::test]
+async fn partial_frames_are_kept_across_a_timeout() {
+    let (mut stream, mut conn) = raw_pair::<U64Header, String>().await;
+    let bytes = frame::<U64Header, _>(&"split across a timeout".to_string());
+    let (first, rest) = bytes.split_at(bytes.len() / 2);
+
+    write_all(&mut stream, first).await;
+    assert!(conn
+        .recv_timeout(Duration::from_millis(100))
+        .await
+        .unwrap()
+        .is_none());
+    write_all(&mut stream, rest).await;
+    let msg = conn.recv_timeout(TIMEOUT).await.unwrap().unwrap();
+    assert_eq!(msg.message(), "split across a timeout");
+
+    // the peer closing is an error, not a timeout
+    drop(stream);
+    assert!(conn.recv_timeout(TIMEOUT).await.is_err());
+}
+
+#[tokio::test]
+async fn the_timeout_bounds_a_stalled_write() {
+    // the client never reads, so writing what the server queued blocks
+    let (client, mut conn) = pair::<U64Header, Vec<u8>>().await;
+    stall_writer(&client, &mut conn).await;
+    let queued = conn.as_writer().queued_messages();
+
+    let res = tokio::time::timeout(TIMEOUT, conn.recv_timeout(Duration::from_millis(50)))
+        .await
+        .expect("recv_timeout blocked on writing");
+    assert!(res.unwrap().is_none());
+    assert_eq!(conn.as_writer().queued_messages(), queued);
+}
+
+#[tokio::test]
+async fn wait_for_message_timeout_behaves_the_same() {
+    let (client, mut conn) = pair::<U64Header, Vec<u8>>().await;
+    stall_writer(&client, &mut conn).await;
+    let res = tokio::time::timeout(
+        TIMEOUT,
+        conn.wait_for_message_timeout(Duration::from_millis(50)),
+    )
+    .await
+    .expect("wait_for_message_timeout blocked on writing");
+    assert!(res.unwrap().is_none());
+
+    // with the errors of wait_for_message
+    let (stream, mut conn) = raw_pair::<U64Header, u32>().await;
+    drop(stream);
+    let err = conn.wait_for_message_timeout(TIMEOUT).await.unwrap_err();
+    assert!(matches!(err, WaitMessageError::Closed), "{err:?}");
+}
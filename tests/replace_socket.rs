@@ -0,0 +1,47 @@
+//! the socket under a reader or writer can be swapped without losing its state
+mod common;
+
+use common::*;
+use smalltalk::{MessageWrapper, U64Header};
+
+#[tokio::test]
+async fn writer_continues_on_a_new_socket() {
+    let (mut writer, mut old_peer) = writer::<U64Header, u32>().await;
+    writer.queue(&MessageWrapper::new(1)).unwrap();
+    writer.flush_all().await.unwrap();
+    // sits in the queue over the switch
+    writer.queue(&MessageWrapper::new(2)).unwrap();
+
+    let (new_socket, mut new_peer) = stream_pair().await;
+    let old = writer.replace_socket(new_socket.into_split().1);
+    drop(old);
+    writer.queue(&MessageWrapper::new(3)).unwrap();
+    writer.flush_all().await.unwrap();
+
+    let one = frame::<U64Header, _>(&1u32);
+    assert_eq!(read_exactly(&mut old_peer, one.len()).await, one);
+    let mut expected = frame::<U64Header, _>(&2u32).to_vec();
+    expected.extend_from_slice(&frame::<U64Header, _>(&3u32));
+    assert_eq!(read_exactly(&mut new_peer, expected.len()).await, expected);
+    // counters carry on
+    assert_eq!(writer.messages_sent(), 3);
+}
+
+#[tokio::test]
+async fn reader_continues_on_a_new_socket() {
+    let (mut reader, mut old_peer) = reader::<U64Header, u32>().await;
+    write_all(&mut old_peer, &frame::<U64Header, _>(&1u32)).await;
+    read_until(&mut reader, |r| r.messages_received() == 1).await;
+    assert!(reader.at_frame_boundary());
+
+    let (new_socket, mut new_peer) = stream_pair().await;
+    reader.replace_socket(new_socket.into_split().0);
+    write_all(&mut new_peer, &frame::<U64Header, _>(&2u32)).await;
+    read_until(&mut reader, |r| r.messages_received() == 2).await;
+    // the message from before the switch was kept
+    let received = reader
+        .ready_messages()
+        .map(|m| m.into_message())
+        .collect::<Vec<_>>();
+    assert_eq!(received, [1, 2]);
+}
@@ -0,0 +1,64 @@
+//! bytes captured off the wire can be fed straight into a reader to decode them
+mod common;
+
+use common::*;
+use serde::{Deserialize, Serialize};
+use smalltalk::U32Header;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Event {
+    Join { name: String },
+    Move(i32, i32),
+    Leave,
+}
+
+/// a payload captured from a connection, three frames with a big endian u32 header each
+const CAPTURE: &[u8] = &[
+    0x00, 0x00, 0x00, 0x05, // header, 5 byte body
+    0x00, 0x03, b'b', b'o', b'b', // Join { name: "bob" }
+    0x00, 0x00, 0x00, 0x03, // header, 3 byte body
+    0x01, 0x0a, 0x03, // Move(5, -2), varint zigzag
+    0x00, 0x00, 0x00, 0x01, // header, 1 byte body
+    0x02, // Leave
+];
+
+#[tokio::test]
+async fn a_captured_stream_decodes() {
+    let (mut reader, _stream) = reader::<U32Header, Event>().await;
+    reader.feed(CAPTURE);
+    while reader.update().await.unwrap() {}
+    let events = reader
+        .ready_messages()
+        .map(|m| m.into_message())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        [
+            Event::Join { name: "bob".into() },
+            Event::Move(5, -2),
+            Event::Leave
+        ]
+    );
+    // and it is what the writer would have sent
+    let framed = [
+        Event::Join { name: "bob".into() },
+        Event::Move(5, -2),
+        Event::Leave,
+    ]
+    .iter()
+    .flat_map(frame::<U32Header, _>)
+    .collect::<Vec<_>>();
+    assert_eq!(framed, CAPTURE);
+}
+
+#[tokio::test]
+async fn a_capture_cut_off_mid_frame_waits_for_the_rest() {
+    let (mut reader, _stream) = reader::<U32Header, Event>().await;
+    let (first, rest) = CAPTURE.split_at(12);
+    reader.feed(first);
+    while reader.update().await.unwrap() {}
+    assert_eq!(reader.messages_received(), 1);
+    reader.feed(rest);
+    while reader.update().await.unwrap() {}
+    assert_eq!(reader.messages_received(), 3);
+}
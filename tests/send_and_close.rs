@@ -0,0 +1,43 @@
+//! one shot notifications connect, send, and close in a single call
+mod common;
+
+use common::*;
+use smalltalk::{
+    socket::interface::error::UpdateError, Client, DefaultCodec, MessageWrapper, U64Header,
+};
+
+#[tokio::test]
+async fn the_server_gets_the_message_then_eof() {
+    let mut server = server().await;
+    let addr = server.as_listener().local_addr().unwrap();
+    let message = MessageWrapper::new("notify".to_string());
+    let (sent, conn) = tokio::join!(
+        Client::<U64Header, String, DefaultCodec>::send_and_close(
+            addr,
+            DefaultCodec::default(),
+            &message
+        ),
+        server.accept::<U64Header, String>()
+    );
+    sent.unwrap();
+    let mut conn = conn.unwrap();
+    assert_eq!(recv(&mut conn).await.message(), "notify");
+    assert!(matches!(
+        tokio::time::timeout(TIMEOUT, conn.recv_timeout(TIMEOUT))
+            .await
+            .unwrap(),
+        Err(UpdateError::Closed)
+    ));
+}
+
+#[tokio::test]
+async fn nothing_listening_is_an_error() {
+    let addr = server().await.as_listener().local_addr().unwrap();
+    let res = Client::<U64Header, String, DefaultCodec>::send_and_close(
+        addr,
+        DefaultCodec::default(),
+        &MessageWrapper::new("lost".into()),
+    )
+    .await;
+    assert!(res.is_err());
+}
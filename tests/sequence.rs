@@ -0,0 +1,185 @@
+//! sequence numbers are stamped by the writer (without copying shared bodies) and checked by the reader
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use bytes::Bytes;
+use common::*;
+use smalltalk::{
+    clock::ManualClock, socket::read::error::UpdateError, IsHeader, MessageWrapper, SequencedHeader,
+};
+
+/// A frame of `n`, numbered `seq`
+fn numbered(seq: u64, n: u32) -> Vec<u8> {
+    let frame = frame::<SequencedHeader, _>(&n);
+    let mut header =
+        SequencedHeader::from_bytes(frame.slice(..SequencedHeader::header_size())).unwrap();
+    header.set_sequence(seq);
+    [
+        &header.as_bytes()[..],
+        &frame[SequencedHeader::header_size()..],
+    ]
+    .concat()
+}
+
+/// Reads `count` frames of `u32`s from `stream`, returning their sequence numbers and messages
+async fn read_frames(stream: &mut tokio::net::TcpStream, count: usize) -> Vec<(u64, Bytes)> {
+    let mut frames = vec![];
+    for _ in 0..count {
+        let header = SequencedHeader::from_bytes(
+            read_exactly(stream, SequencedHeader::header_size())
+                .await
+                .into(),
+        )
+        .unwrap();
+        let body = read_exactly(stream, header.size() as usize).await;
+        frames.push((header.sequence().unwrap(), body.into()));
+    }
+    frames
+}
+
+#[tokio::test]
+async fn writer_numbers_messages_and_groups() {
+    let (mut writer, mut peer) = writer::<SequencedHeader, u32>().await;
+    assert_eq!(writer.send_sequence(), Some(0));
+    writer.queue(&MessageWrapper::new(10)).unwrap();
+    writer
+        .queue_group(&[MessageWrapper::new(11), MessageWrapper::new(12)])
+        .unwrap();
+    // already framed data is renumbered too
+    writer
+        .queue_raw(frame::<SequencedHeader, _>(&13u32))
+        .unwrap();
+    assert_eq!(writer.send_sequence(), Some(4));
+    writer.flush_all().await.unwrap();
+
+    let frames = read_frames(&mut peer, 4).await;
+    let seqs = frames.iter().map(|(seq, _)| *seq).collect::<Vec<_>>();
+    assert_eq!(seqs, [0, 1, 2, 3]);
+    for ((_, body), n) in frames.iter().zip(10u32..) {
+        assert_eq!(
+            body,
+            &frame::<SequencedHeader, _>(&n)[SequencedHeader::header_size()..]
+        );
+    }
+}
+
+#[tokio::test]
+async fn broadcast_numbers_the_shared_message_on_each_connection() {
+    let mut server = server().await;
+    let addr = server.as_listener().local_addr().unwrap();
+    let mut registry = server.new_registry::<SequencedHeader, u32>();
+    let mut peers = vec![];
+    for _ in 0..2 {
+        let (stream, conn) = tokio::join!(
+            tokio::net::TcpStream::connect(addr),
+            server.accept::<SequencedHeader, u32>()
+        );
+        let id = registry.insert(conn.unwrap());
+        peers.push((id, stream.unwrap()));
+    }
+    // put the first connection ahead, so the connections number the broadcast differently
+    let ahead = peers[0].0;
+    for n in 0..3 {
+        let conn = registry.get_mut(ahead).unwrap();
+        conn.queue_message(&MessageWrapper::new(n)).unwrap();
+    }
+
+    assert!(registry
+        .broadcast(&MessageWrapper::new(99))
+        .unwrap()
+        .is_empty());
+    assert!(registry.flush_all().await.is_empty());
+
+    let ahead_frames = read_frames(&mut peers[0].1, 4).await;
+    let other_frames = read_frames(&mut peers[1].1, 1).await;
+    assert_eq!(ahead_frames[3].0, 3);
+    assert_eq!(other_frames[0].0, 0);
+    let body = frame::<SequencedHeader, _>(&99u32).slice(SequencedHeader::header_size()..);
+    assert_eq!(ahead_frames[3].1, body);
+    assert_eq!(other_frames[0].1, body);
+}
+
+#[tokio::test]
+async fn reader_reports_gaps_and_drops_duplicates() {
+    let (mut reader, _peer) = reader::<SequencedHeader, u32>().await;
+    for (seq, n) in [(0, 0), (1, 1), (1, 100), (4, 4), (5, 5)] {
+        reader.feed(&numbered(seq, n));
+    }
+    assert!(matches!(
+        reader.update().await,
+        Err(UpdateError::DuplicateSequence {
+            seq: 1,
+            expected: 2
+        })
+    ));
+    assert_eq!(reader.recv_sequence(), Some(1));
+    assert!(matches!(
+        reader.update().await,
+        Err(UpdateError::SequenceGap {
+            expected: 2,
+            got: 4
+        })
+    ));
+    reader.update().await.unwrap();
+    assert_eq!(reader.recv_sequence(), Some(5));
+    let messages = reader
+        .ready_messages()
+        .map(|m| m.into_message())
+        .collect::<Vec<_>>();
+    // the duplicate was dropped, and the message after the gap was delivered
+    assert_eq!(messages, [0, 1, 4, 5]);
+}
+
+#[tokio::test]
+async fn duplicates_do_not_use_up_the_frame_rate_limit() {
+    let (mut reader, _peer) = reader::<SequencedHeader, u32>().await;
+    let clock = ManualClock::new();
+    reader.set_clock(Arc::new(clock.clone()));
+    reader.set_max_frames_per_sec(Some(1));
+    for (seq, n) in [(0, 0), (0, 100), (1, 1)] {
+        reader.feed(&numbered(seq, n));
+    }
+
+    // the duplicate is dropped without waiting for the limit, which the first message used up
+    assert!(matches!(
+        reader.update().await,
+        Err(UpdateError::DuplicateSequence {
+            seq: 0,
+            expected: 1
+        })
+    ));
+    assert_eq!(reader.messages_received(), 1);
+    reader.update().await.unwrap();
+    assert_eq!(reader.messages_received(), 1);
+    // so the token refilled by advancing the clock goes to the next real message
+    clock.advance(Duration::from_secs(1));
+    reader.update().await.unwrap();
+    assert_eq!(reader.messages_received(), 2);
+    assert_eq!(reader.recv_sequence(), Some(1));
+}
+
+#[tokio::test]
+async fn a_rate_limited_frame_is_not_counted_as_received_twice() {
+    let (mut reader, _peer) = reader::<SequencedHeader, u32>().await;
+    let clock = ManualClock::new();
+    reader.set_clock(Arc::new(clock.clone()));
+    reader.set_max_frames_per_sec(Some(1));
+    reader.feed(&numbered(0, 0));
+    reader.feed(&numbered(1, 1));
+
+    reader.update().await.unwrap();
+    // the second frame waits for the limit, without its sequence number being taken
+    reader.update().await.unwrap();
+    assert_eq!(reader.recv_sequence(), Some(0));
+    clock.advance(Duration::from_secs(1));
+    reader.update().await.unwrap();
+    assert_eq!(reader.recv_sequence(), Some(1));
+    assert_eq!(
+        reader
+            .ready_messages()
+            .map(|m| m.into_message())
+            .collect::<Vec<_>>(),
+        [0, 1]
+    );
+}
@@ -0,0 +1,123 @@
+//! serializing a message gives the same bytes as a header followed by the separately serialized body,
+//! while only serializing the body once
+mod common;
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use bytes::BytesMut;
+use common::*;
+use serde::{de::DeserializeOwned, Serialize};
+use smalltalk::{
+    codec::error::CodecError, msg, ChecksummedHeader, Codec, DefaultCodec, ExtendedHeader,
+    IsHeader, MessageWrapper, TypedHeader, U32Header, U64Header, Writer,
+};
+
+type Message = BTreeMap<u16, String>;
+
+fn messages() -> Vec<Message> {
+    vec![
+        BTreeMap::new(),
+        BTreeMap::from([(1, "one".to_string())]),
+        (0..500).map(|n| (n, "x".repeat(n.into()))).collect(),
+    ]
+}
+
+/// Frames a message the way serialize used to, by serializing the body first and building the header from it
+fn old_path<H: IsHeader>(msg: &Message, extension: &[u8], kind: u16) -> BytesMut {
+    let codec = DefaultCodec::default();
+    let body = codec.serialize(msg).unwrap();
+    let mut header = H::for_body(&body);
+    header.set_extension(extension).unwrap();
+    header.set_kind(kind).unwrap();
+    let mut buf = header.as_bytes_mut();
+    buf.extend_from_slice(&body);
+    buf
+}
+
+/// bincode, counting how many times it serializes. it has no size hint, so any sizing would show up as a serialize
+#[derive(Debug, Clone, Default)]
+struct CountingCodec {
+    serializes: Arc<AtomicUsize>,
+}
+
+impl CountingCodec {
+    fn serializes(&self) -> usize {
+        self.serializes.load(Ordering::Relaxed)
+    }
+}
+
+impl Codec for CountingCodec {
+    fn serialize<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        self.serializes.fetch_add(1, Ordering::Relaxed);
+        DefaultCodec::default().serialize(value)
+    }
+
+    fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, CodecError> {
+        DefaultCodec::default().deserialize(bytes)
+    }
+}
+
+fn matches_old_path<H: IsHeader>(extension: &[u8], kind: u16) {
+    let codec = DefaultCodec::default();
+    let counting = CountingCodec::default();
+    for msg in messages() {
+        let expected = old_path::<H>(&msg, extension, kind);
+        let mut wrapper = MessageWrapper::<Message, H>::new(msg);
+        if !extension.is_empty() {
+            wrapper.set_extension(extension.to_vec());
+        }
+        wrapper.set_kind(kind);
+
+        assert_eq!(wrapper.serialize(&codec).unwrap(), expected);
+        let before = counting.serializes();
+        assert_eq!(wrapper.serialize(&counting).unwrap(), expected);
+        assert_eq!(
+            counting.serializes() - before,
+            1,
+            "serialized more than once"
+        );
+        let mut split = BytesMut::from(&wrapper.header_bytes(&codec).unwrap()[..]);
+        split.extend_from_slice(&wrapper.serialize_self(&codec).unwrap());
+        assert_eq!(split, expected);
+    }
+}
+
+#[test]
+fn plain_headers() {
+    matches_old_path::<U32Header>(&[], 0);
+    matches_old_path::<U64Header>(&[], 0);
+    matches_old_path::<ChecksummedHeader>(&[], 0);
+}
+
+#[test]
+fn headers_with_a_kind() {
+    matches_old_path::<TypedHeader>(&[], 7);
+}
+
+#[test]
+fn headers_with_an_extension() {
+    matches_old_path::<ExtendedHeader<4>>(b"abcd", 0);
+}
+
+#[tokio::test]
+async fn framing_and_queueing_serialize_once() {
+    let codec = CountingCodec::default();
+    let msg = messages().pop().unwrap();
+    let expected = old_path::<U64Header>(&msg, &[], 0);
+
+    assert_eq!(msg::frame::<_, U64Header>(&msg, &codec).unwrap(), expected);
+    assert_eq!(codec.serializes(), 1);
+
+    let (ours, _theirs) = stream_pair().await;
+    let (_, write_half) = ours.into_split();
+    let mut writer = Writer::<U64Header, Message, _>::new(write_half, codec.clone());
+    writer.queue(&MessageWrapper::new(msg)).unwrap();
+    assert_eq!(codec.serializes(), 2);
+    assert_eq!(writer.queue_snapshot().total_bytes, expected.len());
+}
@@ -0,0 +1,35 @@
+//! time spent serializing is added up across everything queued
+mod common;
+
+use common::*;
+use smalltalk::{MessageWrapper, U64Header};
+
+#[tokio::test]
+async fn serialize_time_accumulates() {
+    let (mut writer, _stream) = writer::<U64Header, Vec<String>>().await;
+    assert_eq!(writer.serialize_nanos(), 0);
+    // big enough to take a measurable amount of time
+    let contents = vec!["some text".to_string(); 10_000];
+    let message = MessageWrapper::new(contents.clone());
+    let mut last = 0;
+    for _ in 0..5 {
+        writer.queue(&message).unwrap();
+        let total = writer.serialize_nanos();
+        assert!(total > last);
+        last = total;
+    }
+    writer
+        .queue_group(&[message, MessageWrapper::new(contents)])
+        .unwrap();
+    assert!(writer.serialize_nanos() > last);
+
+    // raw bytes are already serialized
+    let last = writer.serialize_nanos();
+    writer
+        .queue_raw(frame::<U64Header, _>(&vec!["raw".to_string()]))
+        .unwrap();
+    assert_eq!(writer.serialize_nanos(), last);
+    // and writing is not counted
+    writer.flush_all().await.unwrap();
+    assert_eq!(writer.serialize_nanos(), last);
+}
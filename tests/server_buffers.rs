@@ -0,0 +1,27 @@
+//! buffer sizes set on the server are in place on the connections it accepts
+mod common;
+
+use common::{server, Conn};
+use smalltalk::U32Header;
+use tokio::net::TcpStream;
+
+#[tokio::test]
+async fn accepted_connections_inherit_buffer_sizes() {
+    let mut server = server().await;
+    let default_recv = server.recv_buffer_size().unwrap();
+    let default_send = server.send_buffer_size().unwrap();
+    // something small, so it does not hit the OS maximum
+    server.set_recv_buffer_size(default_recv / 4).unwrap();
+    server.set_send_buffer_size(default_send / 4).unwrap();
+    let recv = server.recv_buffer_size().unwrap();
+    let send = server.send_buffer_size().unwrap();
+    assert_ne!(recv, default_recv);
+    assert_ne!(send, default_send);
+
+    let addr = server.as_listener().local_addr().unwrap();
+    let (stream, conn) = tokio::join!(TcpStream::connect(addr), server.accept::<U32Header, u32>());
+    let (_stream, conn): (_, Conn<U32Header, u32>) = (stream.unwrap(), conn.unwrap());
+    assert_eq!(conn.recv_buffer_size().unwrap(), recv);
+    assert_eq!(conn.send_buffer_size().unwrap(), send);
+    assert_eq!(server.setup_failures(), 0);
+}
@@ -0,0 +1,100 @@
+//! a client reconnecting with its token resumes its session on the server
+mod common;
+
+use common::*;
+use smalltalk::{
+    session::{ResumeToken, SessionRegistry},
+    Client, DefaultCodec, MessageWrapper, U64Header,
+};
+
+/// what the server remembers about each client
+#[derive(Debug, Default)]
+struct Session {
+    received: Vec<u32>,
+}
+
+#[tokio::test]
+async fn reconnecting_with_the_token_resumes_the_session() {
+    let mut server = server().await;
+    let addr = server.as_listener().local_addr().unwrap();
+    let mut sessions = SessionRegistry::<Session>::new();
+
+    let mut token = None;
+    for (round, messages) in [[1, 2], [3, 4], [5, 6]].into_iter().enumerate() {
+        let (client, accepted) = tokio::join!(
+            Client::<U64Header, u32, DefaultCodec>::connect_session(
+                addr,
+                DefaultCodec::default(),
+                token
+            ),
+            server.accept_session::<U64Header, u32, _>(&mut sessions, Session::default)
+        );
+        let (mut client, client_start) = client.unwrap();
+        let (mut conn, server_start) = accepted.unwrap();
+        assert_eq!(client_start, server_start);
+        assert_eq!(client_start.resumed, round > 0);
+        if let Some(token) = token {
+            assert_eq!(client_start.token, token);
+        }
+        token = Some(client_start.token);
+
+        // the connection works normally after the handshake
+        for n in messages {
+            client.queue_message(&MessageWrapper::new(n)).unwrap();
+        }
+        client.flush_all().await.unwrap();
+        for _ in messages {
+            let msg = recv(&mut conn).await.into_message();
+            sessions
+                .get_mut(&server_start.token)
+                .unwrap()
+                .received
+                .push(msg);
+        }
+    }
+
+    // one session, holding everything sent over all three connections
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(
+        sessions.get(&token.unwrap()).unwrap().received,
+        [1, 2, 3, 4, 5, 6]
+    );
+}
+
+#[tokio::test]
+async fn an_unknown_token_starts_a_new_session() {
+    let mut server = server().await;
+    let addr = server.as_listener().local_addr().unwrap();
+    let mut sessions = SessionRegistry::<Session>::new();
+
+    let stale = ResumeToken::generate();
+    let (client, accepted) = tokio::join!(
+        Client::<U64Header, u32, DefaultCodec>::connect_session(
+            addr,
+            DefaultCodec::default(),
+            Some(stale)
+        ),
+        server.accept_session::<U64Header, u32, _>(&mut sessions, Session::default)
+    );
+    let (_client, start) = client.unwrap();
+    accepted.unwrap();
+    assert!(!start.resumed);
+    assert_ne!(start.token, stale);
+    assert!(sessions.get(&stale).is_none());
+    assert!(sessions.get(&start.token).is_some());
+
+    // a session that was removed can not be resumed either
+    sessions.remove(&start.token).unwrap();
+    let (client, accepted) = tokio::join!(
+        Client::<U64Header, u32, DefaultCodec>::connect_session(
+            addr,
+            DefaultCodec::default(),
+            Some(start.token)
+        ),
+        server.accept_session::<U64Header, u32, _>(&mut sessions, Session::default)
+    );
+    let (_client, restart) = client.unwrap();
+    accepted.unwrap();
+    assert!(!restart.resumed);
+    assert_eq!(sessions.len(), 1);
+}
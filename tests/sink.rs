@@ -0,0 +1,52 @@
+//! decoded messages can go straight into a channel instead of being stored in the reader
+mod common;
+
+use common::*;
+use smalltalk::{
+    socket::{interface::error::UpdateError as ConnError, read::error::UpdateError},
+    MessageWrapper, U64Header,
+};
+use tokio::sync::mpsc;
+
+#[tokio::test]
+async fn messages_are_received_through_the_channel() {
+    let (mut client, mut conn) = pair::<U64Header, u32>().await;
+    let (tx, mut rx) = mpsc::channel(4);
+    conn.as_reader_mut().set_sink(Some(tx));
+    let reader = tokio::spawn(async move {
+        loop {
+            conn.update_read().await.unwrap();
+            if let Err(e) = conn.update().await {
+                break e;
+            }
+            // nothing is ever stored to be retrieved
+            assert!(conn.get_latest_message().is_none());
+        }
+    });
+
+    // more than the channel holds, so the reader has to wait for the consumer
+    for n in 0..20 {
+        client.queue_message(&MessageWrapper::new(n)).unwrap();
+    }
+    client.flush_all().await.unwrap();
+    for n in 0..20 {
+        let msg = tokio::time::timeout(TIMEOUT, rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(msg.into_message(), n);
+    }
+
+    // the consumer going away is reported, instead of dropping messages silently
+    drop(rx);
+    client.queue_message(&MessageWrapper::new(20)).unwrap();
+    client.flush_all().await.unwrap();
+    let err = tokio::time::timeout(TIMEOUT, reader)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(
+        err,
+        ConnError::ReadUpdate(UpdateError::SinkClosed)
+    ));
+}
@@ -0,0 +1,42 @@
+//! a raw accepted stream can be peeked at before deciding to frame it
+mod common;
+
+use common::*;
+use smalltalk::{server::ClientConnection, DefaultCodec, MessageWrapper, U64Header};
+use tokio::net::TcpStream;
+
+/// a marker a different protocol on the same port might start with
+const OTHER_PROTOCOL: &[u8] = b"\x16\x03\x01";
+
+#[tokio::test]
+async fn peek_then_frame() {
+    let mut server = server().await;
+    let addr = server.as_listener().local_addr().unwrap();
+    let (peer, accepted) = tokio::join!(TcpStream::connect(addr), server.accept_raw());
+    let (mut peer, (stream, client_addr)) = (peer.unwrap(), accepted.unwrap());
+    assert_eq!(client_addr, peer.local_addr().unwrap());
+
+    let bytes = frame::<U64Header, _>(&"framed".to_string());
+    write_all(&mut peer, &bytes).await;
+    let mut first = [0; 3];
+    let peeked = tokio::time::timeout(TIMEOUT, stream.peek(&mut first))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(first[..peeked], bytes[..peeked]);
+    assert_ne!(&first[..], OTHER_PROTOCOL);
+
+    // peeking did not consume anything, so the framed connection gets the whole message
+    let mut conn = ClientConnection::<U64Header, String, _>::from_stream(
+        stream,
+        client_addr,
+        DefaultCodec::default(),
+    );
+    assert_eq!(conn.addr(), client_addr);
+    assert_eq!(recv(&mut conn).await.message(), "framed");
+    conn.queue_message(&MessageWrapper::new("reply".into()))
+        .unwrap();
+    conn.flush_all().await.unwrap();
+    let reply = frame::<U64Header, _>(&"reply".to_string());
+    assert_eq!(read_exactly(&mut peer, reply.len()).await, reply);
+}
@@ -0,0 +1,44 @@
+//! messages past the in memory threshold are spilled to disk, and still sent in order
+mod common;
+
+use common::*;
+use smalltalk::{MessageWrapper, U64Header};
+use tokio::io::AsyncReadExt;
+
+#[tokio::test]
+async fn spilled_messages_are_delivered_in_order() {
+    let (mut writer, mut peer) = writer::<U64Header, Vec<u32>>().await;
+    let message = |n: u32| vec![n; 100];
+    let size = frame::<U64Header, _>(&message(0)).len();
+    writer.enable_spillover(size * 3).unwrap();
+
+    let mut expected = Vec::new();
+    for n in 0..50 {
+        writer.queue(&MessageWrapper::new(message(n))).unwrap();
+        expected.extend_from_slice(&frame::<U64Header, _>(&message(n)));
+    }
+    // only the first few stay in memory
+    assert_eq!(writer.queued_messages(), 50);
+    assert_eq!(writer.spilled_messages(), 47);
+    assert_eq!(writer.spilled_bytes(), size * 47);
+    assert_eq!(writer.queue_snapshot().total_bytes, size * 50);
+
+    // queueing while spilled messages are being read back keeps the order
+    writer.write().await.unwrap();
+    writer.queue(&MessageWrapper::new(message(50))).unwrap();
+    expected.extend_from_slice(&frame::<U64Header, _>(&message(50)));
+
+    let peer = tokio::spawn(async move {
+        let mut received = Vec::new();
+        peer.read_to_end(&mut received).await.unwrap();
+        received
+    });
+    assert_eq!(writer.flush_all().await.unwrap(), 50);
+    assert_eq!(writer.spilled_messages(), 0);
+    writer.close_after_flush().await.unwrap();
+    let received = tokio::time::timeout(TIMEOUT, peer).await.unwrap().unwrap();
+    assert!(
+        received == expected,
+        "the bytes received do not match what was queued"
+    );
+}
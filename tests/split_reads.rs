@@ -0,0 +1,82 @@
+//! frames split across reads at awkward points are put back together without losing any bytes
+mod common;
+
+use common::*;
+use smalltalk::{IsHeader, U64Header};
+
+/// Reads untill `len` bytes in total have been read, without decoding anything
+async fn read_bytes<H, M>(reader: &mut smalltalk::Reader<H, M, smalltalk::DefaultCodec>, len: u64)
+where
+    H: IsHeader + Clone,
+    M: serde::Serialize + serde::de::DeserializeOwned,
+{
+    tokio::time::timeout(TIMEOUT, async {
+        while reader.bytes_read() < len {
+            reader.read().await.unwrap();
+        }
+    })
+    .await
+    .expect("timed out reading");
+}
+
+#[tokio::test]
+async fn header_and_partial_body_then_rest_of_body() {
+    let (mut reader, mut peer) = reader::<U64Header, String>().await;
+    let msg = String::from("a body long enough to be split in two");
+    let bytes = frame::<U64Header, _>(&msg);
+    let first = U64Header::header_size() + 4;
+
+    write_all(&mut peer, &bytes[..first]).await;
+    read_bytes(&mut reader, first as u64).await;
+    reader.update().await.unwrap();
+    assert_eq!(reader.messages_received(), 0);
+    // the 4 body bytes read along with the header are kept
+    assert_eq!(reader.partial_frame_bytes(), first);
+    assert!(!reader.at_frame_boundary());
+
+    write_all(&mut peer, &bytes[first..]).await;
+    read_until(&mut reader, |r| r.messages_received() == 1).await;
+    assert_eq!(reader.latest_message().unwrap().into_message(), msg);
+    assert_eq!(reader.partial_frame_bytes(), 0);
+    assert!(reader.at_frame_boundary());
+}
+
+#[tokio::test]
+async fn every_split_point_of_two_frames() {
+    let first_msg = String::from("first");
+    let second_msg = String::from("second message");
+    let mut bytes = frame::<U64Header, _>(&first_msg).to_vec();
+    bytes.extend_from_slice(&frame::<U64Header, _>(&second_msg));
+
+    for split in 1..bytes.len() {
+        let (mut reader, mut peer) = reader::<U64Header, String>().await;
+        write_all(&mut peer, &bytes[..split]).await;
+        read_bytes(&mut reader, split as u64).await;
+        reader.update().await.unwrap();
+        write_all(&mut peer, &bytes[split..]).await;
+        read_until(&mut reader, |r| r.messages_received() == 2).await;
+
+        let messages = reader
+            .ready_messages()
+            .map(|m| m.into_message())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [first_msg.clone(), second_msg.clone()],
+            "split at {split}"
+        );
+        assert_eq!(reader.partial_frame_bytes(), 0, "split at {split}");
+    }
+}
+
+#[tokio::test]
+async fn fed_byte_by_byte() {
+    let (mut reader, _peer) = reader::<U64Header, String>().await;
+    let msg = String::from("one byte at a time");
+    for byte in frame::<U64Header, _>(&msg).iter() {
+        assert_eq!(reader.messages_received(), 0);
+        reader.feed(&[*byte]);
+        reader.update().await.unwrap();
+    }
+    assert_eq!(reader.latest_message().unwrap().into_message(), msg);
+}
@@ -0,0 +1,83 @@
+//! `update_with` hands the body to the caller, so it can be deserialized into storage that is reused
+mod common;
+
+use common::*;
+use serde::Deserialize;
+use smalltalk::{socket::read::error::UpdateWithError, IsHeader, U64Header};
+
+/// Deserializes `body` into `into`, reusing its allocation
+fn deserialize_in_place(body: &[u8], into: &mut Vec<u32>) -> bincode::Result<usize> {
+    let mut de = bincode::Deserializer::from_slice(body, bincode::DefaultOptions::new());
+    Vec::deserialize_in_place(&mut de, into)?;
+    Ok(into.len())
+}
+
+#[tokio::test]
+async fn bodies_are_deserialized_into_a_reused_buffer() {
+    let (mut reader, mut stream) = reader::<U64Header, Vec<u32>>().await;
+    let sent = [vec![1, 2, 3], vec![], (0..64).collect(), vec![u32::MAX]];
+    for values in &sent {
+        write_all(&mut stream, &frame::<U64Header, _>(values)).await;
+    }
+
+    let mut scratch = Vec::with_capacity(64);
+    let allocation = scratch.as_ptr();
+    let mut received = 0;
+    tokio::time::timeout(TIMEOUT, async {
+        while received < sent.len() {
+            match reader
+                .update_with(|body| deserialize_in_place(body, &mut scratch))
+                .await
+            {
+                Ok(Some(len)) => {
+                    assert_eq!(scratch, sent[received]);
+                    assert_eq!(len, sent[received].len());
+                    received += 1;
+                }
+                Ok(None) => {
+                    reader.read().await.unwrap();
+                }
+                Err(e) => panic!("{e}"),
+            }
+        }
+    })
+    .await
+    .unwrap();
+    // nothing was allocated for any of them
+    assert_eq!(scratch.as_ptr(), allocation);
+    // and none of them were stored as messages
+    assert_eq!(reader.ready_messages().count(), 0);
+}
+
+#[tokio::test]
+async fn processing_errors_are_returned_and_counted() {
+    let (mut reader, mut stream) = reader::<U64Header, Vec<u32>>().await;
+    // a length prefix with no values after it
+    let mut bad = U64Header::new(1).as_bytes().to_vec();
+    bad.push(5);
+    write_all(&mut stream, &bad).await;
+    write_all(&mut stream, &frame::<U64Header, _>(&vec![7u32])).await;
+
+    let mut scratch = Vec::new();
+    let mut results = Vec::new();
+    tokio::time::timeout(TIMEOUT, async {
+        while results.len() < 2 {
+            match reader
+                .update_with(|body| deserialize_in_place(body, &mut scratch))
+                .await
+            {
+                Ok(None) => {
+                    reader.read().await.unwrap();
+                }
+                res => results.push(res),
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert!(matches!(results[0], Err(UpdateWithError::Process(_))));
+    // the bad frame was skipped, so the next one is still read
+    assert!(matches!(results[1], Ok(Some(1))));
+    assert_eq!(scratch, [7]);
+    assert_eq!(reader.decode_errors().deserialize_errors, 1);
+}
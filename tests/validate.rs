@@ -0,0 +1,53 @@
+//! serialization can be checked before connecting
+use serde::{Deserialize, Serialize, Serializer};
+use smalltalk::{Client, Codec, DefaultCodec, DepthLimited, U64Header};
+
+/// a field serde can not serialize, like a handle to something in this process
+#[derive(Debug, Default, Deserialize)]
+struct Handle;
+
+impl Serialize for Handle {
+    fn serialize<S: Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+        Err(serde::ser::Error::custom("handles can not be sent"))
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Good {
+    name: String,
+    values: Vec<u16>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Bad {
+    name: String,
+    handle: Handle,
+}
+
+#[test]
+fn a_serializable_type_reports_its_size() {
+    let codec = DefaultCodec::default();
+    let sample = Good {
+        name: "sample".into(),
+        values: vec![1, 2, 3],
+    };
+    let size = Client::<U64Header, Good, _>::validate_options(&sample, &codec).unwrap();
+    assert_eq!(size, codec.serialize(&sample).unwrap().len() as u64);
+}
+
+#[test]
+fn unserializable_fields_are_caught() {
+    let err =
+        Client::<U64Header, Bad, _>::validate_options(&Bad::default(), &DefaultCodec::default())
+            .unwrap_err();
+    assert!(err.to_string().contains("handles can not be sent"), "{err}");
+}
+
+#[test]
+fn misconfigured_codecs_are_caught() {
+    // fine with the default codec, but nested deeper than this one allows
+    let nested = vec![vec![vec![0u8]]];
+    assert!(Client::<U64Header, _, _>::validate_options(&nested, &DefaultCodec::default()).is_ok());
+    let strict = DepthLimited::new(DefaultCodec::default(), 2);
+    assert!(Client::<U64Header, _, _>::validate_options(&nested, &strict).is_err());
+}
@@ -0,0 +1,78 @@
+//! a writer can be used as a sink of messages, fed from other streams
+mod common;
+
+use common::*;
+use futures::{stream, SinkExt, StreamExt};
+use smalltalk::{
+    socket::read::ReadStatus, DefaultCodec, MessageWrapper, Reader, U64Header, Writer,
+};
+
+/// A writer and a reader, connected to each other
+async fn writer_and_reader() -> (
+    Writer<U64Header, String, DefaultCodec>,
+    Reader<U64Header, String, DefaultCodec>,
+) {
+    let (ours, theirs) = stream_pair().await;
+    let (_, write_half) = ours.into_split();
+    let (read_half, _) = theirs.into_split();
+    (
+        Writer::new(write_half, DefaultCodec::default()),
+        Reader::new(read_half, DefaultCodec::default()),
+    )
+}
+
+#[tokio::test]
+async fn a_stream_forwarded_into_the_sink_round_trips() {
+    let (mut writer, reader) = writer_and_reader().await;
+    let words = ["forwarded", "into", "the", "sink"];
+
+    tokio::time::timeout(
+        TIMEOUT,
+        stream::iter(words)
+            .map(|word| Ok(MessageWrapper::new(word.to_string())))
+            .forward(&mut writer),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    // forwarding closes the sink when done, so the reader sees the end of the stream
+    let received = tokio::time::timeout(TIMEOUT, reader.into_stream().collect::<Vec<_>>())
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|msg| msg.unwrap().into_message())
+        .collect::<Vec<_>>();
+    assert_eq!(received, words);
+    assert_eq!(writer.messages_sent(), words.len() as u64);
+}
+
+#[tokio::test]
+async fn send_flushes_each_message() {
+    let (mut writer, mut reader) = writer_and_reader().await;
+    for word in ["one", "two"] {
+        tokio::time::timeout(TIMEOUT, writer.send(MessageWrapper::new(word.to_string())))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(writer.queued_messages(), 0);
+        let msg = tokio::time::timeout(TIMEOUT, async {
+            loop {
+                assert_eq!(reader.read().await.unwrap(), ReadStatus::Open);
+                reader.update().await.unwrap();
+                if let Some(msg) = reader.latest_message() {
+                    break msg;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(msg.into_message(), word);
+    }
+
+    SinkExt::close(&mut writer).await.unwrap();
+    let status = tokio::time::timeout(TIMEOUT, reader.read())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(status, ReadStatus::Closed);
+}